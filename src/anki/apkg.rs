@@ -0,0 +1,429 @@
+//! `.apkg` packages: a collection in Anki's legacy SQLite schema, the media
+//! files numbered from 0, and a `media` map from those numbers to file names.
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveTime, Utc};
+use rusqlite::Connection;
+use serde_json::{json, Map, Value};
+use sha1::{Digest, Sha1};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::{anki_tag, html, Note};
+use crate::cache::fnv1a;
+use crate::dict::online::fetch_pronunciation;
+
+/// Fixed so that exporting again updates the same note type.
+const MODEL_ID: i64 = 1_702_912_403_217;
+
+const FIELDS: [&str; 5] = ["Word", "Phonetic", "Definition", "Example", "Audio"];
+
+const FRONT: &str = r#"<div class="word">{{Word}}</div>
+<div class="phonetic">{{Phonetic}}</div>
+{{Audio}}"#;
+
+const BACK: &str = r#"{{FrontSide}}
+<hr id="answer">
+<div class="definition">{{Definition}}</div>
+{{#Example}}<div class="example">{{Example}}</div>{{/Example}}"#;
+
+const CSS: &str = ".card { font-family: arial; font-size: 20px; text-align: center; }
+.word { font-size: 32px; font-weight: bold; }
+.phonetic { color: gray; }
+.definition { text-align: left; }
+.example { text-align: left; font-style: italic; margin-top: 1em; }";
+
+/// Schema version 11, which every Anki release since 2.1 imports.
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null,
+    scm integer not null, ver integer not null, dty integer not null,
+    usn integer not null, ls integer not null, conf text not null,
+    models text not null, decks text not null, dconf text not null, tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null,
+    mod integer not null, usn integer not null, tags text not null, flds text not null,
+    sfld integer not null, csum integer not null, flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null,
+    ord integer not null, mod integer not null, usn integer not null,
+    type integer not null, queue integer not null, due integer not null,
+    ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null,
+    odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null,
+    ease integer not null, ivl integer not null, lastIvl integer not null,
+    factor integer not null, time integer not null, type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+/// Derived from the name, so that exporting again fills the same deck.
+fn deck_id(deck: &str) -> i64 {
+    (fnv1a(deck.as_bytes()) >> 12) as i64
+}
+
+fn deck(id: i64, name: &str, now: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "desc": "",
+        "mod": now,
+        "usn": -1,
+        "collapsed": false,
+        "browserCollapsed": false,
+        "dyn": 0,
+        "conf": 1,
+        "extendNew": 10,
+        "extendRev": 50,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+    })
+}
+
+fn model(deck_id: i64, now: i64) -> Value {
+    let fields: Vec<_> = FIELDS
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            json!({
+                "name": name,
+                "ord": i,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": [],
+            })
+        })
+        .collect();
+    json!({
+        "id": MODEL_ID,
+        "name": "dioxionary",
+        "type": 0,
+        "mod": now,
+        "usn": -1,
+        "sortf": 0,
+        "did": deck_id,
+        "tmpls": [{
+            "name": "Card 1",
+            "ord": 0,
+            "qfmt": FRONT,
+            "afmt": BACK,
+            "did": null,
+            "bqfmt": "",
+            "bafmt": "",
+        }],
+        "flds": fields,
+        "css": CSS,
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}",
+        "tags": [],
+        "vers": [],
+        "req": [[0, "any", [0]]],
+    })
+}
+
+fn deck_config() -> Value {
+    json!({
+        "1": {
+            "id": 1,
+            "name": "Default",
+            "mod": 0,
+            "usn": 0,
+            "maxTaken": 60,
+            "autoplay": true,
+            "timer": 0,
+            "replayq": true,
+            "dyn": false,
+            "new": {
+                "delays": [1, 10],
+                "ints": [1, 4, 7],
+                "initialFactor": 2500,
+                "order": 1,
+                "perDay": 20,
+                "bury": true,
+                "separate": true,
+            },
+            "rev": {
+                "perDay": 200,
+                "ease4": 1.3,
+                "fuzz": 0.05,
+                "ivlFct": 1,
+                "maxIvl": 36500,
+                "minSpace": 1,
+                "bury": true,
+            },
+            "lapse": {
+                "delays": [10],
+                "mult": 0,
+                "minInt": 1,
+                "leechFails": 8,
+                "leechAction": 0,
+            },
+        }
+    })
+}
+
+/// The first 32 bits of the SHA-1 of the sort field.
+fn checksum(field: &str) -> u32 {
+    let digest = Sha1::digest(field.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+/// A file name for the pronunciation of `word`, unique among `used`.
+fn media_name(word: &str, used: &mut HashSet<String>) -> String {
+    let stem: String = word
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let mut name = format!("dioxionary-{}.mp3", stem);
+    let mut i = 2;
+    while !used.insert(name.clone()) {
+        name = format!("dioxionary-{}-{}.mp3", stem, i);
+        i += 1;
+    }
+    name
+}
+
+/// Fetch the pronunciations of the notes, skipping those that fail.
+fn fetch_media(notes: &[Note]) -> Vec<Option<(String, Vec<u8>)>> {
+    let mut used = HashSet::new();
+    notes
+        .iter()
+        .map(|note| match fetch_pronunciation(&note.word) {
+            Ok(audio) => Some((media_name(&note.word, &mut used), audio)),
+            Err(e) => {
+                eprintln!("Failed to fetch the pronunciation of {}: {}", note.word, e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn write_collection(
+    conn: &Connection,
+    notes: &[Note],
+    media: &[Option<(String, Vec<u8>)>],
+    deck: &str,
+) -> Result<()> {
+    let now = Utc::now();
+    let (seconds, millis) = (now.timestamp(), now.timestamp_millis());
+    let crt = Local::now()
+        .with_time(NaiveTime::MIN)
+        .earliest()
+        .map_or(seconds, |x| x.timestamp());
+    let did = deck_id(deck);
+    let mut decks = Map::new();
+    decks.insert("1".to_owned(), self::deck(1, "Default", seconds));
+    decks.insert(did.to_string(), self::deck(did, deck, seconds));
+    let conf = json!({
+        "activeDecks": [did],
+        "curDeck": did,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "curModel": MODEL_ID,
+        "nextPos": notes.len() + 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+    });
+
+    conn.execute_batch(SCHEMA)?;
+    conn.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        (
+            crt,
+            millis,
+            conf.to_string(),
+            json!({ MODEL_ID.to_string(): model(did, seconds) }).to_string(),
+            Value::Object(decks).to_string(),
+            deck_config().to_string(),
+        ),
+    )?;
+
+    let mut insert_note =
+        conn.prepare("INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')")?;
+    let mut insert_card = conn.prepare(
+        "INSERT INTO cards VALUES (?1, ?2, ?3, 0, ?4, -1, 0, 0, ?5, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+    )?;
+    for (i, (note, media)) in notes.iter().zip(media).enumerate() {
+        let id = millis + i as i64;
+        let audio = media
+            .as_ref()
+            .map(|(name, _)| format!("[sound:{}]", name))
+            .unwrap_or_default();
+        let fields = [
+            html(&note.word),
+            html(&note.phonetic),
+            html(&note.definition),
+            html(&note.example),
+            audio,
+        ];
+        let tags: Vec<_> = note.tags.iter().map(|x| anki_tag(x)).collect();
+        let tags = if tags.is_empty() {
+            String::new()
+        } else {
+            format!(" {} ", tags.join(" "))
+        };
+        insert_note.execute((
+            id,
+            format!("dioxionary-{:x}", fnv1a(note.word.as_bytes())),
+            MODEL_ID,
+            seconds,
+            tags,
+            fields.join("\x1f"),
+            &note.word,
+            checksum(&note.word),
+        ))?;
+        insert_card.execute((id, id, did, seconds, i + 1))?;
+    }
+    Ok(())
+}
+
+/// Write the notes to an `.apkg` package, with their pronunciations if `audio`.
+pub fn write_apkg(notes: &[Note], deck: &str, audio: bool, path: &Path) -> Result<()> {
+    let media = if audio {
+        fetch_media(notes)
+    } else {
+        vec![None; notes.len()]
+    };
+
+    // SQLite only writes to files, so the collection is built in a temporary one.
+    let collection_path = std::env::temp_dir().join(format!(
+        "dioxionary-{}-{}.anki2",
+        std::process::id(),
+        Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ));
+    let collection = Connection::open(&collection_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|conn| {
+            write_collection(&conn, notes, &media, deck)?;
+            drop(conn);
+            Ok(fs::read(&collection_path)?)
+        });
+    let _ = fs::remove_file(&collection_path);
+    let collection = collection.with_context(|| "Failed to write the Anki collection")?;
+
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut add = |name: &str, data: &[u8]| -> Result<()> {
+        zip.start_file(name, options)?;
+        zip.write_all(data)?;
+        Ok(())
+    };
+    add("collection.anki2", &collection)?;
+    let mut map = Map::new();
+    let files: Vec<_> = media.iter().flatten().collect();
+    for (i, (name, _)) in files.iter().enumerate() {
+        map.insert(i.to_string(), Value::String(name.clone()));
+    }
+    add("media", Value::Object(map).to_string().as_bytes())?;
+    for (i, (_, audio)) in files.iter().enumerate() {
+        add(&i.to_string(), audio)?;
+    }
+    zip.finish()?.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    /// Read the files of an archive in order.
+    fn unzip(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut file = archive.by_index(i).unwrap();
+                let mut content = Vec::new();
+                file.read_to_end(&mut content).unwrap();
+                (file.name().to_owned(), content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sort_field_checksum() {
+        assert_eq!(checksum("rust"), 0x7b41f80f);
+    }
+
+    #[test]
+    fn write_package() {
+        let notes = vec![
+            Note {
+                word: "rust".to_owned(),
+                phonetic: "UK /rʌst/".to_owned(),
+                definition: "n. 铁锈".to_owned(),
+                tags: vec!["CET4".to_owned(), "Book: Dune".to_owned()],
+                ..Default::default()
+            },
+            Note {
+                word: "crate".to_owned(),
+                ..Default::default()
+            },
+        ];
+        let path = std::env::temp_dir().join(format!("dioxionary-{}.apkg", std::process::id()));
+        write_apkg(&notes, "English", false, &path).unwrap();
+        let files = unzip(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(files[1], ("media".to_owned(), b"{}".to_vec()));
+
+        let collection_path = path.with_extension("anki2");
+        fs::write(&collection_path, &files[0].1).unwrap();
+        let conn = Connection::open(&collection_path).unwrap();
+        let (flds, tags, csum): (String, String, u32) = conn
+            .query_row(
+                "SELECT flds, tags, csum FROM notes WHERE sfld = 'rust'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(flds, "rust\x1fUK /rʌst/\x1fn. 铁锈\x1f\x1f");
+        assert_eq!(tags, " CET4 Book:_Dune ");
+        assert_eq!(csum, checksum("rust"));
+        let (cards, did): (i64, i64) = conn
+            .query_row("SELECT COUNT(*), MAX(did) FROM cards", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((cards, did), (2, deck_id("English")));
+        let decks: String = conn
+            .query_row("SELECT decks FROM col", [], |row| row.get(0))
+            .unwrap();
+        let decks: Value = serde_json::from_str(&decks).unwrap();
+        assert_eq!(decks[did.to_string()]["name"], "English");
+        drop(conn);
+        fs::remove_file(&collection_path).unwrap();
+
+        let mut used = HashSet::new();
+        assert_eq!(
+            media_name("rock 'n' roll", &mut used),
+            "dioxionary-rock__n__roll.mp3"
+        );
+        assert_eq!(
+            media_name("rock 'n' roll", &mut used),
+            "dioxionary-rock__n__roll-2.mp3"
+        );
+    }
+}
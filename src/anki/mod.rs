@@ -0,0 +1,312 @@
+//! Export looked-up words to [Anki](https://apps.ankiweb.net), as an `.apkg`
+//! package or a TSV file for Anki's text importer.
+mod apkg;
+
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use rusqlite::Connection;
+use std::io::Write;
+use std::path::Path;
+
+use crate::dict::{Example, LookUpResultItem};
+use crate::history::day_range;
+
+pub use apkg::write_apkg;
+
+/// Anki notes are exported to this deck unless another is given.
+pub const DEFAULT_DECK: &str = "dioxionary";
+
+/// The file an export is written to.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnkiFormat {
+    /// A package with the note type, deck and pronunciations.
+    Apkg,
+    /// Tab-separated fields, read by Anki's text importer.
+    Tsv,
+}
+
+impl AnkiFormat {
+    /// Guess the format from the file extension, falling back to a package.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("tsv") || ext.eq_ignore_ascii_case("txt") => {
+                Self::Tsv
+            }
+            _ => Self::Apkg,
+        }
+    }
+}
+
+/// Which history records are exported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportFilter {
+    /// Only words with this tag, e.g. a difficulty level or a word list.
+    pub tag: Option<String>,
+    /// Only words first looked up on or after this day.
+    pub since: Option<NaiveDate>,
+    /// Only words first looked up on or before this day.
+    pub until: Option<NaiveDate>,
+    /// Only words looked up at least this many times.
+    pub min_lookups: u32,
+}
+
+/// The fields of one exported word.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Note {
+    pub word: String,
+    pub phonetic: String,
+    pub definition: String,
+    /// The sentence the word was met in, or else an example of the dictionary.
+    pub example: String,
+    pub tags: Vec<String>,
+}
+
+/// The history records passing `filter`, in the order they were first looked up.
+pub fn notes(conn: &Connection, filter: &ExportFilter) -> Result<Vec<Note>> {
+    let (since, until) = day_range(filter.since, filter.until);
+    let mut stmt = conn.prepare(
+        "SELECT H.WORD, H.DEFINITION, H.ENTRY,
+        (SELECT GROUP_CONCAT(TAG, CHAR(31)) FROM TAGS T WHERE T.WORD = H.WORD),
+        (SELECT SENTENCE FROM CONTEXT C WHERE C.WORD = H.WORD ORDER BY DATE LIMIT 1)
+        FROM HISTORY H
+        WHERE (?1 IS NULL OR H.WORD IN (SELECT WORD FROM TAGS WHERE TAG = ?1))
+        AND (?2 IS NULL OR H.DATE >= ?2) AND (?3 IS NULL OR H.DATE < ?3)
+        AND H.LOOKUPS >= ?4
+        ORDER BY H.DATE, H.WORD",
+    )?;
+    let rows = stmt.query_map(
+        (filter.tag.as_deref(), since, until, filter.min_lookups),
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        },
+    )?;
+
+    let mut notes = Vec::new();
+    for row in rows {
+        let (word, definition, entry, tags, sentence) = row?;
+        // Entries of records older than `ENTRY`, or unreadable, leave only the definition.
+        let entry: LookUpResultItem = entry
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default();
+        let example = match sentence {
+            Some(sentence) => sentence,
+            None => first_example(&entry)
+                .map(render_example)
+                .unwrap_or_default(),
+        };
+        let mut tags: Vec<String> = tags
+            .map(|x| x.split('\x1f').map(str::to_owned).collect())
+            .unwrap_or_default();
+        tags.sort();
+        notes.push(Note {
+            phonetic: entry.phonetics.to_string(),
+            definition: brief_definition(&entry).or(definition).unwrap_or_default(),
+            example,
+            tags,
+            word,
+        });
+    }
+    Ok(notes)
+}
+
+/// The senses of a structured entry, one part of speech per line, leaving
+/// phonetics and examples to their own fields.
+fn brief_definition(entry: &LookUpResultItem) -> Option<String> {
+    let lines: Vec<_> = entry
+        .parts_of_speech
+        .iter()
+        .map(|pos| {
+            let senses: Vec<_> = pos.senses.iter().map(|x| x.definition.trim()).collect();
+            format!("{} {}", pos.pos, senses.join("; "))
+                .trim()
+                .to_owned()
+        })
+        .collect();
+    if lines.is_empty() {
+        (!entry.translation.trim().is_empty()).then(|| entry.translation.clone())
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+fn first_example(entry: &LookUpResultItem) -> Option<&Example> {
+    entry.examples.first().or_else(|| {
+        entry
+            .parts_of_speech
+            .iter()
+            .chain(&entry.detailed_senses)
+            .flat_map(|x| &x.senses)
+            .flat_map(|x| &x.examples)
+            .next()
+    })
+}
+
+fn render_example(example: &Example) -> String {
+    match &example.translation {
+        Some(translation) => format!("{}\n{}", example.text, translation),
+        None => example.text.clone(),
+    }
+}
+
+/// Escape text for an HTML field, keeping line breaks.
+fn html(text: &str) -> String {
+    text.trim()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "<br>")
+}
+
+/// Anki tags are separated by spaces.
+fn anki_tag(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Write the notes for Anki's text importer; the header lines tell it the
+/// separator, the columns and which column holds the tags.
+pub fn write_tsv(notes: &[Note], out: &mut impl Write) -> Result<()> {
+    writeln!(out, "#separator:tab")?;
+    writeln!(out, "#html:true")?;
+    writeln!(out, "#columns:Word\tPhonetic\tDefinition\tExample\tTags")?;
+    writeln!(out, "#tags column:5")?;
+    for note in notes {
+        let tags: Vec<_> = note.tags.iter().map(|x| anki_tag(x)).collect();
+        let fields = [
+            html(&note.word),
+            html(&note.phonetic),
+            html(&note.definition),
+            html(&note.example),
+            tags.join(" "),
+        ];
+        let fields: Vec<_> = fields.iter().map(|x| x.replace('\t', " ")).collect();
+        writeln!(out, "{}", fields.join("\t"))?;
+    }
+    Ok(())
+}
+
+/// Export the notes passing `filter` to `path`. Returns how many were written.
+pub fn export(
+    conn: &Connection,
+    filter: &ExportFilter,
+    path: &Path,
+    format: AnkiFormat,
+    deck: &str,
+    audio: bool,
+) -> Result<usize> {
+    let notes = notes(conn, filter)?;
+    if notes.is_empty() {
+        bail!("No words to export");
+    }
+    match format {
+        AnkiFormat::Tsv => {
+            if audio {
+                bail!("Pronunciations can only be exported to an .apkg package");
+            }
+            let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+            write_tsv(&notes, &mut out)?;
+            out.flush()?;
+        }
+        AnkiFormat::Apkg => write_apkg(&notes, deck, audio, path)?,
+    }
+    Ok(notes.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::Database;
+    use crate::dict::{PartOfSpeech, Phonetics, Sense};
+    use crate::history::{insert_context_record, insert_history_record, tag_words};
+    use chrono::Local;
+
+    fn history() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        {
+            let conn = db.lock();
+            let mut rust = LookUpResultItem::new("rust", String::new());
+            rust.phonetics = Phonetics {
+                uk: Some("/rʌst/".to_owned()),
+                ..Default::default()
+            };
+            let mut sense = Sense::new("铁锈");
+            sense
+                .examples
+                .push(Example::new("covered in rust", Some("锈迹斑斑".to_owned())));
+            rust.parts_of_speech = vec![PartOfSpeech::new("n.", vec![sense])];
+            rust.difficulty_levels = vec!["CET4".to_owned()];
+            insert_history_record(&conn, &rust).unwrap();
+            insert_history_record(&conn, &rust).unwrap();
+            let crate_ = LookUpResultItem::new("crate", "n. <板条箱>".to_owned());
+            insert_history_record(&conn, &crate_).unwrap();
+            insert_context_record(&conn, "crate", "Pack it in a crate.").unwrap();
+            tag_words(&conn, "Book: Dune", &["crate".to_owned()]).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn collect_notes() {
+        let db = history();
+        let conn = db.lock();
+        let notes = notes(&conn, &ExportFilter::default()).unwrap();
+        assert_eq!(notes.len(), 2);
+        let rust = notes.iter().find(|x| x.word == "rust").unwrap();
+        assert_eq!(rust.phonetic, "UK /rʌst/");
+        assert_eq!(rust.definition, "n. 铁锈");
+        assert_eq!(rust.example, "covered in rust\n锈迹斑斑");
+        assert_eq!(rust.tags, ["CET4"]);
+
+        let filter = ExportFilter {
+            min_lookups: 2,
+            ..Default::default()
+        };
+        assert_eq!(super::notes(&conn, &filter).unwrap()[0].word, "rust");
+        let filter = ExportFilter {
+            tag: Some("book: dune".to_owned()),
+            ..Default::default()
+        };
+        let crate_ = super::notes(&conn, &filter).unwrap().remove(0);
+        assert_eq!(crate_.example, "Pack it in a crate.");
+        let tomorrow = Local::now().date_naive().succ_opt();
+        let filter = ExportFilter {
+            since: tomorrow,
+            ..Default::default()
+        };
+        assert!(super::notes(&conn, &filter).unwrap().is_empty());
+        let filter = ExportFilter {
+            until: Some(Local::now().date_naive()),
+            ..Default::default()
+        };
+        assert_eq!(super::notes(&conn, &filter).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn write_anki_tsv() {
+        let note = Note {
+            word: "crate".to_owned(),
+            definition: "n. <板条箱>\tbox".to_owned(),
+            example: "one\ntwo".to_owned(),
+            tags: vec!["Book: Dune".to_owned(), "CET4".to_owned()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        write_tsv(&[note], &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out.lines().last().unwrap(),
+            "crate\t\tn. &lt;板条箱&gt; box\tone<br>two\tBook:_Dune CET4"
+        );
+        assert_eq!(
+            AnkiFormat::from_path(Path::new("words.TSV")),
+            AnkiFormat::Tsv
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Persistent cache of online and LLM look-ups, kept in the history database.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use prettytable::{Attr, Cell, Row, Table};
+use rusqlite::OptionalExtension;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::db::Database;
+use crate::dict::LookUpResultItem;
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_ttl_days() -> u64 {
+    30
+}
+
+/// The `[cache]` section of `config.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Days before a cached entry is fetched again.
+    #[serde(default = "default_ttl_days")]
+    pub ttl_days: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            ttl_days: default_ttl_days(),
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_days * 24 * 60 * 60)
+    }
+}
+
+/// What a cached response depends on besides the word.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheIdentity {
+    pub provider: String,
+    pub model: String,
+    pub prompt_hash: String,
+}
+
+impl CacheIdentity {
+    pub fn new(provider: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>, prompt: &str) -> Self {
+        self.model = model.into();
+        self.prompt_hash = format!("{:016x}", fnv1a(prompt.as_bytes()));
+        self
+    }
+}
+
+/// A stable hash, unlike `DefaultHasher` which may change between Rust releases.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// An entry read back from the cache.
+#[derive(Debug)]
+pub struct CachedItem {
+    pub item: LookUpResultItem,
+    pub fetched_at: DateTime<Utc>,
+    /// Older than the TTL.
+    pub stale: bool,
+}
+
+/// Entries cached for one provider and model.
+#[derive(Debug)]
+pub struct CacheStats {
+    pub provider: String,
+    pub model: String,
+    pub entries: usize,
+    pub stale: usize,
+    pub oldest: DateTime<Utc>,
+    pub newest: DateTime<Utc>,
+}
+
+pub struct LookUpCache {
+    db: Database,
+    ttl: Duration,
+}
+
+impl LookUpCache {
+    pub fn new(db: Database, ttl: Duration) -> Self {
+        Self { db, ttl }
+    }
+
+    fn is_stale(&self, fetched_at: i64) -> bool {
+        Utc::now().timestamp() - fetched_at > self.ttl.as_secs() as i64
+    }
+
+    pub fn get(&self, identity: &CacheIdentity, word: &str) -> Result<Option<CachedItem>> {
+        let conn = self.db.lock();
+        let row: Option<(String, i64)> = conn
+            .query_row(
+                "SELECT ENTRY, DATE FROM LOOKUP_CACHE
+                WHERE PROVIDER = ?1 AND MODEL = ?2 AND PROMPT_HASH = ?3 AND WORD = ?4",
+                (
+                    &identity.provider,
+                    &identity.model,
+                    &identity.prompt_hash,
+                    word,
+                ),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((entry, date)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(CachedItem {
+            item: serde_json::from_str(&entry)?,
+            fetched_at: DateTime::from_timestamp(date, 0).unwrap_or_default(),
+            stale: self.is_stale(date),
+        }))
+    }
+
+    pub fn put(&self, identity: &CacheIdentity, word: &str, item: &LookUpResultItem) -> Result<()> {
+        let conn = self.db.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO LOOKUP_CACHE
+            (PROVIDER, MODEL, PROMPT_HASH, WORD, ENTRY, DATE) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                &identity.provider,
+                &identity.model,
+                &identity.prompt_hash,
+                word,
+                serde_json::to_string(item)?,
+                Utc::now().timestamp(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<Vec<CacheStats>> {
+        let conn = self.db.lock();
+        let threshold = Utc::now().timestamp() - self.ttl.as_secs() as i64;
+        let mut stmt = conn.prepare(
+            "SELECT PROVIDER, MODEL, COUNT(*), SUM(DATE < ?1), MIN(DATE), MAX(DATE)
+            FROM LOOKUP_CACHE GROUP BY PROVIDER, MODEL ORDER BY PROVIDER, MODEL",
+        )?;
+        let stats = stmt
+            .query_map([threshold], |row| {
+                let timestamp =
+                    |i| -> rusqlite::Result<_> { Ok(DateTime::from_timestamp(row.get(i)?, 0)) };
+                Ok(CacheStats {
+                    provider: row.get(0)?,
+                    model: row.get(1)?,
+                    entries: row.get(2)?,
+                    stale: row.get(3)?,
+                    oldest: timestamp(4)?.unwrap_or_default(),
+                    newest: timestamp(5)?.unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(stats)
+    }
+
+    /// Remove the entries of one provider, or all of them. Returns how many were removed.
+    pub fn clear(&self, provider: Option<&str>) -> Result<usize> {
+        let conn = self.db.lock();
+        let removed = match provider {
+            Some(provider) => {
+                conn.execute("DELETE FROM LOOKUP_CACHE WHERE PROVIDER = ?1", [provider])?
+            }
+            None => conn.execute("DELETE FROM LOOKUP_CACHE", ())?,
+        };
+        Ok(removed)
+    }
+}
+
+/// Print cache statistics as a table.
+pub fn print_stats(cache: &LookUpCache) -> Result<()> {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        [
+            "Provider", "Model", "Entries", "Expired", "Oldest", "Newest",
+        ]
+        .into_iter()
+        .map(|x| Cell::new(x).with_style(Attr::Bold))
+        .collect(),
+    ));
+    let format = |date: DateTime<Utc>| date.format("%Y-%m-%d").to_string();
+    for stats in cache.stats()? {
+        table.add_row(Row::new(vec![
+            Cell::new(&stats.provider),
+            Cell::new(if stats.model.is_empty() {
+                "-"
+            } else {
+                &stats.model
+            }),
+            Cell::new(&stats.entries.to_string()),
+            Cell::new(&stats.stale.to_string()),
+            Cell::new(&format(stats.oldest)),
+            Cell::new(&format(stats.newest)),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cache(ttl: Duration) -> LookUpCache {
+        LookUpCache::new(Database::open_in_memory().unwrap(), ttl)
+    }
+
+    #[test]
+    fn put_and_get() {
+        let cache = cache(Duration::from_secs(3600));
+        let youdao = CacheIdentity::new("Youdao");
+        let deepseek = CacheIdentity::new("DeepSeek").with_model("deepseek-chat", "{{text}}");
+        let item = LookUpResultItem::new("rust", "铁锈".to_owned());
+
+        cache.put(&youdao, "rust", &item).unwrap();
+        let cached = cache.get(&youdao, "rust").unwrap().unwrap();
+        assert_eq!(cached.item, item);
+        assert!(!cached.stale);
+        assert!(cache.get(&deepseek, "rust").unwrap().is_none());
+
+        cache.put(&deepseek, "rust", &item).unwrap();
+        let other_prompt = CacheIdentity::new("DeepSeek").with_model("deepseek-chat", "{{text}}!");
+        assert!(cache.get(&other_prompt, "rust").unwrap().is_none());
+
+        let stats = cache.stats().unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].provider, "DeepSeek");
+        assert_eq!(stats[0].entries, 1);
+
+        assert_eq!(cache.clear(Some("Youdao")).unwrap(), 1);
+        assert!(cache.get(&youdao, "rust").unwrap().is_none());
+        assert_eq!(cache.clear(None).unwrap(), 1);
+    }
+
+    #[test]
+    fn expired_entries_are_stale() {
+        let cache = cache(Duration::ZERO);
+        let youdao = CacheIdentity::new("Youdao");
+        cache
+            .put(&youdao, "rust", &LookUpResultItem::default())
+            .unwrap();
+        cache
+            .db
+            .lock()
+            .execute("UPDATE LOOKUP_CACHE SET DATE = DATE - 10", ())
+            .unwrap();
+        assert!(cache.get(&youdao, "rust").unwrap().unwrap().stale);
+        assert_eq!(cache.stats().unwrap()[0].stale, 1);
+    }
+}
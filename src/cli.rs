@@ -1,9 +1,15 @@
 //! Dioxionary command line parameters.
+use chrono::NaiveDate;
 use std::path::PathBuf;
 
 pub use clap::{Args, Parser};
 use clap_complete::Shell;
 
+use crate::anki::{AnkiFormat, DEFAULT_DECK};
+use crate::stats::StatsFormat;
+use crate::transfer::HistoryFormat;
+use crate::wordlist::WordlistFormat;
+
 /// Dioxionary command line parameters.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, bin_name = "dioxionary", after_help =
@@ -11,12 +17,46 @@ use clap_complete::Shell;
   When no subcommand is specified, the default is 'lookup'.
   you can list all records:
     dioxionary list
-  you can also list the following difficulty level records:
-    'CET4', 'CET6', 'TOEFL', 'IELTS', 'GMAT', 'GRE', 'SAT'
-  you can count all records:
+  you can also list the words of a tag, such as a difficulty level
+  ('CET4', 'CET6', 'TOEFL', 'IELTS', 'GMAT', 'GRE', 'SAT') or your own list:
+    dioxionary list GRE
+    dioxionary tag 'Book: Dune' sandworm spice
+    dioxionary list 'Book: Dune'
+  you can count the words of every tag:
     dioxionary count
+  you can import word lists to tag the words you look up:
+    dioxionary wordlist import CET6 cet6.txt
+    dioxionary wordlist ls
+  you can look up a word in the sentence it appeared in:
+    dioxionary lookup bank --context 'We sat on the bank of the river.'
+  you can ask LLM dictionaries with a prompt template:
+    dioxionary lookup --template etymology rust
+  and list the words looked up in context:
+    dioxionary list --context
+  you can see your look-ups over the last weeks, streaks and retention:
+    dioxionary stats --days 14
+    dioxionary stats --format json
+  you can review the words you looked up, or see how many are due:
+    dioxionary review
+    dioxionary review --summary
+  you can export the words you looked up to Anki:
+    dioxionary export anki --tag CET6 --min-lookups 2 words.apkg
+  you can search the history, keep notes on words or remove typos:
+    dioxionary history search 'ab*'
+    dioxionary history note ubiquitous 'ubi = everywhere'
+    dioxionary history rm teh
+  and list what you looked up in a range of days:
+    dioxionary list --details --since 2024-09-01 --until 2024-09-30
+  you can move your history to another machine and merge it there:
+    dioxionary history export -o history.json
+    dioxionary history import --dry-run history.json
+  or share it between machines through a synced directory:
+    dioxionary history sync ~/Sync/dioxionary
   you can list all dictionaries:
     dioxionary dicts
+  you can inspect or clear the look-up cache:
+    dioxionary cache stats
+    dioxionary cache clear Youdao
 "
 )]
 pub struct Cli {
@@ -32,17 +72,44 @@ pub enum Action {
     #[command(name = "lookup", visible_alias = "l")]
     LookUp(LookUp),
 
-    /// List the specific difficulty level records.
+    /// List the records, or the words of a tag.
     #[command(visible_alias = "ls")]
     List(List),
 
-    /// Count the number of each difficulty level records.
+    /// Count the words of each tag.
     #[command(visible_alias = "c")]
-    Count,
+    Count(Count),
+
+    /// Show look-ups over time, streaks, top words, levels and retention.
+    Stats(Stats),
+
+    /// Add words to a list of your own, or remove them from it.
+    #[command(visible_alias = "t")]
+    Tag(Tag),
+
+    /// Import word lists and see how much of them you have looked up.
+    #[command(subcommand, visible_alias = "wl")]
+    Wordlist(WordlistAction),
+
+    /// Export looked-up words to other programs.
+    #[command(subcommand)]
+    Export(ExportAction),
+
+    /// Export the history to a file, or merge one into it.
+    #[command(subcommand)]
+    History(HistoryAction),
+
+    /// Review the words looked up, spaced out over time.
+    #[command(visible_alias = "r")]
+    Review(Review),
 
     /// Display list of available dictionaries and exit.
     Dicts,
 
+    /// Inspect or clear the cache of online and LLM look-ups.
+    #[command(subcommand)]
+    Cache(CacheAction),
+
     /// Generate shell completion scripts.
     Completion(Completion),
 }
@@ -70,6 +137,20 @@ pub struct LookUp {
     #[arg(short, long, default_value_t = false)]
     pub exact_search: bool,
 
+    /// Ignore cached online and LLM results and query again.
+    #[arg(long, default_value_t = false)]
+    pub refresh: bool,
+
+    /// The sentence the word appeared in, to get the meaning it has there.
+    /// In the REPL, write `word :: sentence` instead.
+    #[arg(short = 'C', long)]
+    pub context: Option<String>,
+
+    /// Ask LLM dictionaries with a named prompt template, e.g. `etymology`.
+    /// In the REPL, prefix the word with `#name` instead.
+    #[arg(short = 'T', long)]
+    pub template: Option<String>,
+
     /// Play word pronunciation.
     #[cfg(feature = "pronunciation")]
     #[arg(short, long, default_value_t = false)]
@@ -94,8 +175,227 @@ pub struct List {
     #[arg(short, long, default_value_t = 5, name = "column", requires("table"))]
     pub max_column: usize,
 
-    /// The difficulty level of the word.
-    pub difficulty_level: Option<String>,
+    /// List the words looked up in context with their sentences.
+    #[arg(long, default_value_t = false, conflicts_with = "table")]
+    pub context: bool,
+
+    /// Show how often, when and from which dictionary words were looked up.
+    #[arg(short, long, default_value_t = false, conflicts_with_all = ["table", "context"])]
+    pub details: bool,
+
+    /// Only list words first looked up on or after this day (YYYY-MM-DD).
+    #[arg(long)]
+    pub since: Option<NaiveDate>,
+
+    /// Only list words first looked up on or before this day (YYYY-MM-DD).
+    #[arg(long)]
+    pub until: Option<NaiveDate>,
+
+    /// List at most this many words.
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+
+    /// Skip this many words first.
+    #[arg(long, default_value_t = 0)]
+    pub offset: usize,
+
+    /// Only list words with this tag, a difficulty level or a list of your own.
+    pub tag: Option<String>,
+}
+
+/// Subcommand line parameters for counting tagged words.
+#[derive(Args, Debug)]
+pub struct Count {
+    /// The tags to count, all of them by default.
+    pub tags: Vec<String>,
+}
+
+/// Subcommand line parameters for statistics.
+#[derive(Args, Debug)]
+pub struct Stats {
+    /// Chart the look-ups of this many days, up to today.
+    #[arg(short, long, default_value_t = 30)]
+    pub days: usize,
+
+    /// List this many of the words looked up most.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub top: usize,
+
+    /// Print charts and tables, or JSON.
+    #[arg(short, long, value_enum, default_value_t = StatsFormat::Text)]
+    pub format: StatsFormat,
+}
+
+/// Subcommand line parameters for tagging words.
+#[derive(Args, Debug)]
+pub struct Tag {
+    /// Remove the tag from the words instead.
+    #[arg(short, long, default_value_t = false)]
+    pub remove: bool,
+
+    /// The tag, e.g. `work-jargon` or `Book: Dune`.
+    pub tag: String,
+
+    /// The words to tag.
+    #[arg(required = true)]
+    pub words: Vec<String>,
+}
+
+/// Subcommand line parameters for reviewing words.
+#[derive(Args, Debug)]
+pub struct Review {
+    /// Only show how many words are due today.
+    #[arg(short, long, default_value_t = false)]
+    pub summary: bool,
+
+    /// The most words reviewed in one session.
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub limit: usize,
+}
+
+/// Subcommands for word lists.
+#[derive(clap::Subcommand, Debug)]
+pub enum WordlistAction {
+    /// Import a word list from a plain-text, CSV or JSON file.
+    Import {
+        /// The format of the file, guessed from its extension by default.
+        #[arg(short, long)]
+        format: Option<WordlistFormat>,
+
+        /// The name of the list, also used to tag its words.
+        name: String,
+
+        file: PathBuf,
+    },
+
+    /// List the imported word lists and how much of them you have looked up.
+    #[command(visible_alias = "list")]
+    Ls,
+
+    /// Remove a word list and its tag.
+    Rm { name: String },
+
+    /// Show the words of a list.
+    Show {
+        /// Only show the words you have not looked up yet.
+        #[arg(short, long, default_value_t = false)]
+        missing: bool,
+
+        name: String,
+    },
+}
+
+/// Subcommands for exporting words.
+#[derive(clap::Subcommand, Debug)]
+pub enum ExportAction {
+    /// Export to an Anki package (.apkg) or a TSV file for Anki's text importer.
+    Anki(AnkiExport),
+}
+
+/// Subcommand line parameters for exporting to Anki.
+#[derive(Args, Debug)]
+pub struct AnkiExport {
+    /// The format of the file, guessed from its extension by default.
+    #[arg(short, long)]
+    pub format: Option<AnkiFormat>,
+
+    /// The deck the words are added to.
+    #[arg(short, long, default_value = DEFAULT_DECK)]
+    pub deck: String,
+
+    /// Only export words with this tag, e.g. a difficulty level or a word list.
+    #[arg(short, long)]
+    pub tag: Option<String>,
+
+    /// Only export words first looked up on or after this day (YYYY-MM-DD).
+    #[arg(long)]
+    pub since: Option<NaiveDate>,
+
+    /// Only export words first looked up on or before this day (YYYY-MM-DD).
+    #[arg(long)]
+    pub until: Option<NaiveDate>,
+
+    /// Only export words looked up at least this many times.
+    #[arg(short, long, default_value_t = 1)]
+    pub min_lookups: u32,
+
+    /// Download pronunciations into the package.
+    #[arg(short, long, default_value_t = false)]
+    pub audio: bool,
+
+    /// The file to write, e.g. `words.apkg` or `words.tsv`.
+    pub output: PathBuf,
+}
+
+/// Subcommands for the history.
+#[derive(clap::Subcommand, Debug)]
+pub enum HistoryAction {
+    /// Remove words looked up by accident or misspelt, with their tags,
+    /// sentences, reviews and notes.
+    #[command(visible_alias = "remove")]
+    Rm {
+        #[arg(required = true)]
+        words: Vec<String>,
+    },
+
+    /// Search the words, definitions and notes; `*` and `?` are wildcards.
+    Search { pattern: String },
+
+    /// Keep a note on a word, e.g. a mnemonic, shown when you look it up
+    /// again. Without a text, show the note.
+    Note {
+        /// Remove the note instead.
+        #[arg(short, long, default_value_t = false, conflicts_with = "text")]
+        remove: bool,
+
+        word: String,
+
+        text: Vec<String>,
+    },
+
+    /// Write every looked-up word with its counts, dates and tags.
+    Export {
+        /// The format of the file, guessed from its extension by default.
+        #[arg(short, long)]
+        format: Option<HistoryFormat>,
+
+        /// The file to write, the standard output by default.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Merge an exported history into this one, word by word.
+    Import {
+        /// The format of the file, guessed from its extension by default.
+        #[arg(short, long)]
+        format: Option<HistoryFormat>,
+
+        /// Only report what would change and which definitions conflict.
+        #[arg(short = 'n', long, default_value_t = false)]
+        dry_run: bool,
+
+        file: PathBuf,
+    },
+
+    /// Write this machine's changes to the sync directory and merge those
+    /// of the others.
+    Sync {
+        /// The shared directory, `dir` of `[sync]` in the configuration by default.
+        dir: Option<PathBuf>,
+    },
+}
+
+/// Subcommands for the look-up cache.
+#[derive(clap::Subcommand, Debug)]
+pub enum CacheAction {
+    /// Count the cached entries of each dictionary.
+    Stats,
+
+    /// Remove cached entries.
+    Clear {
+        /// Only remove the entries of this dictionary.
+        dict: Option<String>,
+    },
 }
 
 /// Subcommand line parameters for shell completion.
@@ -0,0 +1,226 @@
+//! User configuration read from `config.toml` in the dioxionary config directory.
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::cache::CacheConfig;
+use crate::dict::{command::CommandDict, online::OnlineDictConfig, LookUpResultItem};
+use crate::net::NetworkConfig;
+use crate::sync::SyncConfig;
+
+/// Settings from `config.toml`. Every section is optional.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    /// External-command dictionaries, `[[command]]` in the file.
+    #[serde(rename = "command")]
+    pub commands: Vec<CommandDict>,
+    /// Online dictionaries in the order they are tried, `[[online]]` in the file.
+    /// Youdao alone is used when there are none.
+    pub online: Vec<OnlineDictConfig>,
+    pub cache: CacheConfig,
+    pub display: DisplayConfig,
+    pub network: NetworkConfig,
+    pub sync: SyncConfig,
+}
+
+/// Which sections of an entry are printed, `[display]` in the file.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub phonetics: bool,
+    pub inflections: bool,
+    pub synonyms: bool,
+    pub web_definitions: bool,
+    pub phrases: bool,
+    pub detailed_senses: bool,
+    pub examples: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            phonetics: true,
+            inflections: true,
+            synonyms: true,
+            web_definitions: true,
+            phrases: true,
+            detailed_senses: true,
+            examples: true,
+        }
+    }
+}
+
+impl DisplayConfig {
+    /// Drop the sections that are switched off.
+    pub fn apply(&self, item: &mut LookUpResultItem) {
+        if !self.phonetics {
+            item.phonetics = Default::default();
+        }
+        if !self.inflections {
+            item.inflections.clear();
+        }
+        if !self.synonyms {
+            item.synonyms.clear();
+            item.antonyms.clear();
+        }
+        if !self.web_definitions {
+            item.web_definitions.clear();
+        }
+        if !self.phrases {
+            item.phrases.clear();
+        }
+        if !self.detailed_senses {
+            item.detailed_senses.clear();
+        }
+        if !self.examples {
+            item.examples.clear();
+        }
+    }
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read configuration {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse configuration {}", path.display()))
+    }
+
+    /// Load the default configuration file, or the defaults if there is none.
+    pub fn load_default() -> Result<Self> {
+        match default_config_path() {
+            Some(path) => Self::load(path),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("dioxionary").join("config.toml"))
+        .filter(|path| path.exists())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dict::{command::OutputFormat, online::OnlineProvider};
+
+    #[test]
+    fn parse_commands() {
+        let config: Config = toml::from_str(
+            r#"
+            [[command]]
+            name = "Confluence"
+            command = "confluence-search"
+            args = ["--query", "{{word}}"]
+            output = "json"
+            timeout = 3
+            fuzzy = true
+
+            [[command]]
+            name = "man"
+            command = "man-search"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.commands.len(), 2);
+        assert_eq!(config.commands[0].output, OutputFormat::Json);
+        assert_eq!(config.commands[0].timeout, 3);
+        assert!(config.commands[0].fuzzy);
+        assert_eq!(config.commands[1].output, OutputFormat::Text);
+        assert!(!config.commands[1].fuzzy);
+    }
+
+    #[test]
+    fn parse_online_providers() {
+        let config: Config = toml::from_str(
+            r#"
+            [[online]]
+            provider = "cambridge"
+
+            [[online]]
+            provider = "merriam-webster"
+            api_key = "secret"
+
+            [[online]]
+            provider = "urban-dictionary"
+            enabled = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.online.len(), 3);
+        assert_eq!(config.online[0].provider, OnlineProvider::Cambridge);
+        assert!(config.online[0].enabled);
+        assert_eq!(
+            config.online[1].provider,
+            OnlineProvider::MerriamWebster {
+                api_key: "secret".to_owned()
+            }
+        );
+        assert_eq!(config.online[2].provider, OnlineProvider::Urban);
+        assert!(!config.online[2].enabled);
+
+        assert!(toml::from_str::<Config>(
+            r#"
+            [[online]]
+            provider = "merriam-webster"
+            "#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_cache() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.cache, CacheConfig::default());
+
+        let config: Config = toml::from_str(
+            r#"
+            [cache]
+            ttl_days = 7
+            "#,
+        )
+        .unwrap();
+        assert!(config.cache.enabled);
+        assert_eq!(config.cache.ttl().as_secs(), 7 * 24 * 60 * 60);
+    }
+
+    #[test]
+    fn parse_sync() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.sync.dir, None);
+
+        let config: Config = toml::from_str(
+            r#"
+            [sync]
+            dir = "/srv/dioxionary"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.sync.dir, Some(PathBuf::from("/srv/dioxionary")));
+        assert!(config.sync.on_start);
+    }
+
+    #[test]
+    fn display_sections() {
+        let config: Config = toml::from_str(
+            r#"
+            [display]
+            examples = false
+            "#,
+        )
+        .unwrap();
+        assert!(config.display.phrases);
+
+        let mut item = LookUpResultItem::new("rust", String::new());
+        item.examples
+            .push(crate::dict::Example::new("It rusts.", None));
+        item.web_definitions.push("铁锈".to_owned());
+        config.display.apply(&mut item);
+        assert!(item.examples.is_empty());
+        assert_eq!(item.web_definitions, ["铁锈"]);
+    }
+}
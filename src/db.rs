@@ -0,0 +1,503 @@
+//! The history database, versioned with `PRAGMA user_version` and migrated on opening.
+//!
+//! History, reviews and the look-up cache share one connection to it.
+use anyhow::{bail, Context, Result};
+use rusqlite::Connection;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::history::{ensure_cache_directory, ALLOWED_DIFFICULTY_LEVELS};
+use crate::sync::new_machine_name;
+
+/// A schema change, applied once in a transaction.
+struct Migration {
+    description: &'static str,
+    up: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+/// Every schema change in order; a database at version `n` has the first `n` applied.
+///
+/// Tables are created `IF NOT EXISTS` and columns only added when missing,
+/// since databases written before versioning may hold some of them already.
+const MIGRATIONS: [Migration; 12] = [
+    Migration {
+        description: "create the history",
+        up: create_history,
+    },
+    Migration {
+        description: "cache look-ups",
+        up: create_look_up_cache,
+    },
+    Migration {
+        description: "remember sentences of words",
+        up: create_context,
+    },
+    Migration {
+        description: "schedule reviews",
+        up: create_review,
+    },
+    Migration {
+        description: "count look-ups and record events",
+        up: count_look_ups,
+    },
+    Migration {
+        description: "move difficulty levels into tags",
+        up: create_tags,
+    },
+    Migration {
+        description: "import word lists",
+        up: create_wordlists,
+    },
+    Migration {
+        description: "keep whole entries",
+        up: keep_entries,
+    },
+    Migration {
+        description: "sync with other machines",
+        up: create_sync,
+    },
+    Migration {
+        description: "keep notes and removed words",
+        up: create_notes,
+    },
+    Migration {
+        description: "count look-ups by machine",
+        up: count_by_machine,
+    },
+    Migration {
+        description: "tell tags of word lists apart",
+        up: mark_listed_tags,
+    },
+];
+
+/// The schema version this build reads and writes.
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+/// A shared connection to the history database.
+#[derive(Clone)]
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    /// Open the database at `path`, migrating it to [`SCHEMA_VERSION`].
+    ///
+    /// An existing database is copied to `<path>.v<version>.bak` before it is migrated.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let existed = path.metadata().is_ok_and(|x| x.len() > 0);
+        let conn = Connection::open(path)?;
+        let version = schema_version(&conn)?;
+        if version > SCHEMA_VERSION {
+            bail!(
+                "{:?} was written by a newer dioxionary (schema version {}, this one knows {})",
+                path,
+                version,
+                SCHEMA_VERSION
+            );
+        }
+        if version < SCHEMA_VERSION && existed {
+            let backup = backup_path(path, version);
+            fs::copy(path, &backup)
+                .with_context(|| format!("Failed to back up {:?} to {:?}", path, backup))?;
+        }
+        migrate(&conn, version)?;
+        Ok(Self::with_connection(conn))
+    }
+
+    /// Open the database in the cache directory.
+    pub fn open_default() -> Result<Self> {
+        Self::open(ensure_cache_directory()?)
+    }
+
+    /// A fresh database living in memory only.
+    pub fn open_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        migrate(&conn, 0)?;
+        Ok(Self::with_connection(conn))
+    }
+
+    fn with_connection(conn: Connection) -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+}
+
+fn backup_path(path: &Path, version: usize) -> std::path::PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(format!(".v{}.bak", version));
+    path.with_file_name(name)
+}
+
+/// The version of the schema, counting databases of releases before
+/// versioning that hold a history as version 1.
+fn schema_version(conn: &Connection) -> Result<usize> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > 0 {
+        return Ok(version);
+    }
+    let has_history: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM SQLITE_MASTER WHERE TYPE = 'table' AND NAME = 'HISTORY')",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(has_history as usize)
+}
+
+/// Apply the migrations after `version`.
+fn migrate(conn: &Connection, version: usize) -> Result<()> {
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let to = i + 1;
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(&tx)
+            .and_then(|_| tx.pragma_update(None, "user_version", to))
+            .and_then(|_| tx.commit())
+            .with_context(|| {
+                format!(
+                    "Failed to migrate the history database to version {} ({})",
+                    to, migration.description
+                )
+            })?;
+    }
+    Ok(())
+}
+
+fn create_history(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS HISTORY (
+        WORD TEXT PRIMARY KEY,
+        DATE INTEGER NOT NULL,
+        CET4 INTEGER,
+        CET6 INTEGER,
+        TOEFL INTEGER,
+        IELTS INTEGER,
+        GMAT INTEGER,
+        GRE INTEGER,
+        SAT INTEGER
+        )",
+        (),
+    )?;
+    Ok(())
+}
+
+fn create_look_up_cache(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS LOOKUP_CACHE (
+        PROVIDER TEXT NOT NULL,
+        MODEL TEXT NOT NULL,
+        PROMPT_HASH TEXT NOT NULL,
+        WORD TEXT NOT NULL,
+        ENTRY TEXT NOT NULL,
+        DATE INTEGER NOT NULL,
+        PRIMARY KEY (PROVIDER, MODEL, PROMPT_HASH, WORD)
+        )",
+        (),
+    )?;
+    Ok(())
+}
+
+fn create_context(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS CONTEXT (
+        WORD TEXT NOT NULL,
+        SENTENCE TEXT NOT NULL,
+        DATE INTEGER NOT NULL,
+        PRIMARY KEY (WORD, SENTENCE)
+        )",
+        (),
+    )?;
+    Ok(())
+}
+
+fn create_review(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS REVIEW (
+        WORD TEXT PRIMARY KEY,
+        EASE REAL NOT NULL,
+        INTERVAL INTEGER NOT NULL DEFAULT 0,
+        REPETITIONS INTEGER NOT NULL DEFAULT 0,
+        LAPSES INTEGER NOT NULL DEFAULT 0,
+        DUE INTEGER NOT NULL,
+        LAST_REVIEW INTEGER
+        )",
+        (),
+    )?;
+    Ok(())
+}
+
+/// `DATE` is when the word was first looked up, `LAST_DATE` the latest, and
+/// `DEFINITION` the definition shown then by the dictionary `SOURCE`.
+/// `EVENTS` holds one row per look-up.
+fn count_look_ups(conn: &Connection) -> rusqlite::Result<()> {
+    add_missing_columns(
+        conn,
+        "HISTORY",
+        &[
+            ("LOOKUPS", "INTEGER NOT NULL DEFAULT 1"),
+            ("LAST_DATE", "INTEGER"),
+            ("SOURCE", "TEXT"),
+            ("DEFINITION", "TEXT"),
+        ],
+    )?;
+    conn.execute(
+        "UPDATE HISTORY SET LAST_DATE = DATE WHERE LAST_DATE IS NULL",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS EVENTS (
+        ID INTEGER PRIMARY KEY AUTOINCREMENT,
+        WORD TEXT NOT NULL,
+        DATE INTEGER NOT NULL,
+        SOURCE TEXT
+        )",
+        (),
+    )?;
+    Ok(())
+}
+
+/// Words may carry any number of tags: the difficulty levels given by
+/// dictionaries and lists of the user's own, e.g. `Book: Dune`.
+fn create_tags(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS TAGS (
+        WORD TEXT NOT NULL,
+        TAG TEXT NOT NULL COLLATE NOCASE,
+        PRIMARY KEY (WORD, TAG)
+        )",
+        (),
+    )?;
+    for level in ALLOWED_DIFFICULTY_LEVELS {
+        conn.execute(
+            &format!(
+                "INSERT OR IGNORE INTO TAGS (WORD, TAG) SELECT WORD, ?1 FROM HISTORY WHERE {} = 1",
+                level
+            ),
+            [level],
+        )?;
+        conn.execute(&format!("ALTER TABLE HISTORY DROP COLUMN {}", level), ())?;
+    }
+    Ok(())
+}
+
+/// Imported word lists; words of a list looked up are tagged with its name.
+fn create_wordlists(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS WORDLISTS (
+        NAME TEXT PRIMARY KEY COLLATE NOCASE,
+        SOURCE TEXT NOT NULL,
+        DATE INTEGER NOT NULL
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS WORDLIST_WORDS (
+        LIST TEXT NOT NULL COLLATE NOCASE,
+        WORD TEXT NOT NULL COLLATE NOCASE,
+        PRIMARY KEY (LIST, WORD)
+        )",
+        (),
+    )?;
+    Ok(())
+}
+
+/// `ENTRY` is the entry shown at the last look-up as JSON, for exports
+/// needing its phonetics and examples.
+fn keep_entries(conn: &Connection) -> rusqlite::Result<()> {
+    add_missing_columns(conn, "HISTORY", &[("ENTRY", "TEXT")])
+}
+
+/// The name of this machine's log in the sync directory, the changes not
+/// written to it yet, how far the logs of other machines were merged, and
+/// the latest change to each tag of a word.
+fn create_sync(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS SYNC_MACHINE (
+        ID INTEGER PRIMARY KEY CHECK (ID = 1),
+        NAME TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS SYNC_OUTBOX (
+        SEQ INTEGER PRIMARY KEY AUTOINCREMENT,
+        CHANGE TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS SYNC_PEERS (
+        MACHINE TEXT PRIMARY KEY,
+        SEQ INTEGER NOT NULL,
+        OFFSET INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS SYNC_TAGS (
+        WORD TEXT NOT NULL,
+        TAG TEXT NOT NULL COLLATE NOCASE,
+        DATE INTEGER NOT NULL,
+        MACHINE TEXT NOT NULL,
+        SEQ INTEGER NOT NULL,
+        REMOVED INTEGER NOT NULL,
+        PRIMARY KEY (WORD, TAG)
+        );",
+    )
+}
+
+/// Notes of the user on words, and when words were removed from the history.
+fn create_notes(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS NOTES (
+        WORD TEXT PRIMARY KEY COLLATE NOCASE,
+        NOTE TEXT NOT NULL,
+        DATE INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS REMOVED_WORDS (
+        WORD TEXT PRIMARY KEY,
+        DATE INTEGER NOT NULL
+        );",
+    )
+}
+
+/// The name of this machine, kept from its first sync if it synced already,
+/// and how many times each machine looked a word up, the local look-ups so
+/// far counting as this machine's.
+fn count_by_machine(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS MACHINE (
+        ID INTEGER PRIMARY KEY CHECK (ID = 1),
+        NAME TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS MACHINE_LOOKUPS (
+        WORD TEXT NOT NULL,
+        MACHINE TEXT NOT NULL,
+        LOOKUPS INTEGER NOT NULL,
+        PRIMARY KEY (WORD, MACHINE)
+        );
+        INSERT OR IGNORE INTO MACHINE (ID, NAME) SELECT 1, NAME FROM SYNC_MACHINE;",
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO MACHINE (ID, NAME) VALUES (1, ?1)",
+        [new_machine_name()],
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO MACHINE_LOOKUPS (WORD, MACHINE, LOOKUPS)
+        SELECT WORD, (SELECT NAME FROM MACHINE), LOOKUPS FROM HISTORY",
+        [],
+    )?;
+    Ok(())
+}
+
+/// `LISTED` marks tags only given because the word is on the word list of
+/// that name. Tags from before are kept as given by hand, as they may be.
+fn mark_listed_tags(conn: &Connection) -> rusqlite::Result<()> {
+    add_missing_columns(conn, "TAGS", &[("LISTED", "INTEGER NOT NULL DEFAULT 0")])
+}
+
+fn add_missing_columns(
+    conn: &Connection,
+    table: &str,
+    columns: &[(&str, &str)],
+) -> rusqlite::Result<()> {
+    let existing = conn
+        .prepare("SELECT NAME FROM PRAGMA_TABLE_INFO(?1)")?
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (name, definition) in columns {
+        if !existing.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+            conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, name, definition),
+                (),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn upgrade_v1_database() {
+        let path = std::env::temp_dir().join(format!("dioxionary-v1-{}.db", std::process::id()));
+        let backup = backup_path(&path, 1);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&backup);
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(include_str!("../tests/fixtures/history-v1.sql"))
+            .unwrap();
+
+        let db = Database::open(&path).unwrap();
+        assert!(backup.exists());
+        {
+            let conn = db.lock();
+            let version: usize = conn
+                .pragma_query_value(None, "user_version", |row| row.get(0))
+                .unwrap();
+            assert_eq!(version, SCHEMA_VERSION);
+            let row: (i64, i64, i64) = conn
+                .query_row(
+                    "SELECT LOOKUPS, DATE, LAST_DATE FROM HISTORY WHERE WORD = 'rust'",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+            assert_eq!(row, (1, 1700000000, 1700000000));
+            let counted: i64 = conn
+                .query_row(
+                    "SELECT LOOKUPS FROM MACHINE_LOOKUPS WHERE WORD = 'rust'
+                    AND MACHINE = (SELECT NAME FROM MACHINE)",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(counted, 1);
+            let tags = conn
+                .prepare("SELECT WORD, TAG FROM TAGS ORDER BY WORD")
+                .unwrap()
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<(String, String)>>>()
+                .unwrap();
+            assert_eq!(
+                tags,
+                [
+                    ("ephemeral".to_owned(), "GRE".to_owned()),
+                    ("rust".to_owned(), "CET4".to_owned())
+                ]
+            );
+            for table in ["EVENTS", "CONTEXT", "REVIEW", "LOOKUP_CACHE", "WORDLISTS"] {
+                let count: i64 = conn
+                    .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                        row.get(0)
+                    })
+                    .unwrap();
+                assert_eq!(count, 0);
+            }
+        }
+        drop(db);
+
+        // Opening again migrates nothing and leaves the backup alone.
+        fs::remove_file(&backup).unwrap();
+        Database::open(&path).unwrap();
+        assert!(!backup.exists());
+
+        let backup_of_new = backup_path(&path, SCHEMA_VERSION);
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        assert!(Database::open(&path).is_err());
+        assert!(!backup_of_new.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn create_fresh_database() {
+        let path = std::env::temp_dir().join(format!("dioxionary-new-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        Database::open(&path).unwrap();
+        assert!(!backup_path(&path, 0).exists());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+//! Use blocking dictionaries from async code.
+use std::sync::Arc;
+
+use super::{Dict, DictError, LookUpResult};
+
+/// A [`Dict`] whose look-ups run on tokio's blocking thread pool.
+///
+/// Must be used inside a tokio runtime.
+#[derive(Clone)]
+pub struct AsyncDict(Arc<dyn Dict>);
+
+impl AsyncDict {
+    pub fn new(dict: impl Dict + 'static) -> Self {
+        Self(Arc::new(dict))
+    }
+
+    pub fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    pub async fn look_up(&self, enable_fuzzy: bool, word: &str) -> Result<LookUpResult, DictError> {
+        let dict = self.0.clone();
+        let word = word.to_owned();
+        run_blocking(move || dict.look_up(enable_fuzzy, &word)).await
+    }
+}
+
+impl From<Arc<dyn Dict>> for AsyncDict {
+    fn from(dict: Arc<dyn Dict>) -> Self {
+        Self(dict)
+    }
+}
+
+/// Run a blocking closure without stalling the async runtime.
+pub(crate) async fn run_blocking<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
@@ -0,0 +1,247 @@
+//! Dictionaries backed by user-supplied executables.
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use super::{Dict, DictError, DictType, LookUpResult, LookUpResultItem};
+
+/// Placeholder in `args` replaced by the queried word.
+const WORD_PLACEHOLDER: &str = "{{word}}";
+
+fn default_timeout() -> u64 {
+    10
+}
+
+/// How the command prints its result.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The whole of stdout is the definition.
+    #[default]
+    Text,
+    /// Stdout is a JSON array of entries.
+    Json,
+}
+
+/// Runs an executable for every look-up, configured as `[[command]]` in `config.toml`.
+///
+/// The word replaces `{{word}}` in `args`, or is appended when there is no
+/// placeholder. `DIOXIONARY_FUZZY` is set to `1` for fuzzy look-ups. A non-zero
+/// exit status with nothing on stderr means the word was not found.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CommandDict {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub output: OutputFormat,
+    /// Seconds to wait before the command is killed.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Whether the command understands fuzzy look-ups.
+    #[serde(default)]
+    pub fuzzy: bool,
+}
+
+impl CommandDict {
+    fn build_args(&self, word: &str) -> Vec<String> {
+        let mut args: Vec<_> = self
+            .args
+            .iter()
+            .map(|arg| arg.replace(WORD_PLACEHOLDER, word))
+            .collect();
+        if !self.args.iter().any(|arg| arg.contains(WORD_PLACEHOLDER)) {
+            args.push(word.to_owned());
+        }
+        args
+    }
+
+    /// Run the command and collect its stdout.
+    fn run(&self, enable_fuzzy: bool, word: &str) -> Result<String, DictError> {
+        let mut child = Command::new(&self.command)
+            .args(self.build_args(word))
+            .env("DIOXIONARY_FUZZY", if enable_fuzzy { "1" } else { "0" })
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| DictError::Other(format!("Failed to run `{}`: {}", self.command, e)))?;
+
+        // Drain the pipes while waiting so a chatty command cannot block on a full pipe.
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let stdout = thread::spawn(move || {
+            let mut buf = Vec::new();
+            stdout.read_to_end(&mut buf).map(|_| buf)
+        });
+        let stderr = thread::spawn(move || {
+            let mut buf = Vec::new();
+            stderr.read_to_end(&mut buf).map(|_| buf)
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(self.timeout);
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(DictError::Other(format!(
+                        "`{}` timed out after {}s",
+                        self.command, self.timeout
+                    )));
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(e) => return Err(DictError::Other(e.to_string())),
+            }
+        };
+
+        let read = |handle: thread::JoinHandle<std::io::Result<Vec<u8>>>| {
+            handle
+                .join()
+                .expect("pipe reader panicked")
+                .map(|buf| String::from_utf8_lossy(&buf).into_owned())
+                .map_err(|e| DictError::Other(e.to_string()))
+        };
+        let stdout = read(stdout)?;
+        let stderr = read(stderr)?;
+
+        if status.success() {
+            Ok(stdout)
+        } else if stderr.trim().is_empty() {
+            Err(DictError::NotFound)
+        } else {
+            Err(DictError::Other(format!(
+                "`{}` failed with {}: {}",
+                self.command,
+                status,
+                stderr.trim()
+            )))
+        }
+    }
+
+    fn parse_output(
+        &self,
+        enable_fuzzy: bool,
+        word: &str,
+        stdout: &str,
+    ) -> Result<LookUpResult, DictError> {
+        if stdout.trim().is_empty() {
+            return Err(DictError::NotFound);
+        }
+
+        match self.output {
+            OutputFormat::Text => Ok(LookUpResult::Exact(
+                LookUpResultItem::new(word, stdout.to_owned())
+                    .with_source(&self.name, DictType::Command),
+            )),
+            OutputFormat::Json => {
+                let mut items: Vec<LookUpResultItem> = serde_json::from_str(stdout)?;
+                for item in items.iter_mut().filter(|item| item.source.is_none()) {
+                    *item = std::mem::take(item).with_source(&self.name, DictType::Command);
+                }
+                if let Some(pos) = items
+                    .iter()
+                    .position(|item| item.word.eq_ignore_ascii_case(word))
+                {
+                    Ok(LookUpResult::Exact(items.swap_remove(pos)))
+                } else if enable_fuzzy && !items.is_empty() {
+                    Ok(LookUpResult::Fuzzy(items))
+                } else {
+                    Err(DictError::NotFound)
+                }
+            }
+        }
+    }
+}
+
+impl Dict for CommandDict {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn type_(&self) -> DictType {
+        DictType::Command
+    }
+
+    fn supports_fuzzy_search(&self) -> bool {
+        self.fuzzy
+    }
+
+    fn look_up(&self, enable_fuzzy: bool, word: &str) -> Result<LookUpResult, DictError> {
+        let stdout = self.run(enable_fuzzy, word)?;
+        self.parse_output(enable_fuzzy, word, &stdout)
+    }
+
+    fn word_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    fn shell(name: &str, script: &str) -> CommandDict {
+        CommandDict {
+            name: name.to_owned(),
+            command: "sh".to_owned(),
+            args: vec!["-c".to_owned(), script.to_owned(), "sh".to_owned()],
+            output: OutputFormat::Text,
+            timeout: default_timeout(),
+            fuzzy: false,
+        }
+    }
+
+    #[test]
+    fn text_output() {
+        let dict = shell("echo", r#"echo "definition of $1""#);
+        let Ok(LookUpResult::Exact(item)) = dict.look_up(false, "rust & C++") else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.translation.trim(), "definition of rust & C++");
+        assert_eq!(item.source.unwrap().dict_type, DictType::Command);
+    }
+
+    #[test]
+    fn json_output() {
+        let mut dict = shell(
+            "json",
+            r#"echo '[{"word": "rust", "translation": "铁锈"}, {"word": "crust"}]'"#,
+        );
+        dict.output = OutputFormat::Json;
+        dict.fuzzy = true;
+        assert!(matches!(
+            dict.look_up(false, "Rust"),
+            Ok(LookUpResult::Exact(item)) if item.translation == "铁锈"
+        ));
+        assert!(matches!(
+            dict.look_up(true, "rst"),
+            Ok(LookUpResult::Fuzzy(items)) if items.len() == 2
+        ));
+        assert_eq!(dict.look_up(false, "rst").err(), Some(DictError::NotFound));
+    }
+
+    #[test]
+    fn failures() {
+        assert_eq!(
+            shell("grep", "exit 1").look_up(false, "rust").err(),
+            Some(DictError::NotFound)
+        );
+        assert!(matches!(
+            shell("broken", "echo oops >&2; exit 2").look_up(false, "rust"),
+            Err(DictError::Other(e)) if e.ends_with("oops")
+        ));
+
+        let mut slow = shell("slow", "sleep 5");
+        slow.timeout = 0;
+        assert!(matches!(
+            slow.look_up(false, "rust"),
+            Err(DictError::Other(e)) if e.contains("timed out")
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+//! Guess which sense of an entry is meant in a sentence.
+//!
+//! Dictionaries that cannot read the sentence themselves are helped by
+//! counting the words a sense shares with it, which works when definitions
+//! or examples are in the language of the sentence.
+use std::collections::HashSet;
+
+use super::{LookUpResultItem, Sense};
+
+/// Words too common to tell senses apart.
+const STOP_WORDS: [&str; 32] = [
+    "the", "and", "for", "are", "was", "were", "with", "that", "this", "from", "has", "have",
+    "had", "not", "but", "his", "her", "its", "our", "their", "they", "you", "she", "him", "who",
+    "which", "what", "will", "would", "can", "into", "been",
+];
+
+/// The sense of `item` sharing the most words with `context`, rendered with
+/// its part of speech, or `None` if no sense shares any.
+pub fn best_sense(item: &LookUpResultItem, context: &str) -> Option<String> {
+    let mut context_words = words(context);
+    context_words.remove(stem(&item.word.to_lowercase()));
+    if context_words.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(usize, String)> = None;
+    for pos in item.parts_of_speech.iter().chain(&item.detailed_senses) {
+        for sense in &pos.senses {
+            let score = words(&sense_text(sense))
+                .intersection(&context_words)
+                .count();
+            if score > best.as_ref().map_or(0, |(best, _)| *best) {
+                let rendered = format!("{} {}", pos.pos, sense.definition);
+                best = Some((score, rendered.trim().to_owned()));
+            }
+        }
+    }
+    best.map(|(_, sense)| sense)
+}
+
+fn sense_text(sense: &Sense) -> String {
+    let mut text = sense.definition.clone();
+    for example in &sense.examples {
+        text.push(' ');
+        text.push_str(&example.text);
+    }
+    text
+}
+
+/// Distinct, roughly stemmed words of `text` worth comparing.
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|x| x.chars().count() >= 3 && !STOP_WORDS.contains(&x.as_str()))
+        .map(|x| stem(&x).to_owned())
+        .collect()
+}
+
+/// Strip common English suffixes so that `rivers` matches `river`.
+fn stem(word: &str) -> &str {
+    ["ing", "ed", "es", "s"]
+        .iter()
+        .find_map(|suffix| {
+            word.strip_suffix(suffix)
+                .filter(|stem| stem.chars().count() >= 3)
+        })
+        .unwrap_or(word)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dict::{Example, PartOfSpeech};
+
+    #[test]
+    fn pick_sense_by_overlap() {
+        let mut river = Sense::new("the land alongside a river");
+        river
+            .examples
+            .push(Example::new("We walked along the bank.", None));
+        let item = LookUpResultItem {
+            word: "bank".to_owned(),
+            parts_of_speech: vec![
+                PartOfSpeech::new(
+                    "n.",
+                    vec![Sense::new("an organization that keeps money"), river],
+                ),
+                PartOfSpeech::new("v.", vec![Sense::new("to keep money in a bank")]),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            best_sense(&item, "They sat on the bank of the river, fishing.").as_deref(),
+            Some("n. the land alongside a river")
+        );
+        assert_eq!(
+            best_sense(&item, "How much money do you keep in the bank?").as_deref(),
+            Some("n. an organization that keeps money")
+        );
+        assert_eq!(best_sense(&item, "The bank was closed."), None);
+    }
+}
@@ -0,0 +1,136 @@
+//! Structured parts of a dictionary entry.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::DictType;
+
+/// Pronunciations of a headword.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Phonetics {
+    pub uk: Option<String>,
+    pub us: Option<String>,
+    /// Pronunciation given without a specific accent.
+    pub general: Option<String>,
+}
+
+impl Phonetics {
+    pub fn is_empty(&self) -> bool {
+        self.uk.is_none() && self.us.is_none() && self.general.is_none()
+    }
+}
+
+impl fmt::Display for Phonetics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<_> = [("UK ", &self.uk), ("US ", &self.us), ("", &self.general)]
+            .into_iter()
+            .filter_map(|(label, phonetic)| {
+                phonetic
+                    .as_ref()
+                    .map(|phonetic| format!("{}{}", label, phonetic))
+            })
+            .collect();
+        write!(f, "{}", parts.join("  "))
+    }
+}
+
+/// An example sentence with an optional translation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Example {
+    pub text: String,
+    pub translation: Option<String>,
+}
+
+impl Example {
+    pub fn new(text: impl Into<String>, translation: Option<String>) -> Self {
+        Self {
+            text: text.into(),
+            translation,
+        }
+    }
+}
+
+/// One numbered meaning of a word.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sense {
+    pub definition: String,
+    pub examples: Vec<Example>,
+}
+
+impl Sense {
+    pub fn new(definition: impl Into<String>) -> Self {
+        Self {
+            definition: definition.into(),
+            examples: Vec::new(),
+        }
+    }
+}
+
+/// Senses grouped under a part of speech, e.g. `n.` or `verb`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartOfSpeech {
+    pub pos: String,
+    pub senses: Vec<Sense>,
+}
+
+impl PartOfSpeech {
+    pub fn new(pos: impl Into<String>, senses: Vec<Sense>) -> Self {
+        Self {
+            pos: pos.into(),
+            senses,
+        }
+    }
+}
+
+/// An inflected form of the headword, e.g. the plural or past tense.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Inflection {
+    pub form: String,
+    pub word: String,
+}
+
+impl Inflection {
+    pub fn new(form: impl Into<String>, word: impl Into<String>) -> Self {
+        Self {
+            form: form.into(),
+            word: word.into(),
+        }
+    }
+}
+
+/// A common phrase containing the headword, with its translation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Phrase {
+    pub text: String,
+    pub translation: String,
+}
+
+impl Phrase {
+    pub fn new(text: impl Into<String>, translation: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            translation: translation.into(),
+        }
+    }
+}
+
+/// The dictionary an entry comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    pub name: String,
+    pub dict_type: DictType,
+}
+
+impl Source {
+    pub fn new(name: impl Into<String>, dict_type: DictType) -> Self {
+        Self {
+            name: name.into(),
+            dict_type,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Errors returned by dictionary look-ups.
+use std::time::Duration;
+
+use reqwest::{header::RETRY_AFTER, StatusCode};
+
+/// Why a dictionary could not answer a query.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum DictError {
+    /// The word is not in the dictionary.
+    #[display("not found")]
+    NotFound,
+    /// The service could not be reached or the connection broke.
+    #[display("network error: {_0}")]
+    Network(String),
+    /// The service rejected the credentials.
+    #[display("authentication failed: {_0}")]
+    Auth(String),
+    /// Too many requests, possibly with a hint when to retry.
+    #[display("rate limited: {message}")]
+    RateLimit {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The service failed on its side (HTTP 5xx).
+    #[display("server error: {_0}")]
+    Server(String),
+    /// The response did not have the expected shape.
+    #[display("failed to parse response: {_0}")]
+    Parse(String),
+    /// The local dictionary files are damaged.
+    #[display("dictionary corrupted: {_0}")]
+    Corrupted(String),
+    #[display("{_0}")]
+    Other(String),
+}
+
+impl std::error::Error for DictError {}
+
+impl DictError {
+    /// Might the same query succeed if it is retried?
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            DictError::Network(_) | DictError::RateLimit { .. } | DictError::Server(_)
+        )
+    }
+
+    /// Classify an unsuccessful HTTP status.
+    pub fn from_status(status: StatusCode, retry_after: Option<Duration>, body: &str) -> Self {
+        let mut message = status.to_string();
+        let body = body.trim();
+        if !body.is_empty() {
+            message.push_str(": ");
+            message.extend(body.chars().take(200));
+        }
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => DictError::Auth(message),
+            StatusCode::TOO_MANY_REQUESTS => DictError::RateLimit {
+                message,
+                retry_after,
+            },
+            status if status.is_server_error() => DictError::Server(message),
+            // A 404 may as well be a wrong URL or model as an unknown word, so
+            // only the dictionaries that know which it is return `NotFound`.
+            _ => DictError::Other(message),
+        }
+    }
+}
+
+impl From<reqwest::Error> for DictError {
+    fn from(e: reqwest::Error) -> Self {
+        if let Some(status) = e.status() {
+            DictError::from_status(status, None, "")
+        } else if e.is_decode() {
+            DictError::Parse(error_chain(&e))
+        } else {
+            DictError::Network(error_chain(&e))
+        }
+    }
+}
+
+/// Render an error with all its causes, which reqwest leaves out of `Display`.
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        if !message.ends_with(&cause_message) {
+            message.push_str(": ");
+            message.push_str(&cause_message);
+        }
+        source = cause.source();
+    }
+    message
+}
+
+impl From<serde_json::Error> for DictError {
+    fn from(e: serde_json::Error) -> Self {
+        DictError::Parse(e.to_string())
+    }
+}
+
+/// Parse the `Retry-After` header given in seconds.
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Turn an unsuccessful response into a [`DictError`], keeping the body for diagnosis.
+pub fn check_status(
+    response: reqwest::blocking::Response,
+) -> Result<reqwest::blocking::Response, DictError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = retry_after(response.headers());
+    let body = response.text().unwrap_or_default();
+    Err(DictError::from_status(status, retry_after, &body))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn classify_status() {
+        assert!(matches!(
+            DictError::from_status(StatusCode::UNAUTHORIZED, None, "invalid key"),
+            DictError::Auth(m) if m.ends_with("invalid key")
+        ));
+        assert!(matches!(
+            DictError::from_status(
+                StatusCode::TOO_MANY_REQUESTS,
+                Some(Duration::from_secs(3)),
+                ""
+            ),
+            DictError::RateLimit { retry_after: Some(d), .. } if d.as_secs() == 3
+        ));
+        assert!(DictError::from_status(StatusCode::BAD_GATEWAY, None, "").is_transient());
+        assert!(!DictError::from_status(StatusCode::BAD_REQUEST, None, "").is_transient());
+        assert_eq!(
+            DictError::from_status(StatusCode::NOT_FOUND, None, "model \"qwen\" not found"),
+            DictError::Other("404 Not Found: model \"qwen\" not found".to_owned())
+        );
+    }
+}
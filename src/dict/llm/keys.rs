@@ -0,0 +1,234 @@
+//! API keys of an LLM service, rotated when one is rejected or rate limited.
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::dict::DictError;
+
+/// How long a rate-limited key rests when the service gives no `Retry-After`.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Collect the keys given in `llm.toml`: literal keys, an environment
+/// variable read with `getenv` and the first line printed by a command.
+///
+/// A source giving no key is skipped with a warning as long as another
+/// gives one; only when none does is it an error.
+pub fn resolve_keys(
+    keys: &[String],
+    env: Option<&str>,
+    cmd: Option<&str>,
+    getenv: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<String>, DictError> {
+    let mut resolved = keys.to_vec();
+    let mut errors = Vec::new();
+    if let Some(var) = env {
+        match getenv(var) {
+            Some(key) if !key.trim().is_empty() => resolved.push(key.trim().to_owned()),
+            _ => errors.push(format!("Environment variable {} is not set", var)),
+        }
+    }
+    if let Some(cmd) = cmd {
+        match run_key_command(cmd) {
+            Ok(key) => resolved.push(key),
+            Err(e) => errors.push(e),
+        }
+    }
+    if resolved.is_empty() && !errors.is_empty() {
+        return Err(DictError::Auth(errors.join("; ")));
+    }
+    for error in errors {
+        eprintln!("Skipping an API key: {}", error);
+    }
+    Ok(resolved)
+}
+
+fn run_key_command(cmd: &str) -> Result<String, String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", cmd]).output()
+    } else {
+        Command::new("sh").args(["-c", cmd]).output()
+    }
+    .map_err(|e| format!("Failed to run `{}`: {}", cmd, e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let key = stdout.lines().next().unwrap_or_default().trim();
+    if !output.status.success() || key.is_empty() {
+        return Err(format!(
+            "`{}` did not print an API key: {}",
+            cmd,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(key.to_owned())
+}
+
+#[derive(Debug)]
+struct KeyState {
+    key: String,
+    /// The service rejected the key; it is not tried again.
+    revoked: bool,
+    cooldown_until: Option<Instant>,
+}
+
+/// Keys taken in turn, skipping revoked ones and those cooling down.
+#[derive(Debug, Default)]
+pub struct KeyPool {
+    keys: Mutex<(Vec<KeyState>, usize)>,
+}
+
+impl KeyPool {
+    pub fn new(keys: Vec<String>) -> Self {
+        // Start at a random key so separate runs spread the load.
+        let start = if keys.is_empty() {
+            0
+        } else {
+            rand::random_range(0..keys.len())
+        };
+        let keys = keys
+            .into_iter()
+            .map(|key| KeyState {
+                key,
+                revoked: false,
+                cooldown_until: None,
+            })
+            .collect();
+        Self {
+            keys: Mutex::new((keys, start)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.lock().unwrap().0.is_empty()
+    }
+
+    /// The next usable key, as its index and value.
+    pub fn next(&self) -> Option<(usize, String)> {
+        let mut guard = self.keys.lock().unwrap();
+        let (keys, cursor) = &mut *guard;
+        let now = Instant::now();
+        let len = keys.len();
+        let index = (0..len)
+            .map(|i| (*cursor + i) % len)
+            .find(|&i| !keys[i].revoked && keys[i].cooldown_until.is_none_or(|t| t <= now))?;
+        *cursor = (index + 1) % len;
+        Some((index, keys[index].key.clone()))
+    }
+
+    /// Note how a request with the key failed. Returns whether another key
+    /// may do better.
+    pub fn report(&self, index: usize, error: &DictError) -> bool {
+        let mut guard = self.keys.lock().unwrap();
+        let state = &mut guard.0[index];
+        match error {
+            DictError::Auth(_) => state.revoked = true,
+            DictError::RateLimit { retry_after, .. } => {
+                state.cooldown_until =
+                    Some(Instant::now() + retry_after.unwrap_or(DEFAULT_COOLDOWN))
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// How long until a rate-limited key can be used again, if any will be.
+    pub fn next_available_in(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.keys
+            .lock()
+            .unwrap()
+            .0
+            .iter()
+            .filter(|x| !x.revoked)
+            .filter_map(|x| x.cooldown_until)
+            .map(|t| t.saturating_duration_since(now))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rate_limit(retry_after: Option<Duration>) -> DictError {
+        DictError::RateLimit {
+            message: "429 Too Many Requests".to_owned(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn rotate_keys() {
+        let pool = KeyPool::new(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]);
+        let (first, _) = pool.next().unwrap();
+        let (second, _) = pool.next().unwrap();
+        assert_eq!(second, (first + 1) % 3);
+
+        assert!(pool.report(first, &DictError::Auth("401 Unauthorized".to_owned())));
+        assert!(pool.report(second, &rate_limit(Some(Duration::from_secs(5)))));
+        assert!(!pool.report(second, &DictError::Server("502".to_owned())));
+        let (third, _) = pool.next().unwrap();
+        assert_eq!(pool.next().unwrap().0, third);
+
+        assert!(pool.report(third, &rate_limit(None)));
+        assert!(pool.next().is_none());
+        let wait = pool.next_available_in().unwrap();
+        assert!(wait <= Duration::from_secs(5) && wait > Duration::from_secs(4));
+
+        pool.report(second, &rate_limit(Some(Duration::ZERO)));
+        assert_eq!(pool.next().unwrap().0, second);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn key_sources() {
+        let getenv = |var: &str| (var == "DIOXIONARY_TEST_KEY").then(|| "from-env\n".to_owned());
+        let keys = resolve_keys(
+            &["literal".to_owned()],
+            Some("DIOXIONARY_TEST_KEY"),
+            Some("printf 'from-cmd\\nlogin: me\\n'"),
+            getenv,
+        )
+        .unwrap();
+        assert_eq!(keys, ["literal", "from-env", "from-cmd"]);
+
+        assert!(matches!(
+            resolve_keys(&[], Some("DIOXIONARY_TEST_UNSET"), None, getenv),
+            Err(DictError::Auth(_))
+        ));
+        assert!(matches!(
+            resolve_keys(&[], None, Some("exit 1"), getenv),
+            Err(DictError::Auth(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skip_failing_key_sources() {
+        let keys = resolve_keys(
+            &["literal".to_owned()],
+            Some("DIOXIONARY_TEST_UNSET"),
+            Some("exit 1"),
+            |_: &str| None,
+        )
+        .unwrap();
+        assert_eq!(keys, ["literal"]);
+
+        let keys = resolve_keys(
+            &[],
+            Some("DIOXIONARY_TEST_UNSET"),
+            Some("echo key"),
+            |_: &str| None,
+        )
+        .unwrap();
+        assert_eq!(keys, ["key"]);
+
+        let Err(DictError::Auth(message)) = resolve_keys(
+            &[],
+            Some("DIOXIONARY_TEST_UNSET"),
+            Some("exit 1"),
+            |_: &str| None,
+        ) else {
+            panic!("expected no key to resolve");
+        };
+        assert!(message.contains("DIOXIONARY_TEST_UNSET") && message.contains("exit 1"));
+    }
+}
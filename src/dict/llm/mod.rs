@@ -0,0 +1,799 @@
+mod keys;
+mod provider;
+mod structured;
+mod template;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::sync::OnceLock;
+
+use crate::cache::CacheIdentity;
+use crate::net;
+
+use keys::KeyPool;
+pub use provider::LlmProvider;
+use provider::{ChatMessage, Role, StreamEvent};
+pub use template::{FewShotExample, PromptTemplate, DEFAULT_TEMPLATE};
+
+use super::{
+    error::check_status, Dict, DictError, DictType, LookUpResult, LookUpResultItem, Query,
+};
+
+const DEFAULT_PROMPT_TEMPLATE: &str = concat!(
+    "如果文本是一个英文单词，则模仿双语词典格式（不要 Markdown 格式）输出读音、释义和例句，",
+    "其他情况则直接翻译（直接翻译到另一种语言，不需要说明）：\n",
+    "\n",
+    "为下面的文本进行{{targets}}互译：\n",
+    "{{text}}",
+);
+
+/// Appended to templates without `{{context}}` when a sentence is given.
+const DEFAULT_CONTEXT_TEMPLATE: &str = concat!(
+    "\n\n",
+    "该文本出现在下面的句子中，请先给出它在句中的含义，再给出其他释义：\n",
+    "{{context}}",
+);
+
+fn default_prompt_template() -> String {
+    DEFAULT_PROMPT_TEMPLATE.to_string()
+}
+
+fn default_native_language() -> String {
+    "中文".to_string()
+}
+
+fn default_level() -> String {
+    "中级".to_string()
+}
+
+fn default_temperature() -> f64 {
+    0.7
+}
+
+fn default_stream() -> bool {
+    true
+}
+
+fn default_targets() -> Vec<String> {
+    vec!["中文".to_string(), "English".to_string()]
+}
+
+/// A `[[service]]` entry of `llm.toml`.
+#[derive(Deserialize, Debug, Default)]
+pub struct LlmDict {
+    pub name: String,
+    #[serde(default)]
+    pub provider: LlmProvider,
+    pub model_name: String,
+    /// Required for OpenAI-compatible services; the others have a default.
+    #[serde(default)]
+    pub api_url: String,
+    /// Not needed for Ollama.
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// Environment variable holding another key.
+    pub api_key_env: Option<String>,
+    /// Command printing another key on its first line, e.g. `pass show deepseek`.
+    pub api_key_cmd: Option<String>,
+    #[serde(default = "default_targets")]
+    pub targets: Vec<String>,
+    /// Filled in with `{{text}}`, `{{targets}}`, `{{level}}`,
+    /// `{{native_language}}` and `{{context}}`, the sentence the text
+    /// appeared in, if any.
+    #[serde(default = "default_prompt_template")]
+    pub prompt_template: String,
+    /// Sent as the system message before the prompt.
+    pub system_prompt: Option<String>,
+    /// Few-shot examples for `prompt_template`.
+    #[serde(default)]
+    pub examples: Vec<FewShotExample>,
+    /// More templates, picked with `--template` or a `#name` prefix.
+    #[serde(default)]
+    pub templates: BTreeMap<String, PromptTemplate>,
+    #[serde(default = "default_native_language")]
+    pub native_language: String,
+    /// The learner's level, e.g. `CET6` or `advanced`.
+    #[serde(default = "default_level")]
+    pub level: String,
+    #[serde(default = "default_temperature")]
+    pub temperature: f64,
+    /// Receive the answer piece by piece as it is generated.
+    #[serde(default = "default_stream")]
+    pub stream: bool,
+    /// Ask for JSON and fill in phonetics, senses, examples and levels.
+    /// Structured answers are not streamed.
+    #[serde(default)]
+    pub structured: bool,
+    /// Resolved on first use, so listing dictionaries runs no commands, and
+    /// again after a failure, e.g. once the variable is set or `pass` unlocked.
+    #[serde(skip)]
+    keys: OnceLock<KeyPool>,
+}
+
+impl LlmDict {
+    /// The template called `name`, or the one made of `prompt_template`.
+    /// Templates in `llm.toml` take precedence over the built-in ones.
+    fn template(&self, name: Option<&str>) -> Result<PromptTemplate, DictError> {
+        let template = match name.filter(|x| *x != DEFAULT_TEMPLATE) {
+            None => PromptTemplate {
+                prompt: self.prompt_template.clone(),
+                system_prompt: None,
+                examples: self.examples.clone(),
+            },
+            Some(name) => self
+                .templates
+                .get(name)
+                .cloned()
+                .or_else(|| template::builtin(name))
+                .ok_or_else(|| {
+                    DictError::Other(format!("No template `{}` for {}", name, self.name))
+                })?,
+        };
+        Ok(PromptTemplate {
+            system_prompt: template
+                .system_prompt
+                .or_else(|| self.system_prompt.clone()),
+            ..template
+        })
+    }
+
+    fn render(&self, template: &str, text: &str, context: Option<&str>) -> String {
+        template
+            .replace("{{text}}", text)
+            .replace("{{targets}}", &self.targets.join(", "))
+            .replace("{{level}}", &self.level)
+            .replace("{{native_language}}", &self.native_language)
+            .replace("{{context}}", context.unwrap_or_default())
+    }
+
+    /// The prompt for `query`, without the system message and examples.
+    fn build_prompt(&self, template: &PromptTemplate, query: &Query) -> String {
+        let mut prompt = template.prompt.clone();
+        if query.context.is_some() && !prompt.contains("{{context}}") {
+            prompt.push_str(DEFAULT_CONTEXT_TEMPLATE);
+        }
+        let prompt = self.render(&prompt, query.word, query.context);
+        if self.is_structured(query) {
+            format!(
+                "{}{}{}",
+                prompt,
+                structured::INSTRUCTIONS,
+                structured::schema()
+            )
+        } else {
+            prompt
+        }
+    }
+
+    /// The system message, the few-shot examples and the prompt.
+    fn build_messages(&self, query: &Query) -> Result<Vec<ChatMessage>, DictError> {
+        let template = self.template(query.template)?;
+        let mut messages = Vec::new();
+        if let Some(system_prompt) = &template.system_prompt {
+            let system_prompt = self.render(system_prompt, query.word, query.context);
+            messages.push(ChatMessage::new(Role::System, system_prompt));
+        }
+        for example in &template.examples {
+            let prompt = self.render(&template.prompt, &example.text, None);
+            messages.push(ChatMessage::new(Role::User, prompt));
+            messages.push(ChatMessage::new(Role::Assistant, example.answer.clone()));
+        }
+        messages.push(ChatMessage::new(
+            Role::User,
+            self.build_prompt(&template, query),
+        ));
+        Ok(messages)
+    }
+
+    /// Only answers to the default template fit the structured schema.
+    fn is_structured(&self, query: &Query) -> bool {
+        self.structured && query.template.is_none_or(|x| x == DEFAULT_TEMPLATE)
+    }
+
+    fn streams(&self, structured: bool) -> bool {
+        self.stream && !structured
+    }
+}
+
+impl LlmDict {
+    fn api_url(&self) -> Result<&str, DictError> {
+        match (self.api_url.as_str(), self.provider.default_api_url()) {
+            ("", Some(default)) => Ok(default),
+            ("", None) => Err(DictError::Other(format!("No api_url for {}", self.name))),
+            (url, _) => Ok(url),
+        }
+    }
+
+    fn chat(
+        &self,
+        messages: &[ChatMessage],
+        structured: bool,
+        api_key: Option<&str>,
+        sink: &mut dyn FnMut(&str),
+    ) -> Result<String, DictError> {
+        let request = self.provider.request(
+            net::client()?,
+            self,
+            self.api_url()?,
+            messages,
+            structured,
+            api_key,
+        );
+        let response = check_status(request.send()?)?;
+        if self.streams(structured) {
+            return read_stream(self.provider, std::io::BufReader::new(response), sink);
+        }
+        let response_json: serde_json::Value = response.json()?;
+        self.provider.parse_response(&response_json)
+    }
+
+    fn keys(&self) -> Result<&KeyPool, DictError> {
+        if let Some(keys) = self.keys.get() {
+            return Ok(keys);
+        }
+        let keys = keys::resolve_keys(
+            &self.api_keys,
+            self.api_key_env.as_deref(),
+            self.api_key_cmd.as_deref(),
+            |var| std::env::var(var).ok(),
+        )?;
+        Ok(self.keys.get_or_init(|| KeyPool::new(keys)))
+    }
+
+    /// Ask the service, moving on to the next key when one is rejected or
+    /// rate limited.
+    fn translate(&self, query: &Query, sink: &mut dyn FnMut(&str)) -> Result<String, DictError> {
+        let messages = self.build_messages(query)?;
+        let structured = self.is_structured(query);
+        let keys = self.keys()?;
+        if keys.is_empty() {
+            if self.provider.requires_api_key() {
+                return Err(DictError::Auth("No API key available".to_owned()));
+            }
+            return self.chat(&messages, structured, None, sink);
+        }
+
+        let mut last_error = None;
+        while let Some((index, key)) = keys.next() {
+            match self.chat(&messages, structured, Some(&key), sink) {
+                Err(e) if keys.report(index, &e) => last_error = Some(e),
+                result => return result,
+            }
+        }
+        // Waiting briefly for a rate-limited key is left to the caller's
+        // retries; otherwise the next service should be tried.
+        match keys.next_available_in() {
+            Some(wait) if wait <= net::retry_policy().max_delay => Err(DictError::RateLimit {
+                message: "All API keys are rate limited".to_owned(),
+                retry_after: Some(wait),
+            }),
+            _ => Err(DictError::Other(format!(
+                "No usable API key for {}: {}",
+                self.name,
+                last_error.map_or("all keys are cooling down".to_owned(), |e| e.to_string())
+            ))),
+        }
+    }
+}
+
+/// Assemble a streamed answer, passing every piece to `sink`.
+fn read_stream(
+    provider: LlmProvider,
+    reader: impl BufRead,
+    sink: &mut dyn FnMut(&str),
+) -> Result<String, DictError> {
+    let mut text = String::new();
+    let interrupted = |text: &str, reason: String| {
+        DictError::Other(format!(
+            "response interrupted after {} characters: {}",
+            text.chars().count(),
+            reason
+        ))
+    };
+    for line in reader.lines() {
+        let line = line.map_err(|e| interrupted(&text, e.to_string()))?;
+        for event in provider.parse_stream_line(&line)? {
+            match event {
+                StreamEvent::Text(content) => {
+                    sink(&content);
+                    text.push_str(&content);
+                }
+                StreamEvent::Done => return Ok(text),
+                StreamEvent::Error(message) => return Err(interrupted(&text, message)),
+            }
+        }
+    }
+    Err(interrupted(&text, "the stream ended early".to_owned()))
+}
+
+impl LlmDict {
+    fn answer(&self, query: &Query, sink: &mut dyn FnMut(&str)) -> Result<LookUpResult, DictError> {
+        let word = query.word;
+        if !self.is_structured(query) {
+            let translation = self.translate(query, sink)?;
+            return Ok(LookUpResult::Exact(
+                LookUpResultItem::new(word, translation).with_source(&self.name, DictType::LLM),
+            ));
+        }
+
+        // Models occasionally break the format; one more try usually fixes it.
+        let reply = self.translate(query, sink)?;
+        let item = structured::parse_reply(&self.name, word, &reply).or_else(|_| {
+            let reply = self.translate(query, sink)?;
+            structured::parse_reply(&self.name, word, &reply)
+        })?;
+        Ok(LookUpResult::Exact(item))
+    }
+}
+
+impl Dict for LlmDict {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn type_(&self) -> DictType {
+        DictType::LLM
+    }
+
+    fn supports_fuzzy_search(&self) -> bool {
+        false
+    }
+
+    fn look_up(&self, enable_fuzzy: bool, word: &str) -> Result<LookUpResult, DictError> {
+        self.look_up_streaming(enable_fuzzy, word, &mut |_| ())
+    }
+
+    fn look_up_streaming(
+        &self,
+        _: bool,
+        word: &str,
+        sink: &mut dyn FnMut(&str),
+    ) -> Result<LookUpResult, DictError> {
+        self.answer(&Query::new(word), sink)
+    }
+
+    fn understands_queries(&self) -> bool {
+        true
+    }
+
+    fn look_up_query(
+        &self,
+        query: &Query,
+        sink: &mut dyn FnMut(&str),
+    ) -> Result<LookUpResult, DictError> {
+        self.answer(query, sink)
+    }
+
+    fn word_count(&self) -> Option<usize> {
+        None
+    }
+
+    fn cache_identity(&self) -> Option<CacheIdentity> {
+        // Answers change with the prompts; those to a named template are
+        // told apart by the cache key.
+        let prompts = format!(
+            "{:?}{:?}",
+            self.build_messages(&Query::new("")).ok(),
+            self.templates
+        );
+        Some(CacheIdentity::new(&self.name).with_model(&self.model_name, &prompts))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn prompt(llm_dict: &LlmDict, query: &Query) -> String {
+        llm_dict.build_prompt(&llm_dict.template(query.template).unwrap(), query)
+    }
+
+    #[test]
+    fn test_basic_replacement() {
+        let llm_dict = LlmDict {
+            prompt_template: "Text: {{text}}. Targets: {{targets}}".to_string(),
+            targets: vec!["Chinese".to_string(), "English".to_string()],
+            ..Default::default()
+        };
+
+        let result = prompt(&llm_dict, &Query::new("rust"));
+        assert_eq!(result, "Text: rust. Targets: Chinese, English");
+    }
+
+    #[test]
+    fn context_replacement() {
+        let mut llm_dict = LlmDict {
+            prompt_template: "{{text}} in: {{context}}".to_string(),
+            ..Default::default()
+        };
+        let query = Query::new("bank").in_context(Some("We sat on the bank."));
+        assert_eq!(prompt(&llm_dict, &query), "bank in: We sat on the bank.");
+        assert_eq!(prompt(&llm_dict, &Query::new("bank")), "bank in: ");
+
+        llm_dict.prompt_template = "{{text}}".to_string();
+        let prompt = prompt(&llm_dict, &query);
+        assert!(prompt.starts_with("bank\n\n"));
+        assert!(prompt.ends_with("\nWe sat on the bank."));
+    }
+
+    #[test]
+    fn named_templates() {
+        let llm_dict: LlmDict = toml::from_str(
+            r#"
+            name = "DeepSeek"
+            model_name = "deepseek-chat"
+            system_prompt = "You are a dictionary."
+            native_language = "English"
+            level = "GRE"
+            prompt_template = "Define {{text}}"
+            examples = [{ text = "rust", answer = "iron oxide" }]
+
+            [templates.etymology]
+            prompt = "Etymology of {{text}} in {{native_language}}"
+            "#,
+        )
+        .unwrap();
+
+        let messages = llm_dict.build_messages(&Query::new("crate")).unwrap();
+        assert_eq!(
+            messages,
+            [
+                ChatMessage::new(Role::System, "You are a dictionary."),
+                ChatMessage::new(Role::User, "Define rust"),
+                ChatMessage::new(Role::Assistant, "iron oxide"),
+                ChatMessage::new(Role::User, "Define crate"),
+            ]
+        );
+
+        let query = Query::new("crate").with_template(Some("etymology"));
+        assert_eq!(
+            llm_dict.build_messages(&query).unwrap(),
+            [
+                ChatMessage::new(Role::System, "You are a dictionary."),
+                ChatMessage::new(Role::User, "Etymology of crate in English"),
+            ]
+        );
+
+        // Built-in templates bring their own system message.
+        let query = Query::new("crate").with_template(Some("teacher"));
+        let messages = llm_dict.build_messages(&query).unwrap();
+        assert!(messages[0].content.contains("GRE"));
+        assert!(messages[1].content.ends_with("crate"));
+
+        let query = Query::new("crate").with_template(Some("poem"));
+        assert!(matches!(
+            llm_dict.build_messages(&query),
+            Err(DictError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn test_translate() {
+        let api_key = std::env::var("API_KEY");
+        if api_key.is_err() {
+            println!("API_KEY not set");
+            return;
+        }
+
+        let llm_dict = LlmDict {
+            name: "DeepSeek".to_string(),
+            provider: LlmProvider::OpenAiCompatible,
+            model_name: "deepseek-chat".to_string(),
+            api_url: "https://api.deepseek.com/chat/completions".to_string(),
+            api_keys: vec![api_key.unwrap()],
+            targets: default_targets(),
+            prompt_template: default_prompt_template(),
+            temperature: default_temperature(),
+            stream: default_stream(),
+            ..Default::default()
+        };
+
+        for text in ["rust", "铁锈"] {
+            let result = llm_dict.translate(&Query::new(text), &mut |_| ());
+            assert!(result.is_ok());
+            println!("{}", result.unwrap());
+        }
+    }
+
+    #[test]
+    fn read_openai_stream() {
+        let stream = concat!(
+            ": keep-alive\n\n",
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"铁\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"锈\"},\"finish_reason\":null}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let mut pieces = vec![];
+        let text = read_stream(LlmProvider::OpenAiCompatible, stream.as_bytes(), &mut |x| {
+            pieces.push(x.to_owned())
+        });
+        assert_eq!(text, Ok("铁锈".to_owned()));
+        assert_eq!(pieces, ["铁", "锈"]);
+
+        let broken = "data: {\"choices\":[{\"delta\":{\"content\":\"铁\"}}]}\n";
+        assert!(matches!(
+            read_stream(LlmProvider::OpenAiCompatible, broken.as_bytes(), &mut |_| ()),
+            Err(DictError::Other(e)) if e.contains("after 1 characters")
+        ));
+
+        let error = "data: {\"error\":{\"message\":\"overloaded\"}}\n";
+        assert!(matches!(
+            read_stream(LlmProvider::OpenAiCompatible, error.as_bytes(), &mut |_| ()),
+            Err(DictError::Other(e)) if e.ends_with("overloaded")
+        ));
+    }
+
+    type MockResponse = (&'static str, &'static str, &'static str);
+
+    /// Answer requests with `responses`, given as status, content type and
+    /// body, and return the requests as received.
+    fn serve(responses: Vec<MockResponse>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, content_type, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_lowercase();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text
+                            .lines()
+                            .find_map(|x| x.strip_prefix("content-length:"))
+                            .map_or(0, |x| x.trim().parse().unwrap());
+                        if request.len() >= end + 4 + length {
+                            break;
+                        }
+                    }
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    content_type,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    /// Answer one request with `body` and return the request as received.
+    fn serve_once(
+        content_type: &'static str,
+        body: &'static str,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        serve(vec![("200 OK", content_type, body)])
+    }
+
+    fn dict(provider: LlmProvider, api_url: String, stream: bool) -> LlmDict {
+        LlmDict {
+            name: "mock".to_owned(),
+            provider,
+            model_name: "model".to_owned(),
+            api_url,
+            api_keys: vec!["secret".to_owned()],
+            prompt_template: "{{text}}".to_owned(),
+            stream,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ollama_without_key() {
+        let (url, server) = serve_once(
+            "application/json",
+            r#"{"model":"model","message":{"role":"assistant","content":"铁锈"},"done":true}"#,
+        );
+        let mut llm = dict(LlmProvider::Ollama, format!("{}/api/chat", url), false);
+        llm.api_keys.clear();
+        assert_eq!(
+            llm.translate(&Query::new("rust"), &mut |_| ()),
+            Ok("铁锈".to_owned())
+        );
+        let request = server.join().unwrap().remove(0);
+        assert!(request.starts_with("POST /api/chat "));
+        assert!(!request.to_lowercase().contains("authorization"));
+        assert!(request.contains(r#""options":{"temperature":"#));
+    }
+
+    #[test]
+    fn ollama_streaming() {
+        let (url, server) = serve_once(
+            "application/x-ndjson",
+            concat!(
+                "{\"message\":{\"role\":\"assistant\",\"content\":\"铁\"},\"done\":false}\n",
+                "{\"message\":{\"role\":\"assistant\",\"content\":\"锈\"},\"done\":false}\n",
+                "{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n",
+            ),
+        );
+        let llm = dict(LlmProvider::Ollama, url, true);
+        let mut pieces = 0;
+        assert_eq!(
+            llm.translate(&Query::new("rust"), &mut |_| pieces += 1),
+            Ok("铁锈".to_owned())
+        );
+        assert_eq!(pieces, 2);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn anthropic_messages() {
+        let (url, server) = serve_once(
+            "text/event-stream",
+            concat!(
+                "event: message_start\n",
+                "data: {\"type\":\"message_start\",\"message\":{\"content\":[]}}\n\n",
+                "event: content_block_delta\n",
+                "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"铁锈\"}}\n\n",
+                "event: message_stop\n",
+                "data: {\"type\":\"message_stop\"}\n\n",
+            ),
+        );
+        let mut llm = dict(LlmProvider::Anthropic, format!("{}/v1/messages", url), true);
+        llm.system_prompt = Some("You are a dictionary.".to_owned());
+        assert_eq!(
+            llm.translate(&Query::new("rust"), &mut |_| ()),
+            Ok("铁锈".to_owned())
+        );
+        let request = server.join().unwrap().remove(0);
+        assert!(request.contains(r#""system":"You are a dictionary.""#));
+        assert!(request.contains(r#""messages":[{"content":"rust","role":"user"}]"#));
+        let request = request.to_lowercase();
+        assert!(request.contains("x-api-key: secret"));
+        assert!(request.contains("anthropic-version:"));
+        assert!(request.contains(r#""max_tokens":"#));
+    }
+
+    #[test]
+    fn gemini_generate_content() {
+        let (url, server) = serve_once(
+            "application/json",
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"铁锈"}]},"finishReason":"STOP"}]}"#,
+        );
+        let llm = dict(LlmProvider::Gemini, format!("{}/v1beta/", url), false);
+        assert_eq!(
+            llm.translate(&Query::new("rust"), &mut |_| ()),
+            Ok("铁锈".to_owned())
+        );
+        let request = server.join().unwrap().remove(0);
+        assert!(request.starts_with("POST /v1beta/models/model:generateContent "));
+        assert!(request.to_lowercase().contains("x-goog-api-key: secret"));
+        assert!(request.contains(r#""parts":[{"text":"rust"}]"#));
+    }
+
+    #[test]
+    fn structured_entry() {
+        let (url, server) = serve(vec![
+            (
+                "200 OK",
+                "application/json",
+                r#"{"choices":[{"message":{"content":"rust: 铁锈"}}]}"#,
+            ),
+            (
+                "200 OK",
+                "application/json",
+                r#"{"choices":[{"message":{"content":"{\"word\":\"rust\",\"senses\":[{\"pos\":\"n.\",\"definition\":\"铁锈\"}],\"difficulty_levels\":[\"CET4\"]}"}}]}"#,
+            ),
+        ]);
+        let mut llm = dict(LlmProvider::OpenAiCompatible, url, true);
+        llm.structured = true;
+        let LookUpResult::Exact(item) = llm.look_up(false, "rust").unwrap() else {
+            panic!("expected an exact result");
+        };
+        assert_eq!(item.parts_of_speech[0].senses[0].definition, "铁锈");
+        assert_eq!(item.difficulty_levels, ["CET4"]);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains(r#""response_format":{"type":"json_object"}"#));
+        assert!(requests[0].contains(r#""stream":false"#));
+    }
+
+    #[test]
+    fn openai_requires_url_and_key() {
+        let llm = dict(LlmProvider::OpenAiCompatible, String::new(), false);
+        assert!(matches!(
+            llm.translate(&Query::new("rust"), &mut |_| ()),
+            Err(DictError::Other(_))
+        ));
+        let mut llm = dict(LlmProvider::OpenAiCompatible, String::new(), false);
+        llm.api_keys.clear();
+        assert!(matches!(
+            llm.translate(&Query::new("rust"), &mut |_| ()),
+            Err(DictError::Auth(_))
+        ));
+
+        let config: LlmDict = toml::from_str(
+            r#"
+            name = "Local"
+            provider = "ollama"
+            model_name = "qwen2.5"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.provider, LlmProvider::Ollama);
+        assert!(config.api_keys.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_keys_again_after_failure() {
+        let path = std::env::temp_dir().join(format!("dioxionary-key-{}", std::process::id()));
+        let mut llm = dict(LlmProvider::OpenAiCompatible, String::new(), false);
+        llm.api_keys.clear();
+        llm.api_key_cmd = Some(format!("cat '{}'", path.display()));
+        assert!(matches!(llm.keys(), Err(DictError::Auth(_))));
+
+        std::fs::write(&path, "secret\n").unwrap();
+        let keys = llm.keys().map(|x| x.next());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(keys, Ok(Some((0, "secret".to_owned()))));
+    }
+
+    #[test]
+    fn rotate_api_keys() {
+        let answer = r#"{"choices":[{"message":{"content":"铁锈"}}]}"#;
+        let (url, server) = serve(vec![
+            (
+                "401 Unauthorized",
+                "application/json",
+                r#"{"error":"invalid key"}"#,
+            ),
+            ("200 OK", "application/json", answer),
+            ("200 OK", "application/json", answer),
+        ]);
+        let mut llm = dict(LlmProvider::OpenAiCompatible, url, false);
+        llm.api_keys = vec!["first".to_owned(), "second".to_owned()];
+        assert_eq!(
+            llm.translate(&Query::new("rust"), &mut |_| ()),
+            Ok("铁锈".to_owned())
+        );
+        assert_eq!(
+            llm.translate(&Query::new("rust"), &mut |_| ()),
+            Ok("铁锈".to_owned())
+        );
+
+        let keys: Vec<_> = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|x| {
+                let x = x.to_lowercase();
+                let start = x.find("bearer ").unwrap() + "bearer ".len();
+                x[start..].lines().next().unwrap().to_owned()
+            })
+            .collect();
+        // The rejected key is not tried again.
+        assert_ne!(keys[0], keys[1]);
+        assert_eq!(keys[1], keys[2]);
+    }
+
+    #[test]
+    fn all_keys_rate_limited() {
+        let (url, server) = serve(vec![(
+            "429 Too Many Requests",
+            "application/json",
+            r#"{"error":"slow down"}"#,
+        )]);
+        let llm = dict(LlmProvider::OpenAiCompatible, url, false);
+        // Too long to wait for, so the next service should be tried.
+        assert!(matches!(
+            llm.translate(&Query::new("rust"), &mut |_| ()),
+            Err(DictError::Other(e)) if e.contains("rate limited")
+        ));
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+//! Request and response shapes of the supported LLM APIs.
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{structured, LlmDict};
+use crate::dict::DictError;
+
+/// Anthropic requires an upper bound on the answer length.
+const ANTHROPIC_MAX_TOKENS: u32 = 2048;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// The API a `[[service]]` speaks, selected by `provider` in `llm.toml`.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LlmProvider {
+    /// OpenAI chat completions, also offered by DeepSeek, GLM and many others.
+    #[default]
+    #[serde(alias = "openai")]
+    OpenAiCompatible,
+    /// Ollama's native `/api/chat`.
+    Ollama,
+    /// Anthropic Messages.
+    Anthropic,
+    /// Google Gemini `generateContent`.
+    Gemini,
+}
+
+/// Who a chat message is from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl Role {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+        }
+    }
+}
+
+/// A piece of a streamed answer.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum StreamEvent {
+    Text(String),
+    Done,
+    Error(String),
+}
+
+impl LlmProvider {
+    /// The endpoint used when `api_url` is not given. For Gemini it is the
+    /// base the model path is appended to.
+    pub fn default_api_url(&self) -> Option<&'static str> {
+        match self {
+            LlmProvider::OpenAiCompatible => None,
+            LlmProvider::Ollama => Some("http://localhost:11434/api/chat"),
+            LlmProvider::Anthropic => Some("https://api.anthropic.com/v1/messages"),
+            LlmProvider::Gemini => Some("https://generativelanguage.googleapis.com/v1beta"),
+        }
+    }
+
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, LlmProvider::Ollama)
+    }
+
+    /// Is the stream newline-delimited JSON rather than server-sent events?
+    fn streams_json_lines(&self) -> bool {
+        matches!(self, LlmProvider::Ollama)
+    }
+
+    pub(super) fn request(
+        &self,
+        client: &Client,
+        dict: &LlmDict,
+        api_url: &str,
+        messages: &[ChatMessage],
+        structured: bool,
+        api_key: Option<&str>,
+    ) -> RequestBuilder {
+        let stream = dict.streams(structured);
+        // Anthropic and Gemini take the system message apart from the others.
+        let system = messages
+            .iter()
+            .find(|x| x.role == Role::System)
+            .map(|x| x.content.as_str());
+        let turns = messages.iter().filter(|x| x.role != Role::System);
+        let chat_messages: Vec<_> = messages
+            .iter()
+            .map(|x| json!({ "role": x.role.as_str(), "content": x.content }))
+            .collect();
+        let (url, mut body) = match self {
+            LlmProvider::OpenAiCompatible => (
+                api_url.to_owned(),
+                json!({
+                    "model": dict.model_name,
+                    "messages": chat_messages,
+                    "temperature": dict.temperature,
+                    "stream": stream,
+                }),
+            ),
+            LlmProvider::Ollama => (
+                api_url.to_owned(),
+                json!({
+                    "model": dict.model_name,
+                    "messages": chat_messages,
+                    "options": { "temperature": dict.temperature },
+                    "stream": stream,
+                }),
+            ),
+            LlmProvider::Anthropic => {
+                let messages: Vec<_> = turns
+                    .map(|x| json!({ "role": x.role.as_str(), "content": x.content }))
+                    .collect();
+                let mut body = json!({
+                    "model": dict.model_name,
+                    "messages": messages,
+                    "max_tokens": ANTHROPIC_MAX_TOKENS,
+                    "temperature": dict.temperature,
+                    "stream": stream,
+                });
+                if let Some(system) = system {
+                    body["system"] = json!(system);
+                }
+                (api_url.to_owned(), body)
+            }
+            LlmProvider::Gemini => {
+                let method = if stream {
+                    "streamGenerateContent?alt=sse"
+                } else {
+                    "generateContent"
+                };
+                let url = format!(
+                    "{}/models/{}:{}",
+                    api_url.trim_end_matches('/'),
+                    dict.model_name,
+                    method
+                );
+                let contents: Vec<_> = turns
+                    .map(|x| {
+                        let role = if x.role == Role::Assistant {
+                            "model"
+                        } else {
+                            "user"
+                        };
+                        json!({ "role": role, "parts": [{ "text": x.content }] })
+                    })
+                    .collect();
+                let mut body = json!({
+                    "contents": contents,
+                    "generationConfig": { "temperature": dict.temperature },
+                });
+                if let Some(system) = system {
+                    body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+                }
+                (url, body)
+            }
+        };
+        if structured {
+            self.request_json(&mut body);
+        }
+
+        let mut request = client.post(url).json(&body);
+        if *self == LlmProvider::Anthropic {
+            request = request.header("anthropic-version", ANTHROPIC_VERSION);
+        }
+        match (self, api_key) {
+            (_, None) => request,
+            (LlmProvider::Anthropic, Some(key)) => request.header("x-api-key", key),
+            (LlmProvider::Gemini, Some(key)) => request.header("x-goog-api-key", key),
+            (_, Some(key)) => request.bearer_auth(key),
+        }
+    }
+
+    /// Ask for a JSON reply where the API has a way to. Anthropic has none
+    /// and relies on the prompt alone.
+    fn request_json(&self, body: &mut Value) {
+        match self {
+            LlmProvider::OpenAiCompatible => {
+                body["response_format"] = json!({ "type": "json_object" })
+            }
+            LlmProvider::Ollama => body["format"] = structured::schema(),
+            LlmProvider::Anthropic => {}
+            LlmProvider::Gemini => {
+                body["generationConfig"]["responseMimeType"] = json!("application/json")
+            }
+        }
+    }
+
+    /// Extract the answer from a complete response.
+    pub(super) fn parse_response(&self, response: &Value) -> Result<String, DictError> {
+        let text = match self {
+            LlmProvider::OpenAiCompatible => &response["choices"][0]["message"]["content"],
+            LlmProvider::Ollama => &response["message"]["content"],
+            LlmProvider::Anthropic => &response["content"][0]["text"],
+            LlmProvider::Gemini => &response["candidates"][0]["content"]["parts"][0]["text"],
+        };
+        text.as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| DictError::Parse("Invalid response format".to_owned()))
+    }
+
+    /// Interpret one line of a streamed response.
+    pub(super) fn parse_stream_line(&self, line: &str) -> Result<Vec<StreamEvent>, DictError> {
+        let data = if self.streams_json_lines() {
+            line.trim()
+        } else {
+            // Other fields, comments and blank separator lines carry no content.
+            match line.strip_prefix("data:") {
+                Some(data) => data.trim(),
+                None => return Ok(vec![]),
+            }
+        };
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
+        if data == "[DONE]" {
+            return Ok(vec![StreamEvent::Done]);
+        }
+
+        let event: Value = serde_json::from_str(data)?;
+        let mut events = vec![];
+        if let Some(error) = event.get("error") {
+            let message = error["message"]
+                .as_str()
+                .or(error.as_str())
+                .unwrap_or("unknown error");
+            events.push(StreamEvent::Error(message.to_owned()));
+            return Ok(events);
+        }
+        let (text, done) = match self {
+            LlmProvider::OpenAiCompatible => {
+                let choice = &event["choices"][0];
+                (
+                    &choice["delta"]["content"],
+                    choice["finish_reason"].is_string(),
+                )
+            }
+            LlmProvider::Ollama => (&event["message"]["content"], event["done"] == true),
+            LlmProvider::Anthropic => (&event["delta"]["text"], event["type"] == "message_stop"),
+            LlmProvider::Gemini => {
+                let candidate = &event["candidates"][0];
+                (
+                    &candidate["content"]["parts"][0]["text"],
+                    candidate["finishReason"].is_string(),
+                )
+            }
+        };
+        if let Some(text) = text.as_str().filter(|x| !x.is_empty()) {
+            events.push(StreamEvent::Text(text.to_owned()));
+        }
+        if done {
+            events.push(StreamEvent::Done);
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_stream_lines() {
+        let openai = LlmProvider::OpenAiCompatible;
+        assert_eq!(
+            openai
+                .parse_stream_line(r#"data: {"choices":[{"delta":{"content":"铁"}}]}"#)
+                .unwrap(),
+            [StreamEvent::Text("铁".to_owned())]
+        );
+        assert_eq!(
+            openai.parse_stream_line("data: [DONE]").unwrap(),
+            [StreamEvent::Done]
+        );
+        assert!(openai.parse_stream_line(": keep-alive").unwrap().is_empty());
+
+        let ollama = LlmProvider::Ollama;
+        assert_eq!(
+            ollama
+                .parse_stream_line(r#"{"message":{"role":"assistant","content":""},"done":true}"#)
+                .unwrap(),
+            [StreamEvent::Done]
+        );
+        assert_eq!(
+            ollama
+                .parse_stream_line(r#"{"error":"model not found"}"#)
+                .unwrap(),
+            [StreamEvent::Error("model not found".to_owned())]
+        );
+
+        let anthropic = LlmProvider::Anthropic;
+        assert_eq!(
+            anthropic
+                .parse_stream_line(
+                    r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"锈"}}"#
+                )
+                .unwrap(),
+            [StreamEvent::Text("锈".to_owned())]
+        );
+        assert!(anthropic
+            .parse_stream_line("event: content_block_delta")
+            .unwrap()
+            .is_empty());
+
+        let gemini = LlmProvider::Gemini;
+        assert_eq!(
+            gemini
+                .parse_stream_line(
+                    r#"data: {"candidates":[{"content":{"parts":[{"text":"锈"}]},"finishReason":"STOP"}]}"#
+                )
+                .unwrap(),
+            [StreamEvent::Text("锈".to_owned()), StreamEvent::Done]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Dictionary entries requested from an LLM as JSON.
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::dict::{DictError, DictType, Example, LookUpResultItem, PartOfSpeech, Phonetics, Sense};
+use crate::history::ALLOWED_DIFFICULTY_LEVELS;
+
+/// Appended to the prompt in structured mode.
+pub(super) const INSTRUCTIONS: &str = concat!(
+    "\n\n",
+    "Reply with a single JSON object and nothing else, following this JSON schema. ",
+    "Give `translation` instead of `senses` when the text is not a single word or phrase.\n",
+);
+
+/// The shape of the reply, also sent to services that accept a schema.
+pub(super) fn schema() -> Value {
+    let example = json!({
+        "type": "object",
+        "properties": {
+            "text": { "type": "string" },
+            "translation": { "type": "string" },
+        },
+        "required": ["text"],
+    });
+    json!({
+        "type": "object",
+        "properties": {
+            "word": { "type": "string" },
+            "phonetics": {
+                "type": "object",
+                "properties": {
+                    "uk": { "type": "string" },
+                    "us": { "type": "string" },
+                },
+            },
+            "senses": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "pos": { "type": "string" },
+                        "definition": { "type": "string" },
+                        "examples": { "type": "array", "items": example },
+                    },
+                    "required": ["pos", "definition"],
+                },
+            },
+            "examples": { "type": "array", "items": example },
+            "difficulty_levels": {
+                "type": "array",
+                "items": { "type": "string", "enum": ALLOWED_DIFFICULTY_LEVELS },
+            },
+            "translation": { "type": "string" },
+        },
+        "required": ["word"],
+    })
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    word: String,
+    #[serde(default)]
+    phonetics: Phonetics,
+    #[serde(default)]
+    senses: Vec<ReplySense>,
+    #[serde(default)]
+    examples: Vec<Example>,
+    #[serde(default)]
+    difficulty_levels: Vec<String>,
+    #[serde(default)]
+    translation: String,
+}
+
+#[derive(Deserialize)]
+struct ReplySense {
+    pos: String,
+    definition: String,
+    #[serde(default)]
+    examples: Vec<Example>,
+}
+
+/// Take the JSON object out of a reply, which some models wrap in a Markdown fence.
+fn extract_object(reply: &str) -> Option<&str> {
+    let start = reply.find('{')?;
+    let end = reply.rfind('}')?;
+    (start < end).then(|| &reply[start..=end])
+}
+
+/// Validate a reply into an entry.
+pub(super) fn parse_reply(
+    dict_name: &str,
+    word: &str,
+    reply: &str,
+) -> Result<LookUpResultItem, DictError> {
+    let json = extract_object(reply)
+        .ok_or_else(|| DictError::Parse("The reply holds no JSON object".to_owned()))?;
+    let reply: Reply = serde_json::from_str(json)?;
+    if reply.senses.is_empty() && reply.translation.trim().is_empty() {
+        return Err(DictError::Parse(
+            "The reply has neither senses nor a translation".to_owned(),
+        ));
+    }
+
+    let mut item = LookUpResultItem::new(
+        if reply.word.trim().is_empty() {
+            word
+        } else {
+            reply.word.trim()
+        },
+        reply.translation,
+    )
+    .with_source(dict_name, DictType::LLM);
+    item.phonetics = reply.phonetics;
+    for sense in reply.senses {
+        let pos = sense.pos.trim().to_owned();
+        let sense = Sense {
+            definition: sense.definition,
+            examples: sense.examples,
+        };
+        match item.parts_of_speech.iter_mut().find(|x| x.pos == pos) {
+            Some(existing) => existing.senses.push(sense),
+            None => item
+                .parts_of_speech
+                .push(PartOfSpeech::new(pos, vec![sense])),
+        }
+    }
+    item.examples = reply.examples;
+    item.difficulty_levels = reply
+        .difficulty_levels
+        .into_iter()
+        .filter_map(|level| {
+            ALLOWED_DIFFICULTY_LEVELS
+                .iter()
+                .find(|x| x.eq_ignore_ascii_case(level.trim()))
+                .map(|x| x.to_string())
+        })
+        .collect();
+    Ok(item)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_entry() {
+        let reply = r#"```json
+{
+  "word": "rust",
+  "phonetics": {"uk": "/rʌst/", "us": "/rʌst/"},
+  "senses": [
+    {"pos": "n.", "definition": "铁锈", "examples": [{"text": "covered in rust", "translation": "锈迹斑斑"}]},
+    {"pos": "v.", "definition": "生锈"},
+    {"pos": "n.", "definition": "锈病"}
+  ],
+  "difficulty_levels": ["cet4", "HSK"]
+}
+```"#;
+        let item = parse_reply("DeepSeek", "Rust", reply).unwrap();
+        assert_eq!(item.word, "rust");
+        assert_eq!(item.phonetics.us.as_deref(), Some("/rʌst/"));
+        assert_eq!(item.parts_of_speech.len(), 2);
+        assert_eq!(item.parts_of_speech[0].senses[1].definition, "锈病");
+        assert_eq!(
+            item.parts_of_speech[0].senses[0].examples[0]
+                .translation
+                .as_deref(),
+            Some("锈迹斑斑")
+        );
+        assert_eq!(item.difficulty_levels, ["CET4"]);
+        assert!(item.translation.is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_replies() {
+        for reply in [
+            "rust: 铁锈",
+            r#"{"word": "rust", "senses": "铁锈"}"#,
+            r#"{"word": "rust"}"#,
+        ] {
+            assert!(matches!(
+                parse_reply("DeepSeek", "rust", reply),
+                Err(DictError::Parse(_))
+            ));
+        }
+        let item = parse_reply(
+            "DeepSeek",
+            "Iron rusts.",
+            r#"{"word": "", "translation": "铁会生锈。"}"#,
+        )
+        .unwrap();
+        assert_eq!(item.word, "Iron rusts.");
+        assert_eq!(item.translation, "铁会生锈。");
+    }
+}
@@ -0,0 +1,67 @@
+//! Named prompt templates of an LLM service.
+use serde::Deserialize;
+
+/// The template built from `prompt_template`, used when none is picked.
+pub const DEFAULT_TEMPLATE: &str = "dictionary";
+
+/// A prompt with its optional system message and few-shot examples.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PromptTemplate {
+    pub prompt: String,
+    /// Sent as the system message; the service's own is used when missing.
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub examples: Vec<FewShotExample>,
+}
+
+/// A text and the expected answer, shown to the model before the real question.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FewShotExample {
+    pub text: String,
+    pub answer: String,
+}
+
+/// The templates every service has besides [`DEFAULT_TEMPLATE`]:
+/// `etymology`, `synonyms`, `examples` and `teacher`.
+pub fn builtin(name: &str) -> Option<PromptTemplate> {
+    let (prompt, system_prompt) = match name {
+        "etymology" => (
+            concat!(
+                "讲解下面英文单词或短语的词源（不要 Markdown 格式），",
+                "包括词根词缀、来源语言和词义的演变，用{{native_language}}回答：\n",
+                "{{text}}",
+            ),
+            None,
+        ),
+        "synonyms" => (
+            concat!(
+                "列出下面单词或短语的近义词和反义词（不要 Markdown 格式），",
+                "并用{{native_language}}说明它们在含义、语气和用法上的区别：\n",
+                "{{text}}",
+            ),
+            None,
+        ),
+        "examples" => (
+            concat!(
+                "为下面的单词或短语给出 5 个适合{{level}}水平学习者的例句（不要 Markdown 格式），",
+                "每个例句附{{native_language}}翻译：\n",
+                "{{text}}",
+            ),
+            None,
+        ),
+        "teacher" => (
+            concat!(
+                "讲解下面的单词或短语（不要 Markdown 格式）：它的含义、常见搭配和容易用错的地方，",
+                "最后给出两个例句：\n",
+                "{{text}}",
+            ),
+            Some("你是一位耐心的英语老师，学生的水平是{{level}}，母语是{{native_language}}。"),
+        ),
+        _ => return None,
+    };
+    Some(PromptTemplate {
+        prompt: prompt.to_owned(),
+        system_prompt: system_prompt.map(str::to_owned),
+        examples: Vec::new(),
+    })
+}
@@ -1,31 +1,148 @@
+pub mod async_dict;
+pub mod command;
+pub mod context;
+pub mod entry;
+pub mod error;
 pub mod llm;
 pub mod offline;
 pub mod online;
 pub mod stardict;
 
+use crate::cache::CacheIdentity;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(derive_more::Display)]
+pub use async_dict::AsyncDict;
+pub use entry::{Example, Inflection, PartOfSpeech, Phonetics, Phrase, Sense, Source};
+pub use error::DictError;
+
+#[derive(derive_more::Display, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DictType {
     OnlineDict,
     StarDict,
     LLM,
+    Command,
 }
 
-pub trait Dict {
+pub trait Dict: Send + Sync {
     fn name(&self) -> &str;
     fn type_(&self) -> DictType;
     fn supports_fuzzy_search(&self) -> bool;
-    fn look_up(&self, enable_fuzzy: bool, word: &str) -> LookUpResult;
+    fn look_up(&self, enable_fuzzy: bool, word: &str) -> Result<LookUpResult, DictError>;
     fn word_count(&self) -> Option<usize>;
+
+    /// Like [`Dict::look_up`], but passes the definition to `sink` piece by
+    /// piece as it arrives. Dictionaries that cannot stream answer at once
+    /// without calling `sink`.
+    fn look_up_streaming(
+        &self,
+        enable_fuzzy: bool,
+        word: &str,
+        sink: &mut dyn FnMut(&str),
+    ) -> Result<LookUpResult, DictError> {
+        let _ = sink;
+        self.look_up(enable_fuzzy, word)
+    }
+
+    /// Does [`Dict::look_up_query`] make use of the context and template of
+    /// a query?
+    fn understands_queries(&self) -> bool {
+        false
+    }
+
+    /// Look up the word of `query`, e.g. giving the meaning it has in the
+    /// sentence it appeared in first. Dictionaries that cannot make use of
+    /// the query look the word up as usual.
+    fn look_up_query(
+        &self,
+        query: &Query,
+        sink: &mut dyn FnMut(&str),
+    ) -> Result<LookUpResult, DictError> {
+        self.look_up_streaming(false, query.word, sink)
+    }
+
+    /// Identifies responses worth caching. Local dictionaries are not cached.
+    fn cache_identity(&self) -> Option<CacheIdentity> {
+        matches!(self.type_(), DictType::OnlineDict).then(|| CacheIdentity::new(self.name()))
+    }
 }
 
 pub type DifficultyLevel = String;
 
+/// A word to look up, with what dictionaries that understand more may be told.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Query<'a> {
+    pub word: &'a str,
+    /// The sentence the word appeared in.
+    pub context: Option<&'a str>,
+    /// A named prompt template of LLM dictionaries.
+    pub template: Option<&'a str>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(word: &'a str) -> Self {
+        Self {
+            word,
+            ..Default::default()
+        }
+    }
+
+    pub fn in_context(mut self, context: Option<&'a str>) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn with_template(mut self, template: Option<&'a str>) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Is there nothing but the word?
+    pub fn is_plain(&self) -> bool {
+        self.context.is_none() && self.template.is_none()
+    }
+
+    /// Identifies the answer in the look-up cache; the word alone for plain
+    /// queries.
+    pub fn cache_key(&self) -> String {
+        let mut key = self.word.to_owned();
+        if let Some(template) = self.template {
+            key = format!("#{} {}", template, key);
+        }
+        if let Some(context) = self.context {
+            key = format!("{} :: {}", key, context);
+        }
+        key
+    }
+}
+
+/// A dictionary entry.
+///
+/// Backends fill in whatever structure they can extract. `translation` holds
+/// the definition as plain text; when it is empty, the structured fields are
+/// rendered instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LookUpResultItem {
+    /// The headword.
     pub word: String,
+    pub phonetics: Phonetics,
+    pub parts_of_speech: Vec<PartOfSpeech>,
+    /// Longer senses with examples, such as Collins' English definitions.
+    pub detailed_senses: Vec<PartOfSpeech>,
+    pub examples: Vec<Example>,
+    pub phrases: Vec<Phrase>,
+    /// Translations collected from the web.
+    pub web_definitions: Vec<String>,
+    pub synonyms: Vec<String>,
+    pub antonyms: Vec<String>,
+    pub inflections: Vec<Inflection>,
+    pub source: Option<Source>,
+    /// Plain-text definition.
     pub translation: String,
     pub difficulty_levels: Vec<DifficultyLevel>,
+    /// The sense most likely meant in the sentence the word was looked up in.
+    pub context_sense: Option<String>,
 }
 
 impl fmt::Display for LookUpResultItem {
@@ -34,13 +151,11 @@ impl fmt::Display for LookUpResultItem {
         self.difficulty_levels
             .iter()
             .for_each(|x| difficulty_levels_str.push_str(&format!("<{}> ", x)));
-        write!(
-            f,
-            "{}\n{}\n{}",
-            self.word,
-            self.translation.trim(),
-            difficulty_levels_str
-        )
+        writeln!(f, "{}", self.word)?;
+        if let Some(sense) = &self.context_sense {
+            writeln!(f, "In context: {}", sense)?;
+        }
+        write!(f, "{}\n{}", self.plain_text().trim(), difficulty_levels_str)
     }
 }
 
@@ -49,7 +164,7 @@ impl LookUpResultItem {
         LookUpResultItem {
             word: word.into(),
             translation,
-            difficulty_levels: Vec::new(),
+            ..Default::default()
         }
     }
 
@@ -62,12 +177,159 @@ impl LookUpResultItem {
             word,
             translation,
             difficulty_levels,
+            ..Default::default()
         }
     }
+
+    pub fn with_source(mut self, name: impl Into<String>, dict_type: DictType) -> Self {
+        self.source = Some(Source::new(name, dict_type));
+        self
+    }
+
+    /// Does the entry carry anything beyond the plain-text definition?
+    pub fn is_structured(&self) -> bool {
+        !self.phonetics.is_empty()
+            || !self.parts_of_speech.is_empty()
+            || !self.detailed_senses.is_empty()
+            || !self.examples.is_empty()
+            || !self.phrases.is_empty()
+            || !self.web_definitions.is_empty()
+            || !self.synonyms.is_empty()
+            || !self.antonyms.is_empty()
+            || !self.inflections.is_empty()
+    }
+
+    /// The definition as plain text, rendered from the structured fields
+    /// when the dictionary provided no text of its own.
+    pub fn plain_text(&self) -> String {
+        if !self.translation.trim().is_empty() || !self.is_structured() {
+            return self.translation.clone();
+        }
+
+        let mut lines = Vec::new();
+        if !self.phonetics.is_empty() {
+            lines.push(self.phonetics.to_string());
+        }
+        render_parts_of_speech(&self.parts_of_speech, &mut lines);
+        if !self.inflections.is_empty() {
+            let forms: Vec<_> = self
+                .inflections
+                .iter()
+                .map(|x| format!("{} {}", x.form, x.word))
+                .collect();
+            lines.push(format!("Forms: {}", forms.join("; ")));
+        }
+        if !self.synonyms.is_empty() {
+            lines.push(format!("Synonyms: {}", self.synonyms.join(", ")));
+        }
+        if !self.antonyms.is_empty() {
+            lines.push(format!("Antonyms: {}", self.antonyms.join(", ")));
+        }
+        if !self.web_definitions.is_empty() {
+            lines.push(format!("Web: {}", self.web_definitions.join("; ")));
+        }
+        if !self.phrases.is_empty() {
+            lines.push("Phrases:".to_owned());
+            for phrase in &self.phrases {
+                lines.push(format!("  {}  {}", phrase.text, phrase.translation));
+            }
+        }
+        if !self.detailed_senses.is_empty() {
+            lines.push("Detailed senses:".to_owned());
+            render_parts_of_speech(&self.detailed_senses, &mut lines);
+        }
+        if !self.examples.is_empty() {
+            lines.push("Examples:".to_owned());
+            for example in &self.examples {
+                lines.push(format!("  {}", example.text));
+                if let Some(translation) = &example.translation {
+                    lines.push(format!("  {}", translation));
+                }
+            }
+        }
+        lines.join("\n")
+    }
 }
 
+fn render_parts_of_speech(parts_of_speech: &[PartOfSpeech], lines: &mut Vec<String>) {
+    for pos in parts_of_speech {
+        let (head, indent) = if pos.pos.is_empty() {
+            (String::new(), String::new())
+        } else {
+            (
+                format!("{} ", pos.pos),
+                " ".repeat(pos.pos.chars().count() + 1),
+            )
+        };
+        for (i, sense) in pos.senses.iter().enumerate() {
+            let head = if i == 0 { &head } else { &indent };
+            let number = if pos.senses.len() > 1 {
+                format!("{}. ", i + 1)
+            } else {
+                String::new()
+            };
+            lines.push(format!("{}{}{}", head, number, sense.definition));
+            for example in &sense.examples {
+                lines.push(format!("{}   {}", indent, example.text));
+                if let Some(translation) = &example.translation {
+                    lines.push(format!("{}   {}", indent, translation));
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum LookUpResult {
     Exact(LookUpResultItem),
     Fuzzy(Vec<LookUpResultItem>),
-    None,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_preferred() {
+        let mut item = LookUpResultItem::new("rust", "n. 铁锈".to_owned());
+        item.phonetics.general = Some("[rʌst]".to_owned());
+        assert_eq!(item.plain_text(), "n. 铁锈");
+    }
+
+    #[test]
+    fn render_structured_entry() {
+        let item = LookUpResultItem {
+            word: "rust".to_owned(),
+            phonetics: Phonetics {
+                uk: Some("/rʌst/".to_owned()),
+                us: Some("/rʌst/".to_owned()),
+                general: None,
+            },
+            parts_of_speech: vec![
+                PartOfSpeech::new("n.", vec![Sense::new("铁锈"), Sense::new("锈病")]),
+                PartOfSpeech::new("v.", vec![Sense::new("生锈")]),
+            ],
+            inflections: vec![Inflection::new("复数", "rusts")],
+            ..Default::default()
+        };
+        assert_eq!(
+            item.plain_text(),
+            "UK /rʌst/  US /rʌst/\nn. 1. 铁锈\n   2. 锈病\nv. 生锈\nForms: 复数 rusts"
+        );
+    }
+
+    #[test]
+    fn render_extra_sections() {
+        let item = LookUpResultItem {
+            word: "rust".to_owned(),
+            web_definitions: vec!["铁锈".to_owned(), "锈病".to_owned()],
+            phrases: vec![Phrase::new("rust belt", "铁锈地带")],
+            detailed_senses: vec![PartOfSpeech::new("VERB", vec![Sense::new("to corrode")])],
+            ..Default::default()
+        };
+        assert_eq!(
+            item.plain_text(),
+            "Web: 铁锈; 锈病\nPhrases:\n  rust belt  铁锈地带\nDetailed senses:\nVERB to corrode"
+        );
+    }
 }
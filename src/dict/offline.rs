@@ -1,8 +1,17 @@
 use std::path::Path;
 
-use super::{stardict::StarDict, Dict, DictType, LookUpResult, LookUpResultItem};
+use super::{
+    stardict::StarDict, Dict, DictError, DictType, LookUpResult, LookUpResultItem, PartOfSpeech,
+    Phonetics, Sense,
+};
 use anyhow::{Context, Result};
 
+/// Abbreviations that start a part-of-speech line in common StarDict dictionaries.
+const POS_ABBREVIATIONS: [&str; 18] = [
+    "n", "v", "vt", "vi", "a", "adj", "adv", "ad", "prep", "conj", "pron", "int", "interj", "num",
+    "art", "aux", "abbr", "pl",
+];
+
 pub struct OfflineDict {
     name: String,
     stardict: StarDict,
@@ -16,6 +25,47 @@ impl OfflineDict {
         let name = stardict.dict_name().to_owned();
         Ok(Self { name, stardict })
     }
+
+    fn build_item(&self, word: &str, translation: &str) -> LookUpResultItem {
+        let (phonetics, parts_of_speech) = parse_translation(translation);
+        LookUpResultItem {
+            phonetics,
+            parts_of_speech,
+            ..LookUpResultItem::new(word, translation.to_owned())
+        }
+        .with_source(&self.name, DictType::StarDict)
+    }
+}
+
+/// Pick the phonetic and part-of-speech lines out of a plain-text definition.
+///
+/// StarDict definitions have no fixed layout, so anything unrecognised is left
+/// to the plain-text translation.
+fn parse_translation(translation: &str) -> (Phonetics, Vec<PartOfSpeech>) {
+    let mut phonetics = Phonetics::default();
+    let mut parts_of_speech: Vec<PartOfSpeech> = Vec::new();
+    for line in translation.lines().map(str::trim).filter(|x| !x.is_empty()) {
+        let is_phonetic = (line.starts_with('[') && line.ends_with(']'))
+            || (line.len() > 1 && line.starts_with('/') && line.ends_with('/'));
+        if is_phonetic {
+            phonetics.general.get_or_insert_with(|| line.to_owned());
+            continue;
+        }
+        let Some((pos, rest)) = line.split_once('.') else {
+            continue;
+        };
+        if !POS_ABBREVIATIONS.contains(&pos) {
+            continue;
+        }
+        let senses = rest
+            .split(['；', ';'])
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(Sense::new)
+            .collect();
+        parts_of_speech.push(PartOfSpeech::new(format!("{}.", pos), senses));
+    }
+    (phonetics, parts_of_speech)
 }
 
 impl Dict for OfflineDict {
@@ -31,28 +81,24 @@ impl Dict for OfflineDict {
         true
     }
 
-    fn look_up(&self, enable_fuzzy: bool, word: &str) -> LookUpResult {
-        if let Some(result) = self.stardict.exact_look_up(word) {
-            let word = result.word.to_owned();
-            let translation = result.translation.to_owned();
-            LookUpResult::Exact(LookUpResultItem::new(word, translation))
+    fn look_up(&self, enable_fuzzy: bool, word: &str) -> Result<LookUpResult, DictError> {
+        let corrupted = |e: anyhow::Error| DictError::Corrupted(format!("{:#}", e));
+        if let Some(result) = self.stardict.exact_look_up(word).map_err(corrupted)? {
+            Ok(LookUpResult::Exact(
+                self.build_item(result.word, result.translation),
+            ))
         } else if enable_fuzzy {
-            if let Some(results) = self.stardict.fuzzy_look_up(word) {
-                LookUpResult::Fuzzy(
+            match self.stardict.fuzzy_look_up(word).map_err(corrupted)? {
+                Some(results) if !results.is_empty() => Ok(LookUpResult::Fuzzy(
                     results
                         .iter()
-                        .map(|result| {
-                            let word = result.word.to_owned();
-                            let translation = result.translation.to_owned();
-                            LookUpResultItem::new(word, translation)
-                        })
+                        .map(|result| self.build_item(result.word, result.translation))
                         .collect(),
-                )
-            } else {
-                LookUpResult::None
+                )),
+                _ => Err(DictError::NotFound),
             }
         } else {
-            LookUpResult::None
+            Err(DictError::NotFound)
         }
     }
 
@@ -60,3 +106,22 @@ impl Dict for OfflineDict {
         Some(self.stardict.word_count())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_plain_translation() {
+        let (phonetics, parts_of_speech) =
+            parse_translation("<<rust>>\n[rʌst]\nn. 铁锈；锈病\nvi. 生锈\n例：rust never sleeps");
+        assert_eq!(phonetics.general.as_deref(), Some("[rʌst]"));
+        assert_eq!(
+            parts_of_speech,
+            vec![
+                PartOfSpeech::new("n.", vec![Sense::new("铁锈"), Sense::new("锈病")]),
+                PartOfSpeech::new("vi.", vec![Sense::new("生锈")]),
+            ]
+        );
+    }
+}
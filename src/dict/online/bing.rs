@@ -0,0 +1,154 @@
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+
+use super::{fetch_text, normalize_whitespace};
+use crate::dict::{
+    Dict, DictError, DictType, Example, Inflection, LookUpResult, LookUpResultItem, PartOfSpeech,
+    Sense,
+};
+
+/// Scrapes the [Bing Dictionary](https://cn.bing.com/dict) result page.
+pub struct Bing;
+
+fn selector(selector: &str) -> Selector {
+    Selector::parse(selector).expect("selector is valid")
+}
+
+fn text_of(element: ElementRef) -> String {
+    normalize_whitespace(&element.text().collect::<String>())
+}
+
+/// Strip the `美` / `英` label in front of a phonetic.
+fn strip_accent(text: &str) -> String {
+    text.trim_start_matches(['美', '英'])
+        .trim_start_matches(|c: char| c.is_whitespace())
+        .to_owned()
+}
+
+fn parse(word: &str, html: &Html) -> Result<LookUpResult, DictError> {
+    let mut item =
+        LookUpResultItem::new(word, String::new()).with_source("Bing", DictType::OnlineDict);
+
+    if let Some(headword) = html.select(&selector(".qdef #headword")).next() {
+        item.word = text_of(headword);
+    }
+    item.phonetics.us = html
+        .select(&selector(".qdef .hd_prUS"))
+        .next()
+        .map(|x| strip_accent(&text_of(x)));
+    item.phonetics.uk = html
+        .select(&selector(".qdef .hd_pr"))
+        .next()
+        .map(|x| strip_accent(&text_of(x)));
+
+    let pos_selector = selector(".pos");
+    let def_selector = selector(".def");
+    for li in html.select(&selector(".qdef > ul > li")) {
+        let pos = li.select(&pos_selector).next().map(text_of);
+        let definition = li.select(&def_selector).next().map(text_of);
+        if let (Some(pos), Some(definition)) = (pos, definition) {
+            let senses = definition
+                .split('；')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(Sense::new)
+                .collect();
+            item.parts_of_speech.push(PartOfSpeech::new(pos, senses));
+        }
+    }
+
+    // Labels such as `复数：` are followed by links to the inflected forms.
+    let mut label = String::new();
+    for element in html.select(&selector(".qdef .hd_if span, .qdef .hd_if a")) {
+        let text = text_of(element);
+        if element.value().name() == "span" {
+            label = text.trim_end_matches(['：', ':']).to_owned();
+        } else if !text.is_empty() {
+            item.inflections.push(Inflection::new(&label, text));
+        }
+    }
+
+    let en_selector = selector(".sen_en");
+    let cn_selector = selector(".sen_cn");
+    for sentence in html.select(&selector("#sentenceSeg .se_li")) {
+        if let Some(text) = sentence.select(&en_selector).next().map(text_of) {
+            let translation = sentence.select(&cn_selector).next().map(text_of);
+            item.examples.push(Example::new(text, translation));
+        }
+    }
+
+    if item.parts_of_speech.is_empty() {
+        Err(DictError::NotFound)
+    } else {
+        Ok(LookUpResult::Exact(item))
+    }
+}
+
+impl Dict for Bing {
+    fn name(&self) -> &str {
+        "Bing"
+    }
+
+    fn type_(&self) -> DictType {
+        DictType::OnlineDict
+    }
+
+    fn supports_fuzzy_search(&self) -> bool {
+        false
+    }
+
+    fn look_up(&self, _: bool, word: &str) -> Result<LookUpResult, DictError> {
+        let url = Url::parse_with_params("https://cn.bing.com/dict/search", [("q", word)])
+            .expect("base url is valid");
+        parse(word, &Html::parse_document(&fetch_text(url.as_str())?))
+    }
+
+    fn word_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_fixture() {
+        let html = Html::parse_document(include_str!("fixtures/bing.html"));
+        let Ok(LookUpResult::Exact(item)) = parse("rust", &html) else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.word, "rust");
+        assert_eq!(item.phonetics.us.as_deref(), Some("[rʌst]"));
+        assert_eq!(item.phonetics.uk.as_deref(), Some("[rʌst]"));
+        assert_eq!(
+            item.parts_of_speech
+                .iter()
+                .map(|x| x.pos.as_str())
+                .collect::<Vec<_>>(),
+            vec!["n.", "v.", "网络"]
+        );
+        assert_eq!(item.parts_of_speech[0].senses.len(), 3);
+        assert_eq!(
+            item.inflections,
+            vec![
+                Inflection::new("复数", "rusts"),
+                Inflection::new("现在分词", "rusting"),
+                Inflection::new("过去式", "rusted"),
+            ]
+        );
+        assert_eq!(item.examples.len(), 2);
+        assert_eq!(
+            item.examples[0].translation.as_deref(),
+            Some("这辆旧车满是锈迹。")
+        );
+    }
+
+    #[test]
+    fn parse_unknown_word() {
+        let html = Html::parse_document(
+            r#"<html><body><div class="no_results"><h1>没有找到与 rsut 相符的结果</h1></div></body></html>"#,
+        );
+        assert_eq!(parse("rsut", &html).err(), Some(DictError::NotFound));
+    }
+}
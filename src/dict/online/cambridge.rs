@@ -0,0 +1,139 @@
+use scraper::{ElementRef, Html, Selector};
+
+use super::{fetch_text, normalize_whitespace, url_with_segment};
+use crate::dict::{
+    Dict, DictError, DictType, Example, LookUpResult, LookUpResultItem, PartOfSpeech, Sense,
+};
+
+/// Scrapes the [Cambridge Dictionary](https://dictionary.cambridge.org) English pages.
+pub struct Cambridge;
+
+fn selector(selector: &str) -> Selector {
+    Selector::parse(selector).expect("selector is valid")
+}
+
+fn text_of(element: ElementRef) -> String {
+    normalize_whitespace(&element.text().collect::<String>())
+}
+
+fn first_text(element: ElementRef, selector: &Selector) -> Option<String> {
+    element
+        .select(selector)
+        .next()
+        .map(text_of)
+        .filter(|x| !x.is_empty())
+}
+
+fn parse(word: &str, html: &Html) -> Result<LookUpResult, DictError> {
+    let entry_selector = selector(".entry-body__el");
+    let headword_selector = selector(".headword");
+    let pos_selector = selector(".pos-header .pos");
+    let uk_selector = selector(".pos-header .uk .ipa");
+    let us_selector = selector(".pos-header .us .ipa");
+    let def_block_selector = selector(".def-block");
+    let def_selector = selector(".def");
+    let example_selector = selector(".examp .eg");
+
+    let mut item =
+        LookUpResultItem::new(word, String::new()).with_source("Cambridge", DictType::OnlineDict);
+
+    for entry in html.select(&entry_selector) {
+        if let Some(headword) = first_text(entry, &headword_selector) {
+            item.word = headword;
+        }
+        if item.phonetics.uk.is_none() {
+            item.phonetics.uk = first_text(entry, &uk_selector).map(|x| format!("/{}/", x));
+        }
+        if item.phonetics.us.is_none() {
+            item.phonetics.us = first_text(entry, &us_selector).map(|x| format!("/{}/", x));
+        }
+
+        let pos = first_text(entry, &pos_selector).unwrap_or_default();
+        let senses: Vec<_> = entry
+            .select(&def_block_selector)
+            .filter_map(|block| {
+                let definition = first_text(block, &def_selector)?;
+                Some(Sense {
+                    definition: definition.trim_end_matches(':').trim_end().to_owned(),
+                    examples: block
+                        .select(&example_selector)
+                        .map(|x| Example::new(text_of(x), None))
+                        .collect(),
+                })
+            })
+            .collect();
+        if senses.is_empty() {
+            continue;
+        }
+        match item.parts_of_speech.iter_mut().find(|x| x.pos == pos) {
+            Some(existing) => existing.senses.extend(senses),
+            None => item.parts_of_speech.push(PartOfSpeech::new(pos, senses)),
+        }
+    }
+
+    // Unknown words are redirected to a search page without entries.
+    if item.parts_of_speech.is_empty() {
+        Err(DictError::NotFound)
+    } else {
+        Ok(LookUpResult::Exact(item))
+    }
+}
+
+impl Dict for Cambridge {
+    fn name(&self) -> &str {
+        "Cambridge"
+    }
+
+    fn type_(&self) -> DictType {
+        DictType::OnlineDict
+    }
+
+    fn supports_fuzzy_search(&self) -> bool {
+        false
+    }
+
+    fn look_up(&self, _: bool, word: &str) -> Result<LookUpResult, DictError> {
+        let url = url_with_segment("https://dictionary.cambridge.org/dictionary/english/", word);
+        parse(word, &Html::parse_document(&fetch_text(&url)?))
+    }
+
+    fn word_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_fixture() {
+        let html = Html::parse_document(include_str!("fixtures/cambridge.html"));
+        let Ok(LookUpResult::Exact(item)) = parse("rust", &html) else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.phonetics.uk.as_deref(), Some("/rʌst/"));
+        assert_eq!(item.phonetics.us.as_deref(), Some("/rʌst/"));
+        assert_eq!(item.parts_of_speech.len(), 2);
+        let noun = &item.parts_of_speech[0];
+        assert_eq!(noun.pos, "noun");
+        assert_eq!(
+            noun.senses[0].definition,
+            "a reddish-brown substance that forms on iron or steel when it is in contact with water or wet air"
+        );
+        assert_eq!(
+            noun.senses[0].examples[0].text,
+            "The old car was covered in rust."
+        );
+        assert_eq!(item.parts_of_speech[1].pos, "verb");
+        assert_eq!(item.parts_of_speech[1].senses[0].examples.len(), 2);
+    }
+
+    #[test]
+    fn parse_search_page() {
+        let html = Html::parse_document(
+            r#"<html><body><div class="hfl-s lt2b lmt-10">Search suggestions for rsut</div></body></html>"#,
+        );
+        assert_eq!(parse("rsut", &html).err(), Some(DictError::NotFound));
+    }
+}
@@ -0,0 +1,208 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use super::url_with_segment;
+use crate::dict::{
+    error::check_status, Dict, DictError, DictType, Example, LookUpResult, LookUpResultItem,
+    PartOfSpeech, Sense,
+};
+use crate::net;
+
+/// The [Free Dictionary API](https://dictionaryapi.dev).
+pub struct FreeDictionary;
+
+#[derive(Deserialize)]
+struct ApiEntry {
+    word: String,
+    #[serde(default)]
+    phonetic: Option<String>,
+    #[serde(default)]
+    phonetics: Vec<ApiPhonetic>,
+    #[serde(default)]
+    meanings: Vec<ApiMeaning>,
+}
+
+#[derive(Deserialize)]
+struct ApiPhonetic {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    audio: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiMeaning {
+    part_of_speech: String,
+    #[serde(default)]
+    definitions: Vec<ApiDefinition>,
+    #[serde(default)]
+    synonyms: Vec<String>,
+    #[serde(default)]
+    antonyms: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiDefinition {
+    definition: String,
+    #[serde(default)]
+    example: Option<String>,
+    #[serde(default)]
+    synonyms: Vec<String>,
+    #[serde(default)]
+    antonyms: Vec<String>,
+}
+
+fn parse(body: &str) -> Result<LookUpResult, DictError> {
+    let entries: Vec<ApiEntry> = serde_json::from_str(body)?;
+    let Some(first) = entries.first() else {
+        return Err(DictError::NotFound);
+    };
+
+    let mut item = LookUpResultItem::new(first.word.clone(), String::new())
+        .with_source("Free Dictionary", DictType::OnlineDict);
+
+    for entry in &entries {
+        for phonetic in &entry.phonetics {
+            let Some(text) = phonetic.text.clone().filter(|x| !x.is_empty()) else {
+                continue;
+            };
+            let audio = phonetic.audio.as_deref().unwrap_or_default();
+            let slot = if audio.ends_with("-uk.mp3") {
+                &mut item.phonetics.uk
+            } else if audio.ends_with("-us.mp3") {
+                &mut item.phonetics.us
+            } else {
+                &mut item.phonetics.general
+            };
+            slot.get_or_insert(text);
+        }
+        if item.phonetics.is_empty() {
+            item.phonetics.general = entry.phonetic.clone();
+        }
+
+        for meaning in &entry.meanings {
+            let senses = meaning
+                .definitions
+                .iter()
+                .map(|definition| Sense {
+                    definition: definition.definition.clone(),
+                    examples: definition
+                        .example
+                        .iter()
+                        .map(|example| Example::new(example, None))
+                        .collect(),
+                })
+                .collect();
+            match item
+                .parts_of_speech
+                .iter_mut()
+                .find(|pos| pos.pos == meaning.part_of_speech)
+            {
+                Some(pos) => pos.senses.extend(senses),
+                None => item
+                    .parts_of_speech
+                    .push(PartOfSpeech::new(&meaning.part_of_speech, senses)),
+            }
+
+            let synonyms = meaning
+                .synonyms
+                .iter()
+                .chain(meaning.definitions.iter().flat_map(|x| &x.synonyms));
+            let antonyms = meaning
+                .antonyms
+                .iter()
+                .chain(meaning.definitions.iter().flat_map(|x| &x.antonyms));
+            for (words, list) in [
+                (synonyms.collect::<Vec<_>>(), &mut item.synonyms),
+                (antonyms.collect::<Vec<_>>(), &mut item.antonyms),
+            ] {
+                for word in words {
+                    if !list.contains(word) {
+                        list.push(word.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    if item.parts_of_speech.is_empty() {
+        Err(DictError::NotFound)
+    } else {
+        Ok(LookUpResult::Exact(item))
+    }
+}
+
+/// The API answers unknown words with 404 and a message such as
+/// `{"title": "No Definitions Found", ...}`; any other 404 is an error.
+fn parse_not_found(body: &str) -> DictError {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(json) if json.get("title").is_some() => DictError::NotFound,
+        _ => DictError::from_status(StatusCode::NOT_FOUND, None, body),
+    }
+}
+
+impl Dict for FreeDictionary {
+    fn name(&self) -> &str {
+        "Free Dictionary"
+    }
+
+    fn type_(&self) -> DictType {
+        DictType::OnlineDict
+    }
+
+    fn supports_fuzzy_search(&self) -> bool {
+        false
+    }
+
+    fn look_up(&self, _: bool, word: &str) -> Result<LookUpResult, DictError> {
+        let url = url_with_segment("https://api.dictionaryapi.dev/api/v2/entries/en/", word);
+        let response = net::client()?.get(&url).send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(parse_not_found(&response.text()?));
+        }
+        parse(&check_status(response)?.text()?)
+    }
+
+    fn word_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_fixture() {
+        let Ok(LookUpResult::Exact(item)) = parse(include_str!("fixtures/free_dictionary.json"))
+        else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.word, "rust");
+        assert_eq!(item.phonetics.uk.as_deref(), Some("/ɹʌst/"));
+        assert_eq!(item.phonetics.us.as_deref(), Some("/ɹʌst/"));
+        assert_eq!(item.parts_of_speech.len(), 2);
+        assert_eq!(item.parts_of_speech[0].pos, "noun");
+        assert_eq!(item.parts_of_speech[0].senses.len(), 3);
+        assert_eq!(
+            item.parts_of_speech[0].senses[0].examples[0].text,
+            "The old bicycle was covered in rust."
+        );
+        assert_eq!(item.parts_of_speech[1].pos, "verb");
+        assert_eq!(item.synonyms, vec!["corrosion", "oxidize"]);
+        assert_eq!(item.antonyms, vec!["polish"]);
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(parse("[]").err(), Some(DictError::NotFound));
+        assert!(matches!(parse("<html>"), Err(DictError::Parse(_))));
+        let body = r#"{"title": "No Definitions Found", "message": "Sorry pal"}"#;
+        assert_eq!(parse_not_found(body), DictError::NotFound);
+        assert!(matches!(
+            parse_not_found("404 page not found"),
+            DictError::Other(m) if m.ends_with("page not found")
+        ));
+    }
+}
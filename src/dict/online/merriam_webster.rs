@@ -0,0 +1,200 @@
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::fetch_text;
+use crate::dict::{
+    Dict, DictError, DictType, Inflection, LookUpResult, LookUpResultItem, PartOfSpeech, Sense,
+};
+
+/// The [Merriam-Webster Collegiate Dictionary API](https://dictionaryapi.com), which needs an API key.
+pub struct MerriamWebster {
+    api_key: String,
+}
+
+impl MerriamWebster {
+    pub fn new(api_key: String) -> Self {
+        Self { api_key }
+    }
+}
+
+#[derive(Deserialize)]
+struct MwEntry {
+    meta: MwMeta,
+    hwi: MwHeadword,
+    #[serde(default)]
+    fl: Option<String>,
+    #[serde(default)]
+    shortdef: Vec<String>,
+    #[serde(default)]
+    ins: Vec<MwInflection>,
+}
+
+#[derive(Deserialize)]
+struct MwMeta {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct MwHeadword {
+    hw: String,
+    #[serde(default)]
+    prs: Vec<MwPronunciation>,
+}
+
+#[derive(Deserialize)]
+struct MwPronunciation {
+    #[serde(default)]
+    mw: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MwInflection {
+    #[serde(default)]
+    il: Option<String>,
+    #[serde(rename = "if", default)]
+    form: Option<String>,
+}
+
+/// Headwords mark syllable breaks with `*`.
+fn strip_syllables(word: &str) -> String {
+    word.replace('*', "")
+}
+
+fn parse(word: &str, body: &str) -> Result<LookUpResult, DictError> {
+    if body.trim_start().starts_with("Invalid API key") {
+        return Err(DictError::Auth(body.trim().to_owned()));
+    }
+    let value: serde_json::Value = serde_json::from_str(body)?;
+    // Unknown words come back as a list of spelling suggestions.
+    if value
+        .as_array()
+        .is_none_or(|entries| entries.iter().all(|x| x.is_string()))
+    {
+        return Err(DictError::NotFound);
+    }
+    let entries: Vec<MwEntry> = serde_json::from_value(value)?;
+
+    // Homographs have ids like `rust:1` and `rust:2`; the rest are run-on entries.
+    let is_headword = |entry: &&MwEntry| {
+        entry
+            .meta
+            .id
+            .split(':')
+            .next()
+            .is_some_and(|id| id.eq_ignore_ascii_case(word))
+    };
+    let mut matched: Vec<_> = entries.iter().filter(is_headword).collect();
+    if matched.is_empty() {
+        matched = entries.iter().collect();
+    }
+
+    let mut item = LookUpResultItem::new(strip_syllables(&matched[0].hwi.hw), String::new())
+        .with_source("Merriam-Webster", DictType::OnlineDict);
+    for entry in matched {
+        if item.phonetics.us.is_none() {
+            item.phonetics.us = entry
+                .hwi
+                .prs
+                .iter()
+                .find_map(|x| x.mw.as_ref())
+                .map(|x| format!("\\{}\\", x));
+        }
+        let senses: Vec<_> = entry.shortdef.iter().map(Sense::new).collect();
+        if !senses.is_empty() {
+            item.parts_of_speech.push(PartOfSpeech::new(
+                entry.fl.clone().unwrap_or_default(),
+                senses,
+            ));
+        }
+        for inflection in &entry.ins {
+            if let Some(form) = &inflection.form {
+                let label = inflection.il.clone().unwrap_or_default();
+                item.inflections
+                    .push(Inflection::new(label, strip_syllables(form)));
+            }
+        }
+    }
+
+    if item.parts_of_speech.is_empty() {
+        Err(DictError::NotFound)
+    } else {
+        Ok(LookUpResult::Exact(item))
+    }
+}
+
+impl Dict for MerriamWebster {
+    fn name(&self) -> &str {
+        "Merriam-Webster"
+    }
+
+    fn type_(&self) -> DictType {
+        DictType::OnlineDict
+    }
+
+    fn supports_fuzzy_search(&self) -> bool {
+        false
+    }
+
+    fn look_up(&self, _: bool, word: &str) -> Result<LookUpResult, DictError> {
+        let mut url =
+            Url::parse("https://www.dictionaryapi.com/api/v3/references/collegiate/json/")
+                .expect("base url is valid");
+        url.path_segments_mut()
+            .expect("base url has a path")
+            .pop_if_empty()
+            .push(word);
+        url.query_pairs_mut().append_pair("key", &self.api_key);
+        parse(word, &fetch_text(url.as_str())?)
+    }
+
+    fn word_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_fixture() {
+        let Ok(LookUpResult::Exact(item)) =
+            parse("rust", include_str!("fixtures/merriam_webster.json"))
+        else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.word, "rust");
+        assert_eq!(item.phonetics.us.as_deref(), Some("\\ˈrəst\\"));
+        assert_eq!(
+            item.parts_of_speech
+                .iter()
+                .map(|x| x.pos.as_str())
+                .collect::<Vec<_>>(),
+            vec!["noun", "verb"]
+        );
+        assert_eq!(item.parts_of_speech[0].senses.len(), 3);
+        assert_eq!(
+            item.inflections,
+            vec![
+                Inflection::new("", "rusted"),
+                Inflection::new("", "rusting"),
+                Inflection::new("", "rusts"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_suggestions_and_bad_key() {
+        assert_eq!(
+            parse("rsut", r#"["rust", "rusty", "roust"]"#).err(),
+            Some(DictError::NotFound)
+        );
+        assert!(matches!(
+            parse(
+                "rust",
+                "Invalid API key. Not subscribed for this reference."
+            ),
+            Err(DictError::Auth(_))
+        ));
+    }
+}
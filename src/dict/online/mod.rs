@@ -0,0 +1,101 @@
+//! Online dictionaries.
+//!
+//! Each provider scrapes a web page or calls an API, and parses the response
+//! with a pure function so it can be tested against saved fixtures.
+mod bing;
+mod cambridge;
+mod free_dictionary;
+mod merriam_webster;
+mod urban;
+mod youdao;
+
+pub use bing::Bing;
+pub use cambridge::Cambridge;
+pub use free_dictionary::FreeDictionary;
+pub use merriam_webster::MerriamWebster;
+pub use urban::Urban;
+pub use youdao::{fetch_pronunciation, Youdao};
+
+use reqwest::Url;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use super::{error::check_status, Dict, DictError};
+use crate::net;
+
+/// Fetch a page or API response as text.
+fn fetch_text(url: &str) -> Result<String, DictError> {
+    let response = check_status(net::client()?.get(url).send()?)?;
+    Ok(response.text()?)
+}
+
+/// Append `word` to `base` as a single, percent-encoded path segment.
+fn url_with_segment(base: &str, word: &str) -> String {
+    let mut url = Url::parse(base).expect("base url is valid");
+    url.path_segments_mut()
+        .expect("base url has a path")
+        .pop_if_empty()
+        .push(word);
+    url.into()
+}
+
+/// Collapse runs of whitespace, as found in scraped text.
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// The online dictionary services that can be configured.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "provider", rename_all = "kebab-case")]
+pub enum OnlineProvider {
+    Youdao,
+    Cambridge,
+    MerriamWebster {
+        api_key: String,
+    },
+    FreeDictionary,
+    Bing,
+    #[serde(alias = "urban-dictionary")]
+    Urban,
+}
+
+/// One `[[online]]` entry of `config.toml`. Entries are tried in the order they are listed.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OnlineDictConfig {
+    #[serde(flatten)]
+    pub provider: OnlineProvider,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl OnlineProvider {
+    pub fn build(&self) -> Arc<dyn Dict> {
+        match self {
+            OnlineProvider::Youdao => Arc::new(Youdao),
+            OnlineProvider::Cambridge => Arc::new(Cambridge),
+            OnlineProvider::MerriamWebster { api_key } => {
+                Arc::new(MerriamWebster::new(api_key.clone()))
+            }
+            OnlineProvider::FreeDictionary => Arc::new(FreeDictionary),
+            OnlineProvider::Bing => Arc::new(Bing),
+            OnlineProvider::Urban => Arc::new(Urban),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_path_segment() {
+        assert_eq!(
+            url_with_segment("https://example.com/define/", "C++ & co?"),
+            "https://example.com/define/C++%20&%20co%3F"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::fetch_text;
+use crate::dict::{
+    Dict, DictError, DictType, Example, LookUpResult, LookUpResultItem, PartOfSpeech, Sense,
+};
+
+/// How many of the most upvoted definitions to keep.
+const MAX_DEFINITIONS: usize = 5;
+
+/// [Urban Dictionary](https://www.urbandictionary.com), for slang.
+pub struct Urban;
+
+#[derive(Deserialize)]
+struct UrbanResponse {
+    #[serde(default)]
+    list: Vec<UrbanDefinition>,
+}
+
+#[derive(Deserialize)]
+struct UrbanDefinition {
+    word: String,
+    definition: String,
+    #[serde(default)]
+    example: String,
+    #[serde(default)]
+    thumbs_up: i64,
+}
+
+/// Definitions link other terms as `[term]`.
+fn strip_links(text: &str) -> String {
+    text.replace(['[', ']'], "").replace("\r\n", "\n")
+}
+
+fn parse(body: &str) -> Result<LookUpResult, DictError> {
+    let mut response: UrbanResponse = serde_json::from_str(body)?;
+    response
+        .list
+        .sort_by_key(|x| std::cmp::Reverse(x.thumbs_up));
+    let Some(first) = response.list.first() else {
+        return Err(DictError::NotFound);
+    };
+
+    let senses = response
+        .list
+        .iter()
+        .take(MAX_DEFINITIONS)
+        .map(|x| Sense {
+            definition: strip_links(x.definition.trim()),
+            examples: Some(x.example.trim())
+                .filter(|example| !example.is_empty())
+                .map(|example| Example::new(strip_links(example), None))
+                .into_iter()
+                .collect(),
+        })
+        .collect();
+
+    let mut item = LookUpResultItem::new(first.word.clone(), String::new())
+        .with_source("Urban Dictionary", DictType::OnlineDict);
+    item.parts_of_speech.push(PartOfSpeech::new("", senses));
+    Ok(LookUpResult::Exact(item))
+}
+
+impl Dict for Urban {
+    fn name(&self) -> &str {
+        "Urban Dictionary"
+    }
+
+    fn type_(&self) -> DictType {
+        DictType::OnlineDict
+    }
+
+    fn supports_fuzzy_search(&self) -> bool {
+        false
+    }
+
+    fn look_up(&self, _: bool, word: &str) -> Result<LookUpResult, DictError> {
+        let url = Url::parse_with_params(
+            "https://api.urbandictionary.com/v0/define",
+            [("term", word)],
+        )
+        .expect("base url is valid");
+        parse(&fetch_text(url.as_str())?)
+    }
+
+    fn word_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_fixture() {
+        let Ok(LookUpResult::Exact(item)) = parse(include_str!("fixtures/urban.json")) else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.word, "yeet");
+        let senses = &item.parts_of_speech[0].senses;
+        assert_eq!(senses.len(), 2);
+        assert_eq!(
+            senses[0].definition,
+            "To throw something with force and without regard."
+        );
+        assert_eq!(
+            senses[0].examples[0].text,
+            "He yeeted the empty can into the bin."
+        );
+        assert!(senses[1].examples.is_empty());
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(parse(r#"{"list": []}"#).err(), Some(DictError::NotFound));
+    }
+}
@@ -0,0 +1,482 @@
+use anyhow::{anyhow, Result};
+use itertools::{
+    EitherOrBoth::{Both, Left, Right},
+    Itertools,
+};
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use serde::Deserialize;
+
+use super::{fetch_text, normalize_whitespace};
+use crate::dict::error::check_status;
+use crate::dict::{
+    Dict, DictError, DictType, Example, Inflection, LookUpResult, LookUpResultItem, PartOfSpeech,
+    Phonetics, Phrase, Sense,
+};
+use crate::net;
+
+/// Scrapes the [Youdao](https://www.youdao.com) result page for words, and
+/// uses Youdao's translation service for sentences.
+#[derive(Default)]
+pub struct Youdao;
+
+/// Fetch Youdao's recording of `word`, an MP3.
+pub fn fetch_pronunciation(word: &str) -> Result<Vec<u8>, DictError> {
+    let url: String = Url::parse_with_params(
+        "https://dict.youdao.com/dictvoice",
+        [("audio", word), ("type", "1")],
+    )
+    .expect("base url is valid")
+    .into();
+    net::retry_policy().run(|| {
+        let response = check_status(net::client()?.get(&url).send()?)?;
+        Ok(response.bytes()?.to_vec())
+    })
+}
+
+fn look_up(word: &str) -> Result<LookUpResult, DictError> {
+    let language = Language::detect(word);
+    if is_sentence(word, language) {
+        return parse_sentence_translation(word, &fetch_text(&build_sentence_url(word))?);
+    }
+    let html = Html::parse_document(&fetch_text(&build_result_url(word))?);
+    parse(word, language, &html)
+}
+
+fn parse(word: &str, language: Language, html: &Html) -> Result<LookUpResult, DictError> {
+    let mut item =
+        LookUpResultItem::new(word, String::new()).with_source("Youdao", DictType::OnlineDict);
+    if language == Language::English {
+        let (phonetics, parts_of_speech) = parse_english_to_chinese(html).map_err(parse_error)?;
+        item.phonetics = phonetics;
+        item.parts_of_speech = parts_of_speech;
+        item.difficulty_levels = extract_difficulty_levels(html).map_err(parse_error)?;
+        item.inflections = parse_inflections(html);
+        item.web_definitions = parse_web_definitions(html);
+        item.phrases = parse_phrases(html);
+        item.detailed_senses = parse_collins(html);
+        item.examples = parse_examples(html);
+    } else {
+        item.translation = parse_chinese_to_english(html)
+            .map_err(parse_error)?
+            .trim()
+            .to_string();
+    }
+    // find nothing about the word
+    if item.translation.is_empty() && item.parts_of_speech.is_empty() {
+        Err(DictError::NotFound)
+    } else {
+        Ok(LookUpResult::Exact(item))
+    }
+}
+
+impl Dict for Youdao {
+    fn name(&self) -> &str {
+        "Youdao"
+    }
+
+    fn type_(&self) -> DictType {
+        DictType::OnlineDict
+    }
+
+    fn supports_fuzzy_search(&self) -> bool {
+        false
+    }
+
+    fn look_up(&self, _: bool, word: &str) -> Result<LookUpResult, DictError> {
+        look_up(word)
+    }
+
+    fn word_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+fn parse_error(e: anyhow::Error) -> DictError {
+    DictError::Parse(e.to_string())
+}
+
+/// Build the url of the result page for a word.
+fn build_result_url(word: &str) -> String {
+    Url::parse_with_params(
+        "https://www.youdao.com/result",
+        [("word", word), ("lang", "en")],
+    )
+    .expect("base url is valid")
+    .into()
+}
+
+/// Build the url of the translation service for a sentence.
+fn build_sentence_url(text: &str) -> String {
+    Url::parse_with_params(
+        "https://fanyi.youdao.com/translate",
+        [("doctype", "json"), ("type", "AUTO"), ("i", text)],
+    )
+    .expect("base url is valid")
+    .into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    English,
+    Chinese,
+}
+
+impl Language {
+    /// Any CJK character makes the input Chinese; everything else, including
+    /// words like "C++" or "state-of-the-art", is looked up as English.
+    fn detect(text: &str) -> Self {
+        if text.chars().any(is_cjk) {
+            Language::Chinese
+        } else {
+            Language::English
+        }
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{4e00}'..='\u{9fff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{20000}'..='\u{2ffff}')
+}
+
+/// Is the input a sentence or paragraph rather than a word or short phrase?
+///
+/// Short phrases like "give up" still have entries on the result page.
+fn is_sentence(text: &str, language: Language) -> bool {
+    let text = text.trim();
+    match language {
+        Language::English => {
+            let words = text.split_whitespace().count();
+            words > 3 || (words > 1 && text.ends_with(['.', '!', '?']))
+        }
+        Language::Chinese => {
+            text.chars().count() > 8 || text.contains(['，', '。', '！', '？', '；'])
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranslateResponse {
+    error_code: i64,
+    #[serde(default)]
+    translate_result: Vec<Vec<TranslatedSegment>>,
+}
+
+#[derive(Deserialize)]
+struct TranslatedSegment {
+    tgt: String,
+}
+
+/// Parse the response of the translation service.
+fn parse_sentence_translation(text: &str, json: &str) -> Result<LookUpResult, DictError> {
+    let response: TranslateResponse = serde_json::from_str(json)?;
+    if response.error_code != 0 {
+        return Err(DictError::Other(format!(
+            "translation failed with error code {}",
+            response.error_code
+        )));
+    }
+    // Each inner list is a paragraph made of translated sentences.
+    let translation = response
+        .translate_result
+        .iter()
+        .map(|paragraph| paragraph.iter().map(|x| x.tgt.as_str()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if translation.trim().is_empty() {
+        return Err(DictError::NotFound);
+    }
+    Ok(LookUpResult::Exact(
+        LookUpResultItem::new(text, translation).with_source("Youdao", DictType::OnlineDict),
+    ))
+}
+
+/// Lookup words by Chinese meaning.
+fn parse_chinese_to_english(html: &Html) -> Result<String> {
+    let mut res = String::new();
+    let trans = Selector::parse("ul.basic")
+        .map_err(|_| anyhow!("Failed to select the fields of ul.basic in the HTML body"))?;
+    html.select(&trans).for_each(|x| {
+        x.text().collect::<Vec<_>>().iter().for_each(|x| {
+            res.push_str(x);
+            res.push('\n');
+        });
+    });
+    Ok(res)
+}
+
+/// Lookup words by English word.
+fn parse_english_to_chinese(html: &Html) -> Result<(Phonetics, Vec<PartOfSpeech>)> {
+    let mut phonetics = Phonetics::default();
+    let phonetic = Selector::parse(".per-phone")
+        .map_err(|_| anyhow!("Failed select the fields of .per-phone in the HTML body"))?;
+    html.select(&phonetic).for_each(|x| {
+        let text: Vec<_> = x.text().map(str::trim).filter(|x| !x.is_empty()).collect();
+        let (accent, value) = match text.as_slice() {
+            [] => return,
+            [value] => ("", value.to_string()),
+            [accent, rest @ ..] => (*accent, rest.join(" ")),
+        };
+        match accent {
+            "英" => phonetics.uk = Some(value),
+            "美" => phonetics.us = Some(value),
+            _ => phonetics.general = Some(value),
+        }
+    });
+    let mut pos_text: Vec<&str> = Vec::new();
+    let pos = Selector::parse(".basic .pos")
+        .map_err(|_| anyhow!("Failed select the fields of .pos in the HTML body"))?;
+    html.select(&pos).for_each(|x| {
+        x.text().collect::<Vec<_>>().iter().for_each(|x| {
+            pos_text.push(*x);
+        });
+    });
+    let mut trans_text: Vec<&str> = Vec::new();
+    let trans = Selector::parse(".basic .trans")
+        .map_err(|_| anyhow!("Failed to select the fields of .trans in the HTML body"))?;
+    html.select(&trans).for_each(|x| {
+        x.text().collect::<Vec<_>>().iter().for_each(|x| {
+            trans_text.push(*x);
+        });
+    });
+    let parts_of_speech = pos_text
+        .iter()
+        .zip_longest(trans_text.iter())
+        .map(|x| match x {
+            Both(a, b) => (a, b),
+            Left(a) => (a, &""),
+            Right(b) => (&"", b),
+        })
+        .map(|(pos, trans)| {
+            let senses = trans
+                .split('；')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(Sense::new)
+                .collect();
+            PartOfSpeech::new(pos.trim(), senses)
+        })
+        .collect();
+    Ok((phonetics, parts_of_speech))
+}
+
+/// Get the difficulty level of the word from html.
+fn extract_difficulty_levels(html: &Html) -> Result<Vec<String>> {
+    let types = Selector::parse(".exam_type-value")
+        .map_err(|_| anyhow!("Failed to select the fields of .exam_type-value in the HTML body"))?;
+    let mut res: Vec<String> = Vec::new();
+    html.select(&types).for_each(|x| {
+        x.text()
+            .collect::<Vec<_>>()
+            .iter()
+            .for_each(|x| res.push(x.to_string()))
+    });
+    Ok(res)
+}
+
+fn selector(selector: &str) -> Selector {
+    Selector::parse(selector).expect("selector is valid")
+}
+
+fn text_of(element: ElementRef) -> String {
+    normalize_whitespace(&element.text().collect::<String>())
+}
+
+fn first_text(element: ElementRef, selector: &Selector) -> Option<String> {
+    element
+        .select(selector)
+        .next()
+        .map(text_of)
+        .filter(|x| !x.is_empty())
+}
+
+/// Word forms such as the plural or past tense.
+fn parse_inflections(html: &Html) -> Vec<Inflection> {
+    let cell_selector = selector(".word-wfs-cell-less");
+    let name_selector = selector(".wfs-name");
+    let form_selector = selector(".transformation");
+    html.select(&cell_selector)
+        .filter_map(|cell| {
+            Some(Inflection::new(
+                first_text(cell, &name_selector)?,
+                first_text(cell, &form_selector)?,
+            ))
+        })
+        .collect()
+}
+
+fn parse_web_definitions(html: &Html) -> Vec<String> {
+    html.select(&selector(".web_trans .trans-list .point"))
+        .map(text_of)
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+fn parse_phrases(html: &Html) -> Vec<Phrase> {
+    let item_selector = selector(".phrs .trans-list li");
+    let phrase_selector = selector(".point");
+    let translation_selector = selector(".phr_trans");
+    html.select(&item_selector)
+        .filter_map(|x| {
+            Some(Phrase::new(
+                first_text(x, &phrase_selector)?,
+                first_text(x, &translation_selector).unwrap_or_default(),
+            ))
+        })
+        .collect()
+}
+
+fn parse_example(element: ElementRef) -> Option<Example> {
+    Some(Example::new(
+        first_text(element, &selector(".sen-eng"))?,
+        first_text(element, &selector(".sen-ch")),
+    ))
+}
+
+/// Bilingual example sentences.
+fn parse_examples(html: &Html) -> Vec<Example> {
+    html.select(&selector(".blng_sents_part .trans-list li"))
+        .filter_map(parse_example)
+        .collect()
+}
+
+/// Collins senses, grouped by consecutive parts of speech.
+fn parse_collins(html: &Html) -> Vec<PartOfSpeech> {
+    let item_selector = selector(".collins .trans-list > li");
+    let pos_selector = selector(".collins-intro .pos");
+    let definition_selector = selector(".trans");
+    let example_selector = selector(".exam-sen");
+    let mut parts_of_speech: Vec<PartOfSpeech> = Vec::new();
+    for item in html.select(&item_selector) {
+        let Some(definition) = first_text(item, &definition_selector) else {
+            continue;
+        };
+        let sense = Sense {
+            definition,
+            examples: item
+                .select(&example_selector)
+                .filter_map(parse_example)
+                .collect(),
+        };
+        let pos = first_text(item, &pos_selector).unwrap_or_default();
+        match parts_of_speech.last_mut() {
+            Some(last) if last.pos == pos => last.senses.push(sense),
+            _ => parts_of_speech.push(PartOfSpeech::new(pos, vec![sense])),
+        }
+    }
+    parts_of_speech
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixture(name: &str) -> Html {
+        let path = format!(
+            "{}/src/dict/online/fixtures/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        Html::parse_document(&std::fs::read_to_string(path).unwrap())
+    }
+
+    #[test]
+    fn parse_english_entry() {
+        let Ok(LookUpResult::Exact(item)) =
+            parse("rust", Language::English, &fixture("youdao.html"))
+        else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.phonetics.uk.as_deref(), Some("/ rʌst /"));
+        assert_eq!(item.parts_of_speech.len(), 3);
+        assert_eq!(item.parts_of_speech[0].pos, "n.");
+        assert_eq!(item.parts_of_speech[0].senses[1].definition, "（植）锈病");
+        assert_eq!(item.difficulty_levels, ["高中", "CET4", "CET6"]);
+        assert_eq!(item.inflections[2], Inflection::new("过去式", "rusted"));
+        assert_eq!(item.web_definitions, ["铁锈", "锈病", "生锈"]);
+        assert_eq!(item.phrases[1], Phrase::new("rust belt", "铁锈地带"));
+        assert_eq!(item.detailed_senses.len(), 2);
+        assert_eq!(item.detailed_senses[1].pos, "VERB");
+        assert_eq!(
+            item.detailed_senses[0].senses[0].examples[0],
+            Example::new(
+                "The old car was covered in rust.",
+                Some("那辆旧车锈迹斑斑。".to_owned())
+            )
+        );
+        assert_eq!(item.examples.len(), 2);
+        assert_eq!(item.examples[1].text, "The hinges had rusted.");
+    }
+
+    #[test]
+    fn parse_chinese_entry() {
+        let Ok(LookUpResult::Exact(item)) =
+            parse("铁锈", Language::Chinese, &fixture("youdao_zh.html"))
+        else {
+            panic!("expected an exact match");
+        };
+        assert!(item.translation.starts_with("rust\nn. 铁锈"));
+    }
+
+    #[test]
+    fn parse_missing_entry() {
+        let html = Html::parse_document("<html><body></body></html>");
+        assert_eq!(
+            parse("qwfpgj", Language::English, &html).err(),
+            Some(DictError::NotFound)
+        );
+    }
+
+    #[test]
+    fn encode_urls() {
+        assert_eq!(
+            build_result_url("C++"),
+            "https://www.youdao.com/result?word=C%2B%2B&lang=en"
+        );
+        assert_eq!(
+            build_result_url("R&D #1?"),
+            "https://www.youdao.com/result?word=R%26D+%231%3F&lang=en"
+        );
+    }
+
+    #[test]
+    fn detect_input() {
+        for word in ["state-of-the-art", "C++", "naïve", "give up"] {
+            assert_eq!(Language::detect(word), Language::English);
+            assert!(!is_sentence(word, Language::English), "{}", word);
+        }
+        assert_eq!(Language::detect("铁锈"), Language::Chinese);
+        assert_eq!(Language::detect("Rust 语言"), Language::Chinese);
+        assert!(is_sentence("The hinges had rusted.", Language::English));
+        assert!(is_sentence(
+            "Rust is a systems programming language",
+            Language::English
+        ));
+        assert!(is_sentence("铁会生锈。", Language::Chinese));
+        assert!(!is_sentence("铁锈", Language::Chinese));
+    }
+
+    #[test]
+    fn parse_sentence() {
+        let json = std::fs::read_to_string(format!(
+            "{}/src/dict/online/fixtures/youdao_translate.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let Ok(LookUpResult::Exact(item)) = parse_sentence_translation("Iron rusts.", &json) else {
+            panic!("expected a translation");
+        };
+        assert_eq!(
+            item.translation,
+            "铁会生锈。锈已经侵蚀了金属。\n铰链生锈了。"
+        );
+        assert!(matches!(
+            parse_sentence_translation("x", r#"{"errorCode": 40}"#),
+            Err(DictError::Other(_))
+        ));
+    }
+}
@@ -70,7 +70,7 @@ impl<'a> StarDict {
 
     /// Look up a word with fuzzy searching disabled.
     /// Performs an exact match lookup for the given word
-    pub fn exact_look_up(&self, word: &str) -> Option<DictEntry<'_>> {
+    pub fn exact_look_up(&self, word: &str) -> Result<Option<DictEntry<'_>>> {
         if let Ok(pos) = self.indices.items.binary_search_by(|probe| {
             probe
                 .0
@@ -79,10 +79,10 @@ impl<'a> StarDict {
                 .then(probe.0.as_str().cmp(word))
         }) {
             let (word, offset, size) = &self.indices.items[pos];
-            let translation = self.contents.get(*offset, *size);
-            Some(DictEntry { word, translation })
+            let translation = self.contents.get(*offset, *size)?;
+            Ok(Some(DictEntry { word, translation }))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -97,8 +97,8 @@ impl<'a> StarDict {
             dist[i][0] = i;
         }
 
-        for j in 0..=pattern_chars.len() {
-            dist[0][j] = j;
+        for (j, x) in dist[0].iter_mut().enumerate() {
+            *x = j;
         }
 
         for i in 1..=text_chars.len() {
@@ -115,7 +115,7 @@ impl<'a> StarDict {
 
     /// Look up a word with fuzzy searching enabled.
     /// Performs a fuzzy search for similar words using edit distance
-    pub fn fuzzy_look_up(&self, word: &str) -> Option<Vec<DictEntry<'_>>> {
+    pub fn fuzzy_look_up(&self, word: &str) -> Result<Option<Vec<DictEntry<'_>>>> {
         let distances: Vec<_> = self
             .indices
             .items
@@ -123,7 +123,9 @@ impl<'a> StarDict {
             .filter(|s| !s.0.is_empty())
             .map(|s| Self::min_edit_distance(&word.to_lowercase(), &s.0.to_lowercase()))
             .collect();
-        let min_dist = distances.iter().min()?;
+        let Some(min_dist) = distances.iter().min() else {
+            return Ok(None);
+        };
         let result = self
             .indices
             .items
@@ -133,11 +135,11 @@ impl<'a> StarDict {
             .filter(|(idx, _)| distances[*idx] == *min_dist)
             .map(|(_, x)| {
                 let (word, offset, size) = x;
-                let translation = self.contents.get(*offset, *size);
-                DictEntry { word, translation }
+                let translation = self.contents.get(*offset, *size)?;
+                Ok(DictEntry { word, translation })
             })
-            .collect::<Vec<_>>();
-        Some(result)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(result))
     }
 
     /// Get the name of the stardict.
@@ -291,8 +293,10 @@ impl<'a> DictContents {
         &self.0
     }
 
-    fn get(&'a self, offset: usize, size: usize) -> &'a str {
-        &self.0[offset..offset + size]
+    fn get(&'a self, offset: usize, size: usize) -> Result<&'a str> {
+        self.0
+            .get(offset..offset + size)
+            .with_context(|| format!("Invalid entry at offset {} with size {}", offset, size))
     }
 }
 
@@ -387,7 +391,7 @@ mod test {
     #[test]
     fn lookup_offline() {
         let stardict = StarDict::new("./stardict-heritage/cdict-gb").unwrap();
-        stardict.exact_look_up("rust").unwrap();
+        stardict.exact_look_up("rust").unwrap().unwrap();
     }
 
     #[test]
@@ -396,7 +400,7 @@ mod test {
         let misspell = ["rst", "cago", "crade"];
         let correct = ["rust", "cargo", "crate"];
         for (miss, cor) in izip!(misspell, correct) {
-            let fuzzy = stardict.fuzzy_look_up(miss).unwrap();
+            let fuzzy = stardict.fuzzy_look_up(miss).unwrap().unwrap();
             fuzzy.iter().find(|w| w.word == cor).unwrap();
         }
     }
@@ -1,23 +1,73 @@
-#[cfg(feature = "pronunciation")]
-use crate::pronunciation;
-use crate::{
-    dict::{
-        llm::LlmDict, offline::OfflineDict, online::OnlineDict, Dict, LookUpResult,
-        LookUpResultItem,
-    },
-    history,
+//! Look up words across all configured dictionaries.
+//!
+//! [`DictManager`] performs no terminal I/O. Frontends decide how to present
+//! the [`LookUpReport`] and call [`DictManager::accept`] for the entry the user
+//! settled on, which runs the registered [`LookUpHook`]s.
+use crate::cache::LookUpCache;
+use crate::config::Config;
+use crate::dict::{
+    async_dict::run_blocking, context, llm::LlmDict, offline::OfflineDict, online::Youdao, Dict,
+    DictError, DictType, LookUpResult, LookUpResultItem, Query,
 };
+use crate::history::Notes;
+use crate::net::{self, RetryPolicy};
+use crate::wordlist::Wordlists;
 use anyhow::{Context, Result};
-use dialoguer::{console::Term, theme::ColorfulTheme, Select};
-use prettytable::{Attr, Cell, Row, Table};
-use rustyline::error::ReadlineError;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Side effects run for the entry a user settled on, e.g. recording history.
+pub trait LookUpHook: Send + Sync {
+    /// `context` is the sentence the word was looked up in, if any.
+    fn on_look_up(
+        &self,
+        item: &LookUpResultItem,
+        options: &DictOptions,
+        context: Option<&str>,
+    ) -> Result<()>;
+}
+
+/// A dictionary that failed to answer a query.
+#[derive(Debug, Clone)]
+pub struct LookUpFailure {
+    pub dict: String,
+    pub error: DictError,
+}
+
+/// Everything a query produced.
+pub struct LookUpReport {
+    /// The word with any option prefix stripped.
+    pub word: String,
+    /// The options the query ran with.
+    pub options: DictOptions,
+    /// The sentence the word was looked up in.
+    pub context: Option<String>,
+    /// The prompt template LLM dictionaries were asked with.
+    pub template: Option<String>,
+    /// An exact entry, fuzzy candidates, or nothing at all.
+    pub result: Option<LookUpResult>,
+    /// Dictionaries that were skipped, in the order they were tried.
+    pub failures: Vec<LookUpFailure>,
+    /// The entry is an expired cache entry, served because its dictionary
+    /// could not be reached.
+    pub stale: bool,
+    /// The entry was passed to the sink of [`DictManager::lookup_streaming`]
+    /// as it arrived.
+    pub streamed: bool,
+    /// The user's note on the word of the entry, see [`DictManager::with_notes`].
+    pub note: Option<String>,
+}
 
 pub struct DictManager {
     options: DictOptions,
-    online_dicts: Vec<Box<dyn Dict>>,
-    offline_dicts: Vec<Box<dyn Dict>>,
-    llm_dicts: Vec<Box<dyn Dict>>,
+    online_dicts: Vec<Arc<dyn Dict>>,
+    offline_dicts: Vec<Arc<dyn Dict>>,
+    llm_dicts: Vec<Arc<dyn Dict>>,
+    hooks: Vec<Box<dyn LookUpHook>>,
+    cache: Option<Arc<LookUpCache>>,
+    notes: Option<Notes>,
+    wordlists: Option<Wordlists>,
+    retry_policy: Option<RetryPolicy>,
 }
 
 impl DictManager {
@@ -33,7 +83,7 @@ impl DictManager {
             vec![]
         };
 
-        let online_dict = vec![Box::new(OnlineDict) as Box<dyn Dict>];
+        let online_dict = vec![Arc::new(Youdao) as Arc<dyn Dict>];
 
         let llm_dicts = if let Some(llm_dict_config_path) = llm_dict_config_path {
             let path = llm_dict_config_path.as_ref();
@@ -47,164 +97,389 @@ impl DictManager {
             offline_dicts,
             llm_dicts,
             options,
+            hooks: vec![],
+            cache: None,
+            notes: None,
+            wordlists: None,
+            retry_policy: None,
         })
     }
 
-    fn find_exact_match<'a>(
-        &'a self,
-        mut dicts: impl Iterator<Item = &'a &'a Box<dyn Dict>>,
-        word: &str,
-    ) -> Option<LookUpResultItem> {
-        dicts.find_map(|dict| {
-            let result = dict.look_up(false, word);
-            match result {
-                LookUpResult::Exact(item) => Some(item),
-                _ => {
-                    eprintln!("Failed to look up `{}` in dict {}", word, dict.name(),);
-                    None
-                }
+    /// A manager without any dictionaries, to be filled with [`DictManager::with_dict`].
+    pub fn empty(options: DictOptions) -> Self {
+        Self {
+            options,
+            online_dicts: vec![],
+            offline_dicts: vec![],
+            llm_dicts: vec![],
+            hooks: vec![],
+            cache: None,
+            notes: None,
+            wordlists: None,
+            retry_policy: None,
+        }
+    }
+
+    /// Add a dictionary, grouped by its [`DictType`].
+    pub fn with_dict(mut self, dict: impl Dict + 'static) -> Self {
+        let dict = Arc::new(dict) as Arc<dyn Dict>;
+        match dict.type_() {
+            DictType::OnlineDict => self.online_dicts.push(dict),
+            DictType::StarDict | DictType::Command => self.offline_dicts.push(dict),
+            DictType::LLM => self.llm_dicts.push(dict),
+        }
+        self
+    }
+
+    /// Add dictionaries from the user configuration.
+    pub fn with_config(mut self, config: &Config) -> Self {
+        if !config.online.is_empty() {
+            self.online_dicts = config
+                .online
+                .iter()
+                .filter(|x| x.enabled)
+                .map(|x| x.provider.build())
+                .collect();
+        }
+        config
+            .commands
+            .iter()
+            .cloned()
+            .fold(self, |manager, dict| manager.with_dict(dict))
+    }
+
+    /// Cache the responses of online and LLM dictionaries.
+    pub fn with_cache(mut self, cache: LookUpCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Retry failing dictionaries as `policy` says instead of as configured in `[network]`.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy.unwrap_or_else(net::retry_policy)
+    }
+
+    /// Report the user's notes on the words looked up.
+    pub fn with_notes(mut self, notes: Notes) -> Self {
+        self.notes = Some(notes);
+        self
+    }
+
+    /// The user's note on a word, e.g. one picked from fuzzy candidates.
+    pub fn note(&self, word: &str) -> Option<String> {
+        // Like the cache, notes are not worth failing a look-up over.
+        self.notes.as_ref()?.get(word).ok().flatten()
+    }
+
+    /// Tag the entries looked up with the imported word lists they are on,
+    /// whichever dictionary answered.
+    pub fn with_wordlists(mut self, wordlists: Wordlists) -> Self {
+        self.wordlists = Some(wordlists);
+        self
+    }
+
+    /// Add the word lists the word of `item` is on to its levels.
+    pub fn tag_with_wordlists(&self, item: &mut LookUpResultItem) {
+        let Some(wordlists) = &self.wordlists else {
+            return;
+        };
+        for list in wordlists.lists_of(&item.word).unwrap_or_default() {
+            if !item
+                .difficulty_levels
+                .iter()
+                .any(|x| x.eq_ignore_ascii_case(&list))
+            {
+                item.difficulty_levels.push(list);
             }
-        })
+        }
     }
 
-    // TODO: fuzzily look up all dictionaries and rank them
-    fn find_fuzzy_matches<'a>(
-        &'a self,
-        mut dicts: impl Iterator<Item = &'a &'a Box<dyn Dict>>,
-        word: &str,
-    ) -> Vec<LookUpResultItem> {
-        dicts
-            .find_map(|dict| {
-                if !dict.supports_fuzzy_search() {
-                    return None;
-                }
-                let result = dict.look_up(true, word);
-                match result {
-                    LookUpResult::Exact(item) => Some(vec![item]),
-                    LookUpResult::Fuzzy(items) => Some(items),
-                    LookUpResult::None => {
-                        eprintln!(
-                            "Failed to fuzzily look up `{}` in dict {}",
-                            word,
-                            dict.name(),
-                        );
-                        None
-                    }
-                }
-            })
-            .unwrap_or_default()
-    }
-
-    pub fn repl(&self) {
-        let mut rl = rustyline::DefaultEditor::new().unwrap();
-        loop {
-            let readline = rl.readline(">> ");
-            match readline {
-                Ok(word) => {
-                    let _ = rl.add_history_entry(&word);
-                    self.query(&word);
-                }
-                Err(ReadlineError::Interrupted) => break,
-                Err(ReadlineError::Eof) => break,
-                _ => {
-                    eprintln!("Failed to read lines");
-                    break;
-                }
+    /// Add the note and the word lists of the word of an exact entry to the report.
+    fn with_note(&self, mut report: LookUpReport) -> LookUpReport {
+        if let Some(LookUpResult::Exact(item)) = &mut report.result {
+            report.note = self.note(&item.word);
+            self.tag_with_wordlists(item);
+        }
+        report
+    }
+
+    /// Register a hook run by [`DictManager::accept`].
+    pub fn with_hook(mut self, hook: impl LookUpHook + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// The options used for words without a prefix.
+    pub fn options(&self) -> DictOptions {
+        self.options
+    }
+
+    /// All dictionaries, offline ones first.
+    pub fn dicts(&self) -> impl Iterator<Item = &dyn Dict> {
+        self.offline_dicts
+            .iter()
+            .chain(&self.online_dicts)
+            .chain(&self.llm_dicts)
+            .map(|dict| dict.as_ref())
+    }
+
+    /// Split a leading template name off a query like `#etymology rust`.
+    pub fn split_template(query: &str) -> (Option<&str>, &str) {
+        match query
+            .strip_prefix('#')
+            .and_then(|rest| rest.split_once(char::is_whitespace))
+        {
+            Some((name, word)) if !name.is_empty() => (Some(name), word.trim_start()),
+            _ => (None, query),
+        }
+    }
+
+    /// Split a query like `bank :: We sat on the bank.` into the word and the
+    /// sentence it appeared in.
+    pub fn split_context(query: &str) -> (&str, Option<&str>) {
+        match query.split_once("::") {
+            Some((word, context)) if !context.trim().is_empty() => {
+                (word.trim(), Some(context.trim()))
             }
+            Some((word, _)) => (word.trim(), None),
+            None => (query, None),
         }
     }
 
-    pub fn query(&self, word: &str) {
-        let (options, word) = match DictOptions::parse_prefixed_word(word) {
+    /// Split the option prefix off a query, e.g. `@rust` or `%rust`.
+    pub fn parse_query(&self, word: &str) -> (DictOptions, String) {
+        match DictOptions::parse_prefixed_word(word) {
             (Some(new_options), word) => (new_options, word),
-            (None, word) => (self.options, word.to_owned()),
-        };
-
-        let enable_fuzzy = !options.exact_match_only;
+            (None, word) => (self.options, word),
+        }
+    }
 
+    /// Dictionaries in the order the options ask them to be tried.
+    fn ordered_dicts(&self, options: &DictOptions) -> Vec<Arc<dyn Dict>> {
         let dicts = if options.prioritize_online_dict {
             self.online_dicts.iter().chain(&self.offline_dicts)
         } else {
             self.offline_dicts.iter().chain(&self.online_dicts)
         };
 
-        let dicts: Vec<_> = if options.use_llm_dicts {
-            self.llm_dicts.iter().chain(dicts).collect()
+        if options.use_llm_dicts {
+            self.llm_dicts.iter().chain(dicts).cloned().collect()
         } else {
-            dicts.chain(&self.llm_dicts).collect()
-        };
+            dicts.chain(&self.llm_dicts).cloned().collect()
+        }
+    }
 
-        let item = if let Some(exact_result) = self.find_exact_match(dicts.iter(), &word) {
-            Some(exact_result)
-        } else if enable_fuzzy {
-            println!("Fuzzy search enabled");
-            let fuzzy_results = self.find_fuzzy_matches(dicts.iter(), &word);
-            if let Some(selection) = Select::with_theme(&ColorfulTheme::default())
-                .items(
-                    &fuzzy_results
-                        .iter()
-                        .map(|w| w.word.as_str())
-                        .collect::<Vec<&str>>(),
-                )
-                .default(0)
-                .interact_on_opt(&Term::stderr())
-                .unwrap()
-            {
-                fuzzy_results.into_iter().nth(selection)
-            } else {
-                None
-            }
+    /// Look up a word with the given options.
+    pub fn lookup(&self, word: &str, options: DictOptions) -> LookUpReport {
+        self.lookup_streaming(word, options, &mut |_| ())
+    }
+
+    /// Look up a word, passing the definition to `sink` as it arrives from
+    /// dictionaries that stream, such as LLMs.
+    ///
+    /// The report still holds the complete entry; [`LookUpReport::streamed`]
+    /// tells whether it went through `sink`.
+    pub fn lookup_streaming(
+        &self,
+        word: &str,
+        options: DictOptions,
+        sink: &mut dyn FnMut(&str),
+    ) -> LookUpReport {
+        self.with_note(look_up_in(
+            &self.ordered_dicts(&options),
+            self.cache.as_deref(),
+            self.retry_policy(),
+            &Query::new(word),
+            options,
+            sink,
+        ))
+    }
+
+    /// Look up a word with a context or a prompt template.
+    ///
+    /// Dictionaries that understand queries, such as LLMs, are asked for the
+    /// meaning in the sentence with the chosen template; for the others the
+    /// sense best matching the sentence is picked by [`context::best_sense`].
+    pub fn lookup_query(
+        &self,
+        query: &Query,
+        options: DictOptions,
+        sink: &mut dyn FnMut(&str),
+    ) -> LookUpReport {
+        self.with_note(look_up_in(
+            &self.ordered_dicts(&options),
+            self.cache.as_deref(),
+            self.retry_policy(),
+            query,
+            options,
+            sink,
+        ))
+    }
+
+    /// Look up a word without blocking the async runtime.
+    pub async fn lookup_async(&self, word: &str, options: DictOptions) -> LookUpReport {
+        let dicts = self.ordered_dicts(&options);
+        let cache = self.cache.clone();
+        let retry_policy = self.retry_policy();
+        let word = word.to_owned();
+        let report = run_blocking(move || {
+            look_up_in(
+                &dicts,
+                cache.as_deref(),
+                retry_policy,
+                &Query::new(&word),
+                options,
+                &mut |_| (),
+            )
+        })
+        .await;
+        self.with_note(report)
+    }
+
+    /// Run the hooks for the entry the user settled on.
+    ///
+    /// Every hook runs even if an earlier one fails; the errors are returned.
+    pub fn accept(
+        &self,
+        item: &LookUpResultItem,
+        options: &DictOptions,
+        context: Option<&str>,
+    ) -> Vec<anyhow::Error> {
+        self.hooks
+            .iter()
+            .filter_map(|hook| hook.on_look_up(item, options, context).err())
+            .collect()
+    }
+}
+
+fn look_up_in(
+    dicts: &[Arc<dyn Dict>],
+    cache: Option<&LookUpCache>,
+    retry_policy: RetryPolicy,
+    query: &Query,
+    options: DictOptions,
+    sink: &mut dyn FnMut(&str),
+) -> LookUpReport {
+    let word = query.word;
+    let mut report = LookUpReport {
+        word: word.to_owned(),
+        options,
+        context: query.context.map(str::to_owned),
+        template: query.template.map(str::to_owned),
+        result: None,
+        failures: vec![],
+        stale: false,
+        streamed: false,
+        note: None,
+    };
+
+    // Senses are picked for dictionaries that cannot read the sentence.
+    let pick_sense = |dict: &dyn Dict, mut item: LookUpResultItem| {
+        if let Some(context) = query.context.filter(|_| !dict.understands_queries()) {
+            item.context_sense = context::best_sense(&item, context);
+        }
+        LookUpResult::Exact(item)
+    };
+
+    for dict in dicts {
+        // Answers to a context or template are cached apart from plain ones.
+        let key = if dict.understands_queries() {
+            query.cache_key()
         } else {
-            None
+            word.to_owned()
         };
+        let cache = cache.and_then(|cache| Some((cache, dict.cache_identity()?)));
+        // Cache errors are not worth failing a look-up over; treat them as misses.
+        let cached = cache
+            .as_ref()
+            .and_then(|(cache, identity)| cache.get(identity, &key).ok().flatten());
+        if let Some(cached) = &cached {
+            if !cached.stale && !options.refresh_cache {
+                report.result = Some(pick_sense(dict.as_ref(), cached.item.clone()));
+                return report;
+            }
+        }
 
-        if let Some(item) = item {
-            println!("{}", item);
-            if let Err(e) = history::insert_history_record(&item.word, item.difficulty_levels) {
-                eprintln!("Failed to insert history record: {}", e);
+        let mut streamed = false;
+        let mut tracking_sink = |text: &str| {
+            streamed = true;
+            sink(text);
+        };
+        match look_up_with_retry(
+            retry_policy,
+            dict.as_ref(),
+            false,
+            query,
+            &mut tracking_sink,
+        ) {
+            Ok(LookUpResult::Exact(item)) => {
+                report.streamed = streamed;
+                if let Some((cache, identity)) = &cache {
+                    let _ = cache.put(identity, &key, &item);
+                }
+                report.result = Some(pick_sense(dict.as_ref(), item));
+                return report;
             }
-            #[cfg(feature = "pronunciation")]
-            if options.read_aloud {
-                if let Err(e) = pronunciation::pronounce(&item.word) {
-                    eprintln!("Failed to read aloud: {}", e);
+            Ok(LookUpResult::Fuzzy(_)) => (),
+            Err(error) => {
+                let offline = matches!(error, DictError::Network(_));
+                report.failures.push(LookUpFailure {
+                    dict: dict.name().to_owned(),
+                    error,
+                });
+                if let Some(cached) = cached.filter(|_| offline) {
+                    report.result = Some(pick_sense(dict.as_ref(), cached.item));
+                    report.stale = true;
+                    return report;
                 }
             }
-        } else {
-            eprintln!("No result found");
         }
     }
 
-    pub fn list_dicts(&self) {
-        let mut table: Table = Table::new();
-        table.add_row(Row::new(vec![
-            Cell::new("Dictionary's name").with_style(Attr::Bold),
-            Cell::new("Type").with_style(Attr::Bold),
-            Cell::new("Word count").with_style(Attr::Bold),
-        ]));
+    if options.exact_match_only {
+        return report;
+    }
 
-        self.offline_dicts
-            .iter()
-            .chain(&self.online_dicts)
-            .chain(&self.llm_dicts)
-            .for_each(|dict| {
-                let row = Row::new(vec![
-                    Cell::new(dict.name()),
-                    Cell::new(dict.type_().to_string().as_str()),
-                    Cell::new(
-                        dict.word_count()
-                            .map(|n| n.to_string())
-                            .unwrap_or("-".to_owned())
-                            .as_str(),
-                    ),
-                ]);
-                table.add_row(row);
-            });
-
-        table.printstd();
+    // TODO: fuzzily look up all dictionaries and rank them
+    for dict in dicts.iter().filter(|dict| dict.supports_fuzzy_search()) {
+        match look_up_with_retry(retry_policy, dict.as_ref(), true, query, &mut |_| ()) {
+            Ok(result) => {
+                report.result = Some(result);
+                return report;
+            }
+            Err(error) => report.failures.push(LookUpFailure {
+                dict: dict.name().to_owned(),
+                error,
+            }),
+        }
     }
+
+    report
+}
+
+/// Look up a query, retrying failures that may go away on their own.
+fn look_up_with_retry(
+    retry_policy: RetryPolicy,
+    dict: &dyn Dict,
+    enable_fuzzy: bool,
+    query: &Query,
+    sink: &mut dyn FnMut(&str),
+) -> Result<LookUpResult, DictError> {
+    retry_policy.run(|| {
+        if query.is_plain() || !dict.understands_queries() {
+            dict.look_up_streaming(enable_fuzzy, query.word, sink)
+        } else {
+            dict.look_up_query(query, sink)
+        }
+    })
 }
 
-fn load_offline_dicts<P: AsRef<Path>>(offline_dict_dir: P) -> Result<Vec<Box<dyn Dict>>> {
+fn load_offline_dicts<P: AsRef<Path>>(offline_dict_dir: P) -> Result<Vec<Arc<dyn Dict>>> {
     let path = offline_dict_dir.as_ref();
     let mut dicts: Vec<_> = path
         .read_dir()
@@ -216,11 +491,11 @@ fn load_offline_dicts<P: AsRef<Path>>(offline_dict_dir: P) -> Result<Vec<Box<dyn
         .into_iter()
         .map(|dir| OfflineDict::new(dir.path()))
         .filter_map(|x| x.ok())
-        .map(|dict| Box::new(dict) as Box<dyn Dict>)
+        .map(|dict| Arc::new(dict) as Arc<dyn Dict>)
         .collect())
 }
 
-pub fn load_llm_dicts<P: AsRef<Path>>(path: P) -> Result<Vec<Box<dyn Dict>>> {
+pub fn load_llm_dicts<P: AsRef<Path>>(path: P) -> Result<Vec<Arc<dyn Dict>>> {
     let content = std::fs::read_to_string(path)?;
     let config: toml::Value = content.parse()?;
 
@@ -230,7 +505,7 @@ pub fn load_llm_dicts<P: AsRef<Path>>(path: P) -> Result<Vec<Box<dyn Dict>>> {
 
     let mut result = Vec::new();
     for entry in dicts {
-        let dict: Box<dyn Dict> = entry.clone().try_into::<LlmDict>().map(Box::new)?;
+        let dict: Arc<dyn Dict> = Arc::new(entry.clone().try_into::<LlmDict>()?);
         result.push(dict);
     }
 
@@ -243,6 +518,8 @@ pub struct DictOptions {
     pub prioritize_offline_dicts: bool,
     pub use_llm_dicts: bool,
     pub exact_match_only: bool,
+    /// Ignore cached responses and query the dictionaries again.
+    pub refresh_cache: bool,
     #[cfg(feature = "pronunciation")]
     pub read_aloud: bool,
 }
@@ -256,22 +533,6 @@ fn split_non_alphanumeric_prefix(s: &str) -> (&str, &str) {
     (s, "")
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_split() {
-        assert_eq!(split_non_alphanumeric_prefix("123abc"), ("", "123abc"));
-        assert_eq!(split_non_alphanumeric_prefix("!!hello"), ("!!", "hello"));
-        assert_eq!(split_non_alphanumeric_prefix("abcdef"), ("", "abcdef"));
-        assert_eq!(split_non_alphanumeric_prefix("***123"), ("***", "123"));
-        assert_eq!(split_non_alphanumeric_prefix(""), ("", ""));
-        assert_eq!(split_non_alphanumeric_prefix("á123"), ("", "á123"));
-        assert_eq!(split_non_alphanumeric_prefix("&&&ábc"), ("&&&", "ábc"));
-    }
-}
-
 impl Default for DictOptions {
     fn default() -> Self {
         Self {
@@ -279,6 +540,7 @@ impl Default for DictOptions {
             prioritize_offline_dicts: true,
             use_llm_dicts: false,
             exact_match_only: false,
+            refresh_cache: false,
             #[cfg(feature = "pronunciation")]
             read_aloud: false,
         }
@@ -332,6 +594,11 @@ impl DictOptions {
         self
     }
 
+    pub fn refresh_cache(mut self, refresh: bool) -> Self {
+        self.refresh_cache = refresh;
+        self
+    }
+
     #[cfg(feature = "pronunciation")]
     pub fn read_aloud(mut self, read_aloud: bool) -> Self {
         self.read_aloud = read_aloud;
@@ -372,3 +639,377 @@ pub fn default_llm_dict_config_path() -> Option<PathBuf> {
         None => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    struct MockDict {
+        name: &'static str,
+        words: Vec<&'static str>,
+        failures: Mutex<Vec<DictError>>,
+        type_: DictType,
+    }
+
+    impl MockDict {
+        fn new(name: &'static str, words: Vec<&'static str>) -> Self {
+            Self {
+                name,
+                words,
+                failures: Mutex::new(vec![]),
+                type_: DictType::StarDict,
+            }
+        }
+
+        fn online(mut self) -> Self {
+            self.type_ = DictType::OnlineDict;
+            self
+        }
+
+        fn failing_with(self, failures: Vec<DictError>) -> Self {
+            *self.failures.lock().unwrap() = failures;
+            self
+        }
+    }
+
+    impl Dict for MockDict {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn type_(&self) -> DictType {
+            self.type_
+        }
+
+        fn supports_fuzzy_search(&self) -> bool {
+            true
+        }
+
+        fn look_up(&self, enable_fuzzy: bool, word: &str) -> Result<LookUpResult, DictError> {
+            if let Some(e) = self.failures.lock().unwrap().pop() {
+                return Err(e);
+            }
+            let item = |w: &str| LookUpResultItem::new(w, format!("{} from {}", w, self.name));
+            if self.words.contains(&word) {
+                Ok(LookUpResult::Exact(item(word)))
+            } else if enable_fuzzy {
+                let candidates: Vec<_> = self
+                    .words
+                    .iter()
+                    .filter(|w| w.starts_with(&word[..1]))
+                    .map(|w| item(w))
+                    .collect();
+                if candidates.is_empty() {
+                    Err(DictError::NotFound)
+                } else {
+                    Ok(LookUpResult::Fuzzy(candidates))
+                }
+            } else {
+                Err(DictError::NotFound)
+            }
+        }
+
+        fn word_count(&self) -> Option<usize> {
+            Some(self.words.len())
+        }
+    }
+
+    struct CountingHook(Arc<AtomicUsize>);
+
+    impl LookUpHook for CountingHook {
+        fn on_look_up(&self, _: &LookUpResultItem, _: &DictOptions, _: Option<&str>) -> Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn manager() -> DictManager {
+        DictManager::empty(DictOptions::default())
+            .with_dict(MockDict::new("first", vec!["cargo"]))
+            .with_dict(MockDict::new("second", vec!["rust", "crate"]))
+    }
+
+    #[test]
+    fn lookup_falls_through_dicts() {
+        let report = manager().lookup("rust", DictOptions::default());
+        assert!(matches!(
+            report.result,
+            Some(LookUpResult::Exact(item)) if item.translation == "rust from second"
+        ));
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].dict, "first");
+        assert_eq!(report.failures[0].error, DictError::NotFound);
+    }
+
+    #[test]
+    fn lookup_fuzzy_candidates() {
+        let report = manager().lookup("crat", DictOptions::default());
+        assert!(matches!(
+            report.result,
+            Some(LookUpResult::Fuzzy(items)) if items[0].word == "cargo"
+        ));
+
+        let report = manager().lookup("crat", DictOptions::default().require_exact_match(true));
+        assert!(report.result.is_none());
+    }
+
+    #[test]
+    fn lookup_retries_transient_errors() {
+        let manager = DictManager::empty(DictOptions::default()).with_dict(
+            MockDict::new("flaky", vec!["rust"])
+                .failing_with(vec![DictError::Server("502 Bad Gateway".to_owned())]),
+        );
+        let report = manager.lookup("rust", DictOptions::default());
+        assert!(matches!(report.result, Some(LookUpResult::Exact(_))));
+        assert!(report.failures.is_empty());
+
+        let manager = DictManager::empty(DictOptions::default())
+            .with_dict(
+                MockDict::new("revoked", vec!["rust"])
+                    .failing_with(vec![DictError::Auth("401 Unauthorized".to_owned())]),
+            )
+            .with_dict(MockDict::new("backup", vec!["rust"]));
+        let report = manager.lookup("rust", DictOptions::default());
+        assert!(matches!(
+            report.result,
+            Some(LookUpResult::Exact(item)) if item.translation == "rust from backup"
+        ));
+        assert!(matches!(report.failures[0].error, DictError::Auth(_)));
+    }
+
+    #[test]
+    fn lookup_uses_cache() {
+        let db = Database::open_in_memory().unwrap();
+        let retry_policy = RetryPolicy {
+            backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let manager = |failures: Vec<DictError>| {
+            let cache = LookUpCache::new(db.clone(), Duration::from_secs(3600));
+            DictManager::empty(DictOptions::default())
+                .with_dict(
+                    MockDict::new("online", vec!["rust"])
+                        .online()
+                        .failing_with(failures),
+                )
+                .with_cache(cache)
+                .with_retry_policy(retry_policy)
+        };
+        let report = manager(vec![]).lookup("rust", DictOptions::default());
+        assert!(matches!(report.result, Some(LookUpResult::Exact(_))));
+
+        // Served from the cache without asking the dictionary.
+        let revoked = vec![DictError::Auth("401 Unauthorized".to_owned())];
+        let report = manager(revoked).lookup("rust", DictOptions::default());
+        assert!(matches!(report.result, Some(LookUpResult::Exact(_))));
+        assert!(report.failures.is_empty() && !report.stale);
+
+        // Refreshing while offline falls back to the cached entry.
+        let offline =
+            vec![DictError::Network("unreachable".to_owned()); retry_policy.retries as usize + 1];
+        let report = manager(offline).lookup("rust", DictOptions::default().refresh_cache(true));
+        assert!(matches!(report.result, Some(LookUpResult::Exact(_))));
+        assert!(report.stale);
+    }
+
+    #[test]
+    fn lookup_reports_notes() {
+        let db = Database::open_in_memory().unwrap();
+        crate::history::set_note(&db.lock(), "rust", "Fe2O3").unwrap();
+        let manager = manager().with_notes(crate::history::Notes(db));
+        let report = manager.lookup("rust", DictOptions::default());
+        assert_eq!(report.note.as_deref(), Some("Fe2O3"));
+        let report = manager.lookup("crate", DictOptions::default());
+        assert_eq!(report.note, None);
+    }
+
+    #[test]
+    fn lookup_tags_words_of_lists() {
+        let db = Database::open_in_memory().unwrap();
+        let list = "GRE".to_owned();
+        crate::wordlist::save(&db.lock(), &list, "gre.txt", &["Rust".to_owned()]).unwrap();
+        let manager = manager().with_wordlists(Wordlists(db));
+        let Some(LookUpResult::Exact(item)) = manager.lookup("rust", DictOptions::default()).result
+        else {
+            panic!("expected an exact match");
+        };
+        // Answered by a local dictionary, which knows no levels.
+        assert_eq!(item.translation, "rust from second");
+        assert_eq!(item.difficulty_levels, ["GRE"]);
+        let Some(LookUpResult::Exact(item)) =
+            manager.lookup("crate", DictOptions::default()).result
+        else {
+            panic!("expected an exact match");
+        };
+        assert!(item.difficulty_levels.is_empty());
+    }
+
+    struct StreamingDict;
+
+    impl Dict for StreamingDict {
+        fn name(&self) -> &str {
+            "streaming"
+        }
+
+        fn type_(&self) -> DictType {
+            DictType::LLM
+        }
+
+        fn supports_fuzzy_search(&self) -> bool {
+            false
+        }
+
+        fn look_up(&self, enable_fuzzy: bool, word: &str) -> Result<LookUpResult, DictError> {
+            self.look_up_streaming(enable_fuzzy, word, &mut |_| ())
+        }
+
+        fn look_up_streaming(
+            &self,
+            _: bool,
+            word: &str,
+            sink: &mut dyn FnMut(&str),
+        ) -> Result<LookUpResult, DictError> {
+            ["铁", "锈"].into_iter().for_each(&mut *sink);
+            Ok(LookUpResult::Exact(LookUpResultItem::new(
+                word,
+                "铁锈".to_owned(),
+            )))
+        }
+
+        fn word_count(&self) -> Option<usize> {
+            None
+        }
+    }
+
+    #[test]
+    fn lookup_streams_entries() {
+        let manager = manager().with_dict(StreamingDict);
+        let options = DictOptions::default().use_llm_dicts(true);
+        let mut text = String::new();
+        let report = manager.lookup_streaming("rust", options, &mut |x| text.push_str(x));
+        assert_eq!(text, "铁锈");
+        assert!(report.streamed);
+
+        let report = manager.lookup_streaming("rust", DictOptions::default(), &mut |_| ());
+        assert!(!report.streamed);
+    }
+
+    struct QueryDict;
+
+    impl Dict for QueryDict {
+        fn name(&self) -> &str {
+            "context"
+        }
+
+        fn type_(&self) -> DictType {
+            DictType::LLM
+        }
+
+        fn supports_fuzzy_search(&self) -> bool {
+            false
+        }
+
+        fn look_up(&self, _: bool, word: &str) -> Result<LookUpResult, DictError> {
+            Ok(LookUpResult::Exact(LookUpResultItem::new(
+                word,
+                "any sense".to_owned(),
+            )))
+        }
+
+        fn understands_queries(&self) -> bool {
+            true
+        }
+
+        fn look_up_query(
+            &self,
+            query: &Query,
+            _: &mut dyn FnMut(&str),
+        ) -> Result<LookUpResult, DictError> {
+            let translation = match query.template {
+                Some(template) => format!("the {} of {}", template, query.word),
+                None => format!("the sense in `{}`", query.context.unwrap_or_default()),
+            };
+            Ok(LookUpResult::Exact(LookUpResultItem::new(
+                query.word,
+                translation,
+            )))
+        }
+
+        fn word_count(&self) -> Option<usize> {
+            None
+        }
+    }
+
+    #[test]
+    fn lookup_queries() {
+        assert_eq!(
+            DictManager::split_context("bank :: We sat on the bank."),
+            ("bank", Some("We sat on the bank."))
+        );
+        assert_eq!(DictManager::split_context("bank ::  "), ("bank", None));
+        assert_eq!(DictManager::split_context("bank"), ("bank", None));
+        assert_eq!(
+            DictManager::split_template("#etymology  rust"),
+            (Some("etymology"), "rust")
+        );
+        assert_eq!(DictManager::split_template("#rust"), (None, "#rust"));
+
+        let manager = manager().with_dict(QueryDict);
+        let options = DictOptions::default().use_llm_dicts(true);
+        let query = Query::new("bank").in_context(Some("We sat on the bank."));
+        let report = manager.lookup_query(&query, options, &mut |_| ());
+        let Some(LookUpResult::Exact(item)) = report.result else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.translation, "the sense in `We sat on the bank.`");
+        assert_eq!(report.context.as_deref(), Some("We sat on the bank."));
+
+        let query = Query::new("rust").with_template(Some("etymology"));
+        let report = manager.lookup_query(&query, options, &mut |_| ());
+        assert!(matches!(
+            report.result,
+            Some(LookUpResult::Exact(item)) if item.translation == "the etymology of rust"
+        ));
+        assert_eq!(report.template.as_deref(), Some("etymology"));
+
+        // Dictionaries that cannot read the sentence answer as usual.
+        let query = Query::new("rust").in_context(Some("Rust never sleeps."));
+        let report = manager.lookup_query(&query, DictOptions::default(), &mut |_| ());
+        let Some(LookUpResult::Exact(item)) = report.result else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(item.translation, "rust from second");
+        assert_eq!(item.context_sense, None);
+    }
+
+    #[test]
+    fn lookup_async_and_hooks() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let manager = manager().with_hook(CountingHook(count.clone()));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let report = runtime.block_on(manager.lookup_async("cargo", DictOptions::default()));
+        let Some(LookUpResult::Exact(item)) = report.result else {
+            panic!("expected an exact match");
+        };
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        assert!(manager.accept(&item, &report.options, None).is_empty());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split_non_alphanumeric_prefix("123abc"), ("", "123abc"));
+        assert_eq!(split_non_alphanumeric_prefix("!!hello"), ("!!", "hello"));
+        assert_eq!(split_non_alphanumeric_prefix("abcdef"), ("", "abcdef"));
+        assert_eq!(split_non_alphanumeric_prefix("***123"), ("***", "123"));
+        assert_eq!(split_non_alphanumeric_prefix(""), ("", ""));
+        assert_eq!(split_non_alphanumeric_prefix("á123"), ("", "á123"));
+        assert_eq!(split_non_alphanumeric_prefix("&&&ábc"), ("&&&", "ábc"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Terminal frontend on top of [`DictManager`].
+use crate::config::DisplayConfig;
+use crate::dict::{DictError, LookUpResult, LookUpResultItem, Query};
+use crate::dicts::{DictManager, LookUpFailure};
+use crate::review::{self, Grade, ReviewStore};
+use anyhow::Result;
+use chrono::Utc;
+use dialoguer::{
+    console::{Key, Term},
+    theme::ColorfulTheme,
+    Select,
+};
+use prettytable::{Attr, Cell, Row, Table};
+use rustyline::error::ReadlineError;
+use std::io::Write;
+
+/// Read words from the terminal and look them up until EOF.
+pub fn repl(manager: &DictManager, display: &DisplayConfig) {
+    let mut rl = rustyline::DefaultEditor::new().unwrap();
+    loop {
+        let readline = rl.readline(">> ");
+        match readline {
+            Ok(word) => {
+                let _ = rl.add_history_entry(&word);
+                query(manager, display, &word, None, None);
+            }
+            Err(ReadlineError::Interrupted) => break,
+            Err(ReadlineError::Eof) => break,
+            _ => {
+                eprintln!("Failed to read lines");
+                break;
+            }
+        }
+    }
+}
+
+/// Look up a word, possibly prefixed with `#template` and options and
+/// followed by `:: sentence`, and print the entry with the sections enabled
+/// in `display`.
+///
+/// `context` and `template` are given on the command line; those in the
+/// query take precedence. A template asks LLM dictionaries first.
+pub fn query(
+    manager: &DictManager,
+    display: &DisplayConfig,
+    word: &str,
+    context: Option<&str>,
+    template: Option<&str>,
+) {
+    let (word, inline_context) = DictManager::split_context(word);
+    let context = inline_context.or(context);
+    let (inline_template, word) = DictManager::split_template(word);
+    let template = inline_template.or(template);
+    let (options, word) = manager.parse_query(word);
+    let options = if template.is_some() {
+        options.use_llm_dicts(true)
+    } else {
+        options
+    };
+    let mut started = false;
+    let mut sink = |text: &str| {
+        if !started {
+            println!("{}", word);
+            started = true;
+        }
+        print!("{}", text);
+        let _ = std::io::stdout().flush();
+    };
+    let query = Query::new(&word)
+        .in_context(context)
+        .with_template(template);
+    let report = manager.lookup_query(&query, options, &mut sink);
+    if started {
+        println!();
+    }
+
+    report
+        .failures
+        .iter()
+        .for_each(|failure| report_failure(&report.word, failure));
+
+    let (item, note) = match report.result {
+        Some(LookUpResult::Exact(item)) => (Some(item), report.note),
+        Some(LookUpResult::Fuzzy(items)) => {
+            println!("Fuzzy search enabled");
+            let mut item = select_fuzzy_match(items);
+            if let Some(item) = &mut item {
+                manager.tag_with_wordlists(item);
+            }
+            let note = item.as_ref().and_then(|x| manager.note(&x.word));
+            (item, note)
+        }
+        None => (None, None),
+    };
+
+    if let Some(item) = item {
+        if report.stale {
+            eprintln!("Offline, showing an expired cached result");
+        }
+        // A streamed entry is already on the screen.
+        if !report.streamed {
+            let mut shown = item.clone();
+            display.apply(&mut shown);
+            println!("{}", shown);
+        } else if !item.difficulty_levels.is_empty() {
+            let levels: Vec<_> = item
+                .difficulty_levels
+                .iter()
+                .map(|x| format!("<{}>", x))
+                .collect();
+            println!("{}", levels.join(" "));
+        }
+        if let Some(note) = note {
+            println!("Note: {}", note);
+        }
+        manager
+            .accept(&item, &options, report.context.as_deref())
+            .iter()
+            .for_each(|e| eprintln!("{:#}", e));
+    } else {
+        eprintln!("No result found");
+    }
+}
+
+fn select_fuzzy_match(items: Vec<LookUpResultItem>) -> Option<LookUpResultItem> {
+    if items.is_empty() {
+        return None;
+    }
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .items(&items.iter().map(|w| w.word.as_str()).collect::<Vec<&str>>())
+        .default(0)
+        .interact_on_opt(&Term::stderr())
+        .unwrap()?;
+    items.into_iter().nth(selection)
+}
+
+fn report_failure(word: &str, failure: &LookUpFailure) {
+    match &failure.error {
+        DictError::NotFound => eprintln!("`{}` is not found in dict {}", word, failure.dict),
+        e => eprintln!(
+            "Failed to look up `{}` in dict {}: {}",
+            word, failure.dict, e
+        ),
+    }
+}
+
+/// Review up to `limit` due words: show each word, reveal its definition on
+/// a key press and record the grade. Words without a stored definition are
+/// looked up with `manager`. Esc or `q` ends the session early.
+pub fn review(store: &ReviewStore, manager: &DictManager, limit: usize) -> Result<()> {
+    let cards = store.due(Utc::now(), limit)?;
+    if cards.is_empty() {
+        println!("Nothing to review");
+    }
+    let term = Term::stderr();
+    let total = cards.len();
+    for (i, mut card) in cards.into_iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, total, card.word);
+        term.write_line("Press any key to show the definition, q to stop")?;
+        if matches!(term.read_key()?, Key::Escape | Key::Char('q')) {
+            break;
+        }
+        term.clear_last_lines(1)?;
+
+        let definition = match card.definition.take() {
+            Some(definition) => Some(definition),
+            None => match manager.lookup(&card.word, manager.options()).result {
+                Some(LookUpResult::Exact(item)) => {
+                    let definition = item.plain_text();
+                    store.set_definition(&card.word, &definition)?;
+                    Some(definition)
+                }
+                _ => None,
+            },
+        };
+        println!(
+            "{}",
+            definition
+                .as_deref()
+                .unwrap_or("No definition found")
+                .trim()
+        );
+
+        let labels: Vec<_> = Grade::ALL.iter().map(Grade::label).collect();
+        let Some(selection) = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("How well did you remember it?")
+            .items(&labels)
+            .default(2)
+            .interact_on_opt(&term)?
+        else {
+            break;
+        };
+        let now = Utc::now();
+        card.review(Grade::ALL[selection], now);
+        store.save(&card, now)?;
+        println!("Next review in {} days\n", card.interval);
+    }
+    review::print_summary(&store.summary(Utc::now())?);
+    Ok(())
+}
+
+/// Print a table of all dictionaries.
+pub fn list_dicts(manager: &DictManager) {
+    let mut table: Table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Dictionary's name").with_style(Attr::Bold),
+        Cell::new("Type").with_style(Attr::Bold),
+        Cell::new("Word count").with_style(Attr::Bold),
+    ]));
+
+    manager.dicts().for_each(|dict| {
+        let row = Row::new(vec![
+            Cell::new(dict.name()),
+            Cell::new(dict.type_().to_string().as_str()),
+            Cell::new(
+                dict.word_count()
+                    .map(|n| n.to_string())
+                    .unwrap_or("-".to_owned())
+                    .as_str(),
+            ),
+        ]);
+        table.add_row(row);
+    });
+
+    table.printstd();
+}
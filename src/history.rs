@@ -1,20 +1,25 @@
 //! History query and addition using [sqlite](https://sqlite.org/index.html).
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, Utc};
 use dirs::cache_dir;
 use prettytable::{Attr, Cell, Row, Table};
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::BTreeMap;
 use std::fs::create_dir;
 use std::path::PathBuf;
 
-use crate::dict::DifficultyLevel;
+use crate::db::Database;
+use crate::dict::LookUpResultItem;
+use crate::dicts::{DictOptions, LookUpHook};
+use crate::sync::{self, Change};
+use crate::transfer::HistoryRecord;
 
 /// Allowed difficulty levels of a word.
 pub static ALLOWED_DIFFICULTY_LEVELS: [&str; 7] =
     ["CET4", "CET6", "TOEFL", "IELTS", "GMAT", "GRE", "SAT"];
 
 /// Check and generate cache directory path.
-fn ensure_cache_directory() -> Result<PathBuf> {
+pub fn ensure_cache_directory() -> Result<PathBuf> {
     let mut path = cache_dir().with_context(|| "Couldn't find cache directory")?;
     path.push("dioxionary");
     if !path.exists() {
@@ -24,80 +29,422 @@ fn ensure_cache_directory() -> Result<PathBuf> {
     Ok(path)
 }
 
-/// Insert history record.
-pub fn insert_history_record(word: &str, difficulty_levels: Vec<DifficultyLevel>) -> Result<()> {
-    let date = Utc::now().timestamp();
+/// Records every accepted look-up in the history database.
+pub struct HistoryHook(pub Database);
+
+impl LookUpHook for HistoryHook {
+    fn on_look_up(
+        &self,
+        item: &LookUpResultItem,
+        _: &DictOptions,
+        context: Option<&str>,
+    ) -> Result<()> {
+        let conn = self.0.lock();
+        insert_history_record(&conn, item).with_context(|| "Failed to insert history record")?;
+        if let Some(sentence) = context {
+            insert_context_record(&conn, &item.word, sentence)
+                .with_context(|| "Failed to insert the sentence of the word")?;
+        }
+        Ok(())
+    }
+}
 
-    let path = ensure_cache_directory()?;
+/// Insert history record, or count another look-up of a known word.
+pub fn insert_history_record(conn: &Connection, item: &LookUpResultItem) -> Result<()> {
+    record_look_up(conn, item, Utc::now().timestamp())
+}
 
-    let conn = Connection::open(path)?;
+pub(crate) fn record_look_up(conn: &Connection, item: &LookUpResultItem, date: i64) -> Result<()> {
+    let word = item.word.as_str();
+    let source = item.source.as_ref().map(|x| x.name.as_str());
+    let definition = item.plain_text();
+    let entry = serde_json::to_value(item)?;
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS HISTORY (
-        WORD TEXT PRIMARY KEY,
-        DATE INTEGER NOT NULL,
-        CET4 INTEGER,
-        CET6 INTEGER,
-        TOEFL INTEGER,
-        IELTS INTEGER,
-        GMAT INTEGER,
-        GRE INTEGER,
-        SAT INTEGER
-        )",
-        (), // empty list of parameters.
+        "INSERT INTO HISTORY (WORD, DATE, LAST_DATE, SOURCE, DEFINITION, ENTRY)
+        VALUES (?1, ?2, ?2, ?3, ?4, ?5)
+        ON CONFLICT (WORD) DO UPDATE SET LOOKUPS = LOOKUPS + 1,
+        LAST_DATE = excluded.LAST_DATE, SOURCE = excluded.SOURCE,
+        DEFINITION = excluded.DEFINITION, ENTRY = excluded.ENTRY",
+        (word, date, source, &definition, entry.to_string()),
     )?;
-
     conn.execute(
-        "INSERT OR IGNORE INTO HISTORY (word, date) VALUES (?1, ?2)",
-        (word, date),
+        "INSERT INTO EVENTS (WORD, DATE, SOURCE) VALUES (?1, ?2, ?3)",
+        (word, date, source),
+    )?;
+    let (machine, lookups): (String, u32) = conn.query_row(
+        "INSERT INTO MACHINE_LOOKUPS (WORD, MACHINE, LOOKUPS) SELECT ?1, NAME, 1 FROM MACHINE WHERE TRUE
+        ON CONFLICT (WORD, MACHINE) DO UPDATE SET LOOKUPS = LOOKUPS + 1
+        RETURNING MACHINE, LOOKUPS",
+        [word],
+        |row| Ok((row.get(0)?, row.get(1)?)),
     )?;
 
-    difficulty_levels.iter().for_each(|x| {
-        if ALLOWED_DIFFICULTY_LEVELS.contains(&x.as_str()) {
-            let sql = format!("UPDATE HISTORY SET {} = 1 WHERE WORD = '{}'", x, word);
-            conn.execute(sql.as_str(), ()).unwrap();
-        }
-    });
+    let levels: Vec<_> = item
+        .difficulty_levels
+        .iter()
+        .filter(|x| ALLOWED_DIFFICULTY_LEVELS.contains(&x.as_str()))
+        .cloned()
+        .collect();
+    for level in &levels {
+        add_tag(conn, word, level)?;
+    }
+    tag_from_wordlists(conn, word)?;
+
+    sync::log_change(
+        conn,
+        &Change::LookUp(HistoryRecord {
+            word: word.to_owned(),
+            lookups,
+            first_seen: date,
+            last_seen: date,
+            source: source.map(str::to_owned),
+            definition: Some(definition),
+            tags: levels,
+            entry: Some(entry),
+            machines: BTreeMap::from([(machine, lookups)]),
+        }),
+    )?;
 
     Ok(())
 }
 
-/// List sorted or not history of a word difficulty level or all levels.
-///
-/// The output will be like:
-/// txt
-/// +------+------+-------+-------+------+-----+-----+
-/// | CET4 | CET6 | TOEFL | IELTS | GMAT | GRE | SAT |
-/// +------+------+-------+-------+------+-----+-----+
-/// | 220  | 305  | 207   | 203   | 142  | 242 | 126 |
-/// +------+------+-------+-------+------+-----+-----+
-///
-pub fn list_history_records(
-    difficulty_level: Option<DifficultyLevel>,
+/// Tag a word for a reason other than a word list, so that removing a list
+/// of the same name keeps the tag. Returns whether the word newly got it.
+pub(crate) fn add_tag(conn: &Connection, word: &str, tag: &str) -> Result<bool> {
+    let added = conn
+        .prepare_cached(
+            "INSERT INTO TAGS (WORD, TAG) VALUES (?1, ?2)
+            ON CONFLICT (WORD, TAG) DO UPDATE SET LISTED = 0 WHERE LISTED",
+        )?
+        .execute((word, tag))?;
+    Ok(added > 0)
+}
+
+/// Tag a word with the word lists it is on.
+pub(crate) fn tag_from_wordlists(conn: &Connection, word: &str) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO TAGS (WORD, TAG, LISTED)
+        SELECT ?1, LIST, 1 FROM WORDLIST_WORDS WHERE WORD = ?1",
+    )?
+    .execute([word])?;
+    Ok(())
+}
+
+/// Remember the sentence a word was looked up in.
+pub fn insert_context_record(conn: &Connection, word: &str, sentence: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO CONTEXT (WORD, SENTENCE, DATE) VALUES (?1, ?2, ?3)",
+        (word, sentence, Utc::now().timestamp()),
+    )?;
+    Ok(())
+}
+
+/// The start of a local day, as a timestamp.
+pub(crate) fn day_start(day: NaiveDate) -> Option<i64> {
+    day.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|x| x.timestamp())
+}
+
+/// The days from `since` to `until`, both included, as timestamps from the
+/// start of `since` to the start of the day after `until`.
+pub fn day_range(since: Option<NaiveDate>, until: Option<NaiveDate>) -> (Option<i64>, Option<i64>) {
+    let until = until
+        .and_then(|x| x.checked_add_days(Days::new(1)))
+        .and_then(day_start);
+    (since.and_then(day_start), until)
+}
+
+/// Which records are listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListFilter {
+    /// Only words with this tag, a difficulty level or a list of your own.
+    pub tag: Option<String>,
+    /// Only words first looked up on or after this day.
+    pub since: Option<NaiveDate>,
+    /// Only words first looked up on or before this day.
+    pub until: Option<NaiveDate>,
+    /// At most this many words.
+    pub limit: Option<usize>,
+    /// Skip this many words first.
+    pub offset: usize,
+}
+
+impl ListFilter {
+    /// The parameters `?1` to `?5` of the list queries: the tag, the range of
+    /// dates, the limit and the offset.
+    fn params(&self) -> (Option<&str>, Option<i64>, Option<i64>, i64, i64) {
+        let (since, until) = day_range(self.since, self.until);
+        let limit = self.limit.map_or(-1, |x| x.try_into().unwrap_or(i64::MAX));
+        let offset = self.offset.try_into().unwrap_or(i64::MAX);
+        (self.tag.as_deref(), since, until, limit, offset)
+    }
+}
+
+/// List the words looked up in context together with their sentences,
+/// most recent first unless sorted alphabetically. The dates of the filter
+/// are those of the sentences.
+pub fn list_context_records(
+    conn: &Connection,
+    filter: &ListFilter,
     sort_alphabetically: bool,
-    format_as_table: bool,
-    max_column: usize,
 ) -> Result<()> {
-    let path = ensure_cache_directory()?;
+    for (word, sentence) in context_records(conn, filter, sort_alphabetically)? {
+        println!("{}: {}", word, sentence);
+    }
+    Ok(())
+}
 
-    let mut stmt = "SELECT WORD, DATE FROM HISTORY".to_string();
+fn context_records(
+    conn: &Connection,
+    filter: &ListFilter,
+    sort_alphabetically: bool,
+) -> Result<Vec<(String, String)>> {
+    let order = if sort_alphabetically {
+        "WORD, DATE DESC"
+    } else {
+        "DATE DESC"
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT WORD, SENTENCE FROM CONTEXT
+        WHERE (?1 IS NULL OR WORD IN (SELECT WORD FROM TAGS WHERE TAG = ?1))
+        AND (?2 IS NULL OR DATE >= ?2) AND (?3 IS NULL OR DATE < ?3)
+        ORDER BY {} LIMIT ?4 OFFSET ?5",
+        order
+    ))?;
+    let records = stmt
+        .query_map(filter.params(), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(records)
+}
 
-    if let Some(level) = difficulty_level {
-        if ALLOWED_DIFFICULTY_LEVELS.contains(&level.as_str()) {
-            stmt.push_str(format!(" WHERE {} = 1", level).as_str())
+/// Tag words, e.g. to keep a list of words met in a book. Returns how many
+/// words newly got the tag.
+pub fn tag_words(conn: &Connection, tag: &str, words: &[String]) -> Result<usize> {
+    let mut added = 0;
+    for word in words {
+        added += add_tag(conn, word, tag)? as usize;
+        sync::log_change(conn, &Change::tag(word, tag, false))?;
+    }
+    Ok(added)
+}
+
+/// Remove a tag from words. Returns how many words had it.
+pub fn untag_words(conn: &Connection, tag: &str, words: &[String]) -> Result<usize> {
+    let mut stmt = conn.prepare("DELETE FROM TAGS WHERE WORD = ?1 AND TAG = ?2")?;
+    let mut removed = 0;
+    for word in words {
+        removed += stmt.execute((word, tag))?;
+        sync::log_change(conn, &Change::tag(word, tag, true))?;
+    }
+    Ok(removed)
+}
+
+/// Keep a note on a word, e.g. a mnemonic, replacing any earlier one.
+/// An empty note removes it.
+pub fn set_note(conn: &Connection, word: &str, note: &str) -> Result<()> {
+    let date = Utc::now().timestamp();
+    conn.execute(
+        "INSERT OR REPLACE INTO NOTES (WORD, NOTE, DATE) VALUES (?1, ?2, ?3)",
+        (word, note, date),
+    )?;
+    sync::log_change(
+        conn,
+        &Change::Note {
+            word: word.to_owned(),
+            note: note.to_owned(),
+            date,
+        },
+    )
+}
+
+/// The note on a word, if any.
+pub fn note(conn: &Connection, word: &str) -> Result<Option<String>> {
+    let note = conn
+        .prepare_cached("SELECT NOTE FROM NOTES WHERE WORD = ?1 AND NOTE != ''")?
+        .query_row([word], |row| row.get(0))
+        .optional()?;
+    Ok(note)
+}
+
+/// The notes of words, shown when they are looked up again.
+#[derive(Clone)]
+pub struct Notes(pub Database);
+
+impl Notes {
+    pub fn get(&self, word: &str) -> Result<Option<String>> {
+        note(&self.0.lock(), word)
+    }
+}
+
+/// Forget a word as of `date`: its look-ups, tags, sentences, review and
+/// note. A word looked up again since is kept. Returns whether it was removed
+/// from the history.
+///
+/// The date is kept, so that look-ups up to it merged later from other
+/// machines are dropped.
+pub(crate) fn remove_word(conn: &Connection, word: &str, date: i64) -> Result<bool> {
+    conn.execute(
+        "INSERT INTO REMOVED_WORDS (WORD, DATE) VALUES (?1, ?2)
+        ON CONFLICT (WORD) DO UPDATE SET DATE = MAX(DATE, excluded.DATE)",
+        (word, date),
+    )?;
+    let last_seen: Option<i64> = conn
+        .query_row(
+            "SELECT LAST_DATE FROM HISTORY WHERE WORD = ?1",
+            [word],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if last_seen.is_some_and(|x| x > date) {
+        return Ok(false);
+    }
+    for table in [
+        "EVENTS",
+        "TAGS",
+        "CONTEXT",
+        "REVIEW",
+        "NOTES",
+        "MACHINE_LOOKUPS",
+        "HISTORY",
+    ] {
+        conn.execute(&format!("DELETE FROM {} WHERE WORD = ?1", table), [word])?;
+    }
+    // Tag changes older than the removal must not tag the word again.
+    conn.execute("UPDATE SYNC_TAGS SET REMOVED = 1 WHERE WORD = ?1", [word])?;
+    Ok(last_seen.is_some())
+}
+
+/// Remove words from the history, e.g. typos or accidental look-ups, with
+/// everything kept about them. Returns how many were in the history.
+pub fn remove_words(conn: &Connection, words: &[String]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let date = Utc::now().timestamp();
+    let mut removed = 0;
+    for word in words {
+        if remove_word(&tx, word, date)? {
+            removed += 1;
         }
+        sync::log_change(
+            &tx,
+            &Change::Remove {
+                word: word.clone(),
+                date,
+            },
+        )?;
     }
+    tx.commit()?;
+    Ok(removed)
+}
 
-    let conn = Connection::open(path)?;
+/// A looked-up word matching a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub word: String,
+    pub lookups: u32,
+    pub last_seen: i64,
+    pub definition: Option<String>,
+    pub note: Option<String>,
+}
 
-    let mut stmt = conn.prepare(&stmt)?;
-    let word_iter = stmt.query_map([], |row| row.get(0) as rusqlite::Result<String>)?;
+/// Turn a search pattern into one for `LIKE ... ESCAPE '\'`: `*` and `?`
+/// are wildcards, and a pattern without them matches anywhere.
+fn like_pattern(pattern: &str) -> String {
+    let mut like = String::new();
+    for c in pattern.chars() {
+        match c {
+            '*' => like.push('%'),
+            '?' => like.push('_'),
+            '%' | '_' | '\\' => {
+                like.push('\\');
+                like.push(c);
+            }
+            c => like.push(c),
+        }
+    }
+    if pattern.contains(['*', '?']) {
+        like
+    } else {
+        format!("%{}%", like)
+    }
+}
 
-    let mut words: Vec<String> = word_iter.filter_map(|x| x.ok()).collect();
+/// Search the words, definitions and notes of the history, ignoring case.
+/// Words matching by themselves come first, then the most looked up.
+pub fn search(conn: &Connection, pattern: &str) -> Result<Vec<SearchHit>> {
+    let mut stmt = conn.prepare(
+        "SELECT H.WORD, H.LOOKUPS, H.LAST_DATE, H.DEFINITION, N.NOTE
+        FROM HISTORY H LEFT JOIN NOTES N ON N.WORD = H.WORD AND N.NOTE != ''
+        WHERE H.WORD LIKE ?1 ESCAPE '\\' OR H.DEFINITION LIKE ?1 ESCAPE '\\'
+        OR N.NOTE LIKE ?1 ESCAPE '\\'
+        ORDER BY H.WORD LIKE ?1 ESCAPE '\\' DESC, H.LOOKUPS DESC, H.WORD",
+    )?;
+    let hits = stmt
+        .query_map([like_pattern(pattern)], |row| {
+            Ok(SearchHit {
+                word: row.get(0)?,
+                lookups: row.get(1)?,
+                last_seen: row.get(2)?,
+                definition: row.get(3)?,
+                note: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(hits)
+}
 
-    if sort_alphabetically {
-        words.sort();
+/// The first line of `text`, cut to `width` characters.
+fn summary(text: &str, width: usize) -> String {
+    let line = text.trim().lines().next().unwrap_or_default();
+    if line.chars().count() > width {
+        let cut: String = line.chars().take(width.saturating_sub(1)).collect();
+        format!("{}…", cut)
+    } else {
+        line.to_owned()
     }
+}
 
+/// Print the hits of a search as a table.
+pub fn print_search(hits: &[SearchHit]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Word", "Lookups", "Last", "Definition", "Note"]
+            .into_iter()
+            .map(|x| Cell::new(x).with_style(Attr::Bold))
+            .collect(),
+    ));
+    for hit in hits {
+        table.add_row(Row::new(vec![
+            Cell::new(&hit.word),
+            Cell::new(&hit.lookups.to_string()),
+            Cell::new(&format_date(hit.last_seen)),
+            Cell::new(&summary(hit.definition.as_deref().unwrap_or_default(), 40)),
+            Cell::new(&summary(hit.note.as_deref().unwrap_or_default(), 30)),
+        ]));
+    }
+    table.printstd();
+}
+
+/// A timestamp as a local day, e.g. `2024-09-01`.
+fn format_date(date: i64) -> String {
+    DateTime::from_timestamp(date, 0)
+        .map(|x| x.with_timezone(&Local).format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// List sorted or not history of a tag or all words looked up.
+///
+/// Words of a tag are listed even if they were never looked up, unless
+/// filtered by date.
+pub fn list_history_records(
+    conn: &Connection,
+    filter: &ListFilter,
+    sort_alphabetically: bool,
+    format_as_table: bool,
+    max_column: usize,
+) -> Result<()> {
+    let words = history_words(conn, filter, sort_alphabetically)?;
     if format_as_table {
         let mut table = Table::new();
         words.chunks(max_column).for_each(|x| {
@@ -113,33 +460,374 @@ pub fn list_history_records(
     Ok(())
 }
 
-/// Count history of a word difficulty level or all levels.
-pub fn count_history_records() -> Result<()> {
-    let path = ensure_cache_directory()?;
+fn history_words(
+    conn: &Connection,
+    filter: &ListFilter,
+    sort_alphabetically: bool,
+) -> Result<Vec<String>> {
+    let (from, table) = match filter.tag {
+        Some(_) => (
+            "TAGS T LEFT JOIN HISTORY H ON H.WORD = T.WORD WHERE T.TAG = ?1",
+            "T",
+        ),
+        None => ("HISTORY H WHERE ?1 IS NULL", "H"),
+    };
+    let order = if sort_alphabetically { "WORD" } else { "ROWID" };
+    let words: Vec<String> = conn
+        .prepare(&format!(
+            "SELECT {0}.WORD FROM {1}
+            AND (?2 IS NULL OR H.DATE >= ?2) AND (?3 IS NULL OR H.DATE < ?3)
+            ORDER BY {0}.{2} LIMIT ?4 OFFSET ?5",
+            table, from, order
+        ))?
+        .query_map(filter.params(), |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(words)
+}
 
-    let conn = Connection::open(path)?;
+/// List how often and when words were looked up, the most looked up first
+/// unless sorted alphabetically.
+pub fn list_history_details(
+    conn: &Connection,
+    filter: &ListFilter,
+    sort_alphabetically: bool,
+) -> Result<()> {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Word", "Lookups", "First", "Last", "Dictionary"]
+            .into_iter()
+            .map(|x| Cell::new(x).with_style(Attr::Bold))
+            .collect(),
+    ));
+    for x in history_details(conn, filter, sort_alphabetically)? {
+        table.add_row(Row::new(vec![
+            Cell::new(&x.word),
+            Cell::new(&x.lookups.to_string()),
+            Cell::new(&format_date(x.first_seen)),
+            Cell::new(&format_date(x.last_seen)),
+            Cell::new(x.source.as_deref().unwrap_or("-")),
+        ]));
+    }
+    table.printstd();
 
-    let header: Row = ALLOWED_DIFFICULTY_LEVELS
-        .into_iter()
-        .map(|x| Cell::new(x).with_style(Attr::Bold))
-        .collect();
+    Ok(())
+}
 
-    let mut table: Table = Table::new();
-    table.add_row(header);
-
-    let body: Row = ALLOWED_DIFFICULTY_LEVELS
-        .into_iter()
-        .map(|x| {
-            let stmt = format!("SELECT COUNT(*) FROM HISTORY WHERE {} = 1", x);
-            let mut stmt = conn.prepare(&stmt).unwrap();
-            let res = stmt.query_row([], |row| row.get(0) as rusqlite::Result<i32>);
-            Cell::new(&res.unwrap().to_string())
+/// How often and when a word was looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Details {
+    word: String,
+    lookups: u32,
+    first_seen: i64,
+    last_seen: i64,
+    source: Option<String>,
+}
+
+fn history_details(
+    conn: &Connection,
+    filter: &ListFilter,
+    sort_alphabetically: bool,
+) -> Result<Vec<Details>> {
+    let order = if sort_alphabetically {
+        "WORD"
+    } else {
+        "LOOKUPS DESC, LAST_DATE DESC"
+    };
+    let stmt = format!(
+        "SELECT WORD, LOOKUPS, DATE, LAST_DATE, SOURCE FROM HISTORY
+        WHERE (?1 IS NULL OR WORD IN (SELECT WORD FROM TAGS WHERE TAG = ?1))
+        AND (?2 IS NULL OR DATE >= ?2) AND (?3 IS NULL OR DATE < ?3)
+        ORDER BY {} LIMIT ?4 OFFSET ?5",
+        order
+    );
+
+    let details = conn
+        .prepare(&stmt)?
+        .query_map(filter.params(), |row| {
+            Ok(Details {
+                word: row.get(0)?,
+                lookups: row.get(1)?,
+                first_seen: row.get(2)?,
+                last_seen: row.get(3)?,
+                source: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(details)
+}
+
+/// How many words have each tag: the given ones, or the difficulty levels
+/// followed by every other tag in use.
+pub fn count_tags(conn: &Connection, tags: &[String]) -> Result<Vec<(String, usize)>> {
+    let mut tags: Vec<String> = tags.to_vec();
+    if tags.is_empty() {
+        tags.extend(ALLOWED_DIFFICULTY_LEVELS.iter().map(|x| x.to_string()));
+        let mut stmt = conn.prepare("SELECT DISTINCT TAG FROM TAGS ORDER BY TAG")?;
+        for tag in stmt.query_map([], |row| row.get::<_, String>(0))? {
+            let tag = tag?;
+            if !tags.iter().any(|x| x.eq_ignore_ascii_case(&tag)) {
+                tags.push(tag);
+            }
+        }
+    }
+
+    let mut stmt = conn.prepare("SELECT COUNT(*) FROM TAGS WHERE TAG = ?1")?;
+    tags.into_iter()
+        .map(|tag| {
+            let count = stmt.query_row([&tag], |row| row.get(0))?;
+            Ok((tag, count))
         })
-        .collect();
+        .collect()
+}
+
+/// Count history of the given tags or all of them.
+///
+/// The output will be like:
+/// txt
+/// +------+------+-------+-------+------+-----+-----+------------+
+/// | CET4 | CET6 | TOEFL | IELTS | GMAT | GRE | SAT | Book: Dune |
+/// +------+------+-------+-------+------+-----+-----+------------+
+/// | 220  | 305  | 207   | 203   | 142  | 242 | 126 | 37         |
+/// +------+------+-------+-------+------+-----+-----+------------+
+///
+pub fn count_history_records(conn: &Connection, tags: &[String]) -> Result<()> {
+    let counts = count_tags(conn, tags)?;
 
-    table.add_row(body);
+    let mut table: Table = Table::new();
+    table.add_row(
+        counts
+            .iter()
+            .map(|(tag, _)| Cell::new(tag).with_style(Attr::Bold))
+            .collect(),
+    );
+    table.add_row(
+        counts
+            .iter()
+            .map(|(_, count)| Cell::new(&count.to_string()))
+            .collect(),
+    );
 
     table.printstd();
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dict::DictType;
+
+    #[test]
+    fn count_look_ups() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        let item = LookUpResultItem::new("rust", "n. 铁锈".to_owned())
+            .with_source("Youdao", DictType::OnlineDict);
+        record_look_up(&conn, &item, 100).unwrap();
+        let item = LookUpResultItem::new("rust", "n. 铁锈；v. 生锈".to_owned());
+        record_look_up(&conn, &item, 200).unwrap();
+
+        let row: (i64, i64, i64, Option<String>, String) = conn
+            .query_row(
+                "SELECT LOOKUPS, DATE, LAST_DATE, SOURCE, DEFINITION FROM HISTORY WHERE WORD = 'rust'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(row, (2, 100, 200, None, "n. 铁锈；v. 生锈".to_owned()));
+        let events: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM EVENTS WHERE WORD = 'rust'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(events, 2);
+    }
+
+    #[test]
+    fn tag_words_with_quotes() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        let mut item = LookUpResultItem::new("rock 'n' roll", String::new());
+        item.difficulty_levels = vec!["CET6".to_owned(), "HSK".to_owned()];
+        insert_history_record(&conn, &item).unwrap();
+        let words = ["sandworm".to_owned(), "rock 'n' roll".to_owned()];
+        assert_eq!(tag_words(&conn, "Book: Dune", &words).unwrap(), 2);
+        assert_eq!(tag_words(&conn, "book: dune", &words[..1]).unwrap(), 0);
+
+        let counts = count_tags(&conn, &[]).unwrap();
+        assert_eq!(counts[1], ("CET6".to_owned(), 1));
+        assert_eq!(counts[7], ("Book: Dune".to_owned(), 2));
+        assert_eq!(counts.len(), 8);
+
+        assert_eq!(untag_words(&conn, "BOOK: DUNE", &words[..1]).unwrap(), 1);
+        let counts = count_tags(&conn, &["book: dune".to_owned()]).unwrap();
+        assert_eq!(counts, [("book: dune".to_owned(), 1)]);
+    }
+
+    #[test]
+    fn search_note_and_remove() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        for (word, definition) in [
+            ("abate", "v. 减轻"),
+            ("ubiquitous", "adj. 无处不在的"),
+            ("teh", ""),
+        ] {
+            insert_history_record(&conn, &LookUpResultItem::new(word, definition.to_owned()))
+                .unwrap();
+        }
+        insert_context_record(&conn, "teh", "Fix teh typo.").unwrap();
+        tag_words(&conn, "typos", &["teh".to_owned()]).unwrap();
+        set_note(&conn, "Ubiquitous", "ubi = everywhere").unwrap();
+        assert_eq!(
+            note(&conn, "ubiquitous").unwrap().unwrap(),
+            "ubi = everywhere"
+        );
+
+        let words = |pattern| -> Vec<String> {
+            search(&conn, pattern)
+                .unwrap()
+                .into_iter()
+                .map(|x| x.word)
+                .collect()
+        };
+        assert_eq!(words("AB"), ["abate"]);
+        assert_eq!(words("everywhere"), ["ubiquitous"]);
+        assert_eq!(words("?b*"), ["abate", "ubiquitous"]);
+        assert_eq!(words("减"), ["abate"]);
+        assert!(words("100%").is_empty());
+        assert_eq!(like_pattern("100%_*"), "100\\%\\_%");
+
+        let removed = remove_words(&conn, &["teh".to_owned(), "the".to_owned()]).unwrap();
+        assert_eq!(removed, 1);
+        for table in ["HISTORY", "EVENTS", "TAGS", "CONTEXT"] {
+            let count: i64 = conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM {} WHERE WORD = 'teh'", table),
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(count, 0, "{}", table);
+        }
+
+        set_note(&conn, "ubiquitous", "").unwrap();
+        assert_eq!(note(&conn, "ubiquitous").unwrap(), None);
+        assert!(words("everywhere").is_empty());
+    }
+
+    #[test]
+    fn filter_lists() {
+        let filter = ListFilter {
+            since: NaiveDate::from_ymd_opt(2024, 9, 1),
+            until: NaiveDate::from_ymd_opt(2024, 9, 30),
+            limit: Some(10),
+            ..Default::default()
+        };
+        let (tag, since, until, limit, offset) = filter.params();
+        assert_eq!(tag, None);
+        // Thirty days, give or take a change of daylight saving time.
+        assert!((until.unwrap() - since.unwrap() - 30 * 86400).abs() <= 3600);
+        assert_eq!((limit, offset), (10, 0));
+        assert_eq!(ListFilter::default().params().3, -1);
+    }
+
+    #[test]
+    fn list_by_date_limit_and_offset() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        let day = |d: u32| {
+            day_start(NaiveDate::from_ymd_opt(2024, 9, 1).unwrap() + Days::new(d as u64)).unwrap()
+        };
+        // From the first moment of September to the last of the 30th.
+        let looked_up = [
+            ("abate", day(0) - 1),
+            ("bland", day(0)),
+            ("crisp", day(14)),
+            ("bland", day(15)),
+            ("dwell", day(30) - 1),
+            ("eerie", day(30)),
+        ];
+        for (word, date) in looked_up {
+            record_look_up(&conn, &LookUpResultItem::new(word, String::new()), date).unwrap();
+            conn.execute(
+                "INSERT OR REPLACE INTO CONTEXT (WORD, SENTENCE, DATE) VALUES (?1, ?2, ?3)",
+                (word, format!("A {} sentence.", word), date),
+            )
+            .unwrap();
+        }
+        let words = ["crisp", "dwell", "sandworm"].map(str::to_owned);
+        tag_words(&conn, "mine", &words).unwrap();
+
+        let filter = |tag: Option<&str>, dated: bool, limit, offset| ListFilter {
+            tag: tag.map(str::to_owned),
+            since: dated.then(|| NaiveDate::from_ymd_opt(2024, 9, 1).unwrap()),
+            until: dated.then(|| NaiveDate::from_ymd_opt(2024, 9, 30).unwrap()),
+            limit,
+            offset,
+        };
+        let words = |filter: ListFilter, sort| history_words(&conn, &filter, sort).unwrap();
+        assert_eq!(
+            words(filter(None, true, None, 0), false),
+            ["bland", "crisp", "dwell"]
+        );
+        assert_eq!(words(filter(None, false, None, 0), true).len(), 5);
+        assert_eq!(
+            words(filter(None, true, Some(2), 1), true),
+            ["crisp", "dwell"]
+        );
+        assert_eq!(words(filter(None, true, Some(2), 2), true), ["dwell"]);
+        assert!(words(filter(None, true, Some(2), 3), true).is_empty());
+        assert!(words(filter(None, true, Some(0), 0), true).is_empty());
+        // Words of a tag never looked up are left out only when filtering by date.
+        assert_eq!(
+            words(filter(Some("mine"), false, None, 0), true),
+            ["crisp", "dwell", "sandworm"]
+        );
+        assert_eq!(
+            words(filter(Some("MINE"), true, Some(1), 1), true),
+            ["dwell"]
+        );
+
+        let details = |filter: ListFilter| -> Vec<(String, u32, i64)> {
+            history_details(&conn, &filter, false)
+                .unwrap()
+                .into_iter()
+                .map(|x| (x.word, x.lookups, x.first_seen))
+                .collect()
+        };
+        assert_eq!(
+            details(filter(None, true, None, 0)),
+            [
+                ("bland".to_owned(), 2, day(0)),
+                ("dwell".to_owned(), 1, day(30) - 1),
+                ("crisp".to_owned(), 1, day(14))
+            ]
+        );
+        assert_eq!(details(filter(None, true, Some(1), 2))[0].0, "crisp");
+        assert!(details(filter(Some("mine"), true, None, 2)).is_empty());
+
+        let sentences = |filter: ListFilter, sort| -> Vec<String> {
+            context_records(&conn, &filter, sort)
+                .unwrap()
+                .into_iter()
+                .map(|x| x.0)
+                .collect()
+        };
+        // The dates are those of the sentences, the latest of bland's kept.
+        assert_eq!(
+            sentences(filter(None, true, None, 0), false),
+            ["dwell", "bland", "crisp"]
+        );
+        assert_eq!(
+            sentences(filter(None, true, Some(2), 1), true),
+            ["crisp", "dwell"]
+        );
+        assert_eq!(
+            sentences(filter(Some("mine"), false, Some(5), 0), true),
+            ["crisp", "dwell"]
+        );
+    }
+}
@@ -1,6 +1,17 @@
+pub mod anki;
+pub mod cache;
 pub mod cli;
+pub mod config;
+pub mod db;
 pub mod dict;
 pub mod dicts;
+pub mod frontend;
 pub mod history;
+pub mod net;
 #[cfg(feature = "pronunciation")]
 pub mod pronunciation;
+pub mod review;
+pub mod stats;
+pub mod sync;
+pub mod transfer;
+pub mod wordlist;
@@ -3,11 +3,22 @@
 use anyhow::Result;
 use clap::CommandFactory;
 use dioxionary::{
-    cli::{Action, Cli, Parser},
+    anki::{self, AnkiFormat, ExportFilter},
+    cache::{self, LookUpCache},
+    cli::{Action, CacheAction, Cli, ExportAction, HistoryAction, Parser, WordlistAction},
+    config::Config,
+    db::Database,
     dicts::{default_llm_dict_config_path, default_local_dict_path, DictManager, DictOptions},
-    history,
+    frontend,
+    history::{self, HistoryHook, ListFilter, Notes},
+    net,
+    review::{self, ReviewStore},
+    stats, sync,
+    transfer::{self, HistoryFormat},
+    wordlist::{self, Wordlists},
 };
 use std::env;
+use std::io::Write;
 
 fn main() -> Result<()> {
     let cli = match Cli::try_parse() {
@@ -29,13 +40,29 @@ fn main() -> Result<()> {
         }
     };
 
+    let config = Config::load_default()?;
+    net::init(&config.network)?;
+    let db = Database::open_default()?;
+    if let Some(dir) = &config.sync.dir {
+        let syncing = matches!(
+            cli.action,
+            Action::History(HistoryAction::Sync { .. }) | Action::Completion(_)
+        );
+        if config.sync.on_start && !syncing {
+            if let Err(e) = sync::sync(&db.lock(), dir) {
+                eprintln!("Failed to sync the history: {:#}", e);
+            }
+        }
+    }
+
     match cli.action {
         Action::LookUp(look_up) => {
             let options = DictOptions::default()
                 .prioritize_offline(look_up.local_first)
                 .prioritize_online(look_up.use_online)
                 .use_llm_dicts(look_up.use_llm)
-                .require_exact_match(look_up.exact_search);
+                .require_exact_match(look_up.exact_search)
+                .refresh_cache(look_up.refresh);
             #[cfg(feature = "pronunciation")]
             let options = options.read_aloud(look_up.read_aloud);
             let local_dicts = if let Some(path) = look_up.local_dicts {
@@ -43,12 +70,153 @@ fn main() -> Result<()> {
             } else {
                 default_local_dict_path()
             };
-            let manager =
-                DictManager::new(local_dicts, default_llm_dict_config_path(), options).unwrap();
+            let manager = DictManager::new(local_dicts, default_llm_dict_config_path(), options)
+                .unwrap()
+                .with_config(&config)
+                .with_hook(HistoryHook(db.clone()))
+                .with_notes(Notes(db.clone()))
+                .with_wordlists(Wordlists(db.clone()));
+            let manager = if config.cache.enabled {
+                manager.with_cache(LookUpCache::new(db, config.cache.ttl()))
+            } else {
+                manager
+            };
+            #[cfg(feature = "pronunciation")]
+            let manager = manager.with_hook(dioxionary::pronunciation::PronunciationHook);
             if let Some(words) = look_up.word {
-                words.iter().for_each(|word| manager.query(word));
+                words.iter().for_each(|word| {
+                    frontend::query(
+                        &manager,
+                        &config.display,
+                        word,
+                        look_up.context.as_deref(),
+                        look_up.template.as_deref(),
+                    )
+                });
+            } else {
+                frontend::repl(&manager, &config.display);
+            }
+        }
+        Action::Wordlist(action) => {
+            let conn = db.lock();
+            match action {
+                WordlistAction::Import { format, name, file } => {
+                    let name = name.trim();
+                    if name.is_empty() {
+                        anyhow::bail!("The name must not be empty");
+                    }
+                    let count = wordlist::import(&conn, name, &file, format)?;
+                    println!("Imported {} words into {}", count, name);
+                    if let Some(coverage) = wordlist::coverage(&conn, Some(name))?.first() {
+                        wordlist::print_coverage(coverage);
+                    }
+                }
+                WordlistAction::Ls => wordlist::print_wordlists(&wordlist::coverage(&conn, None)?),
+                WordlistAction::Rm { name } => {
+                    if !wordlist::remove(&conn, &name)? {
+                        anyhow::bail!("No word list named {}", name);
+                    }
+                }
+                WordlistAction::Show { missing, name } => {
+                    let Some(coverage) = wordlist::coverage(&conn, Some(&name))?.pop() else {
+                        anyhow::bail!("No word list named {}", name);
+                    };
+                    for (word, looked_up) in wordlist::words(&conn, &name)? {
+                        if !missing || !looked_up {
+                            println!("{}", word);
+                        }
+                    }
+                    wordlist::print_coverage(&coverage);
+                }
+            }
+        }
+        Action::Export(ExportAction::Anki(args)) => {
+            let filter = ExportFilter {
+                tag: args.tag,
+                since: args.since,
+                until: args.until,
+                min_lookups: args.min_lookups,
+            };
+            let format = args
+                .format
+                .unwrap_or_else(|| AnkiFormat::from_path(&args.output));
+            let count = anki::export(
+                &db.lock(),
+                &filter,
+                &args.output,
+                format,
+                &args.deck,
+                args.audio,
+            )?;
+            println!("Exported {} words to {}", count, args.output.display());
+        }
+        Action::History(HistoryAction::Export { format, output }) => {
+            let records = transfer::records(&db.lock())?;
+            let format = format.unwrap_or_else(|| match &output {
+                Some(path) => HistoryFormat::from_path(path),
+                None => HistoryFormat::Json,
+            });
+            match output {
+                Some(path) => {
+                    let mut out = std::io::BufWriter::new(std::fs::File::create(&path)?);
+                    transfer::write(&records, format, &mut out)?;
+                    out.flush()?;
+                    eprintln!("Exported {} words to {}", records.len(), path.display());
+                }
+                None => transfer::write(&records, format, &mut std::io::stdout().lock())?,
+            }
+        }
+        Action::History(HistoryAction::Import {
+            format,
+            dry_run,
+            file,
+        }) => {
+            let report = transfer::import(&db.lock(), &file, format, dry_run)?;
+            transfer::print_report(&report, dry_run);
+        }
+        Action::History(HistoryAction::Rm { words }) => {
+            let removed = history::remove_words(&db.lock(), &words)?;
+            println!("Removed {} words from the history", removed);
+        }
+        Action::History(HistoryAction::Search { pattern }) => {
+            let hits = history::search(&db.lock(), &pattern)?;
+            if hits.is_empty() {
+                anyhow::bail!("No word matches {}", pattern);
+            }
+            history::print_search(&hits);
+        }
+        Action::History(HistoryAction::Note { remove, word, text }) => {
+            let conn = db.lock();
+            let text = text.join(" ");
+            if remove {
+                history::set_note(&conn, &word, "")?;
+            } else if !text.trim().is_empty() {
+                history::set_note(&conn, &word, text.trim())?;
             } else {
-                manager.repl();
+                match history::note(&conn, &word)? {
+                    Some(note) => println!("{}", note),
+                    None => anyhow::bail!("No note on {}", word),
+                }
+            }
+        }
+        Action::History(HistoryAction::Sync { dir }) => {
+            let Some(dir) = dir.or(config.sync.dir) else {
+                anyhow::bail!("No sync directory given or set in the configuration");
+            };
+            sync::print_report(&sync::sync(&db.lock(), &dir)?);
+        }
+        Action::Review(args) => {
+            let store = ReviewStore::new(db)?;
+            if args.summary {
+                review::print_summary(&store.summary(chrono::Utc::now())?);
+            } else {
+                let manager = DictManager::new(
+                    default_local_dict_path(),
+                    default_llm_dict_config_path(),
+                    DictOptions::default(),
+                )?
+                .with_config(&config);
+                frontend::review(&store, &manager, args.limit)?;
             }
         }
         Action::Dicts => {
@@ -57,20 +225,62 @@ fn main() -> Result<()> {
                 default_llm_dict_config_path(),
                 DictOptions::default(),
             )
-            .unwrap();
-            manager.list_dicts();
+            .unwrap()
+            .with_config(&config);
+            frontend::list_dicts(&manager);
         }
-        Action::Count => {
-            history::count_history_records().unwrap();
+        Action::Cache(action) => {
+            let cache = LookUpCache::new(db, config.cache.ttl());
+            match action {
+                CacheAction::Stats => cache::print_stats(&cache)?,
+                CacheAction::Clear { dict } => {
+                    let removed = cache.clear(dict.as_deref())?;
+                    println!("Removed {} cached entries", removed);
+                }
+            }
+        }
+        Action::Count(count) => {
+            history::count_history_records(&db.lock(), &count.tags)?;
+        }
+        Action::Stats(args) => {
+            let today = chrono::Local::now().date_naive();
+            let stats = stats::stats(&db.lock(), today, args.days, args.top)?;
+            stats::print_stats(&stats, args.format)?;
+        }
+        Action::Tag(args) => {
+            let tag = args.tag.trim();
+            if tag.is_empty() {
+                anyhow::bail!("The tag must not be empty");
+            }
+            if args.remove {
+                let removed = history::untag_words(&db.lock(), tag, &args.words)?;
+                println!("Removed {} words from {}", removed, tag);
+            } else {
+                let added = history::tag_words(&db.lock(), tag, &args.words)?;
+                println!("Added {} words to {}", added, tag);
+            }
         }
         Action::List(list) => {
-            history::list_history_records(
-                list.difficulty_level,
-                list.sort_alphabetically,
-                list.format_as_table,
-                list.max_column,
-            )
-            .unwrap();
+            let filter = ListFilter {
+                tag: list.tag,
+                since: list.since,
+                until: list.until,
+                limit: list.limit,
+                offset: list.offset,
+            };
+            if list.context {
+                history::list_context_records(&db.lock(), &filter, list.sort_alphabetically)?;
+            } else if list.details {
+                history::list_history_details(&db.lock(), &filter, list.sort_alphabetically)?;
+            } else {
+                history::list_history_records(
+                    &db.lock(),
+                    &filter,
+                    list.sort_alphabetically,
+                    list.format_as_table,
+                    list.max_column,
+                )?;
+            }
         }
         Action::Completion(completion) => {
             let bin_name = env::args().next().expect("impossible");
@@ -0,0 +1,215 @@
+//! The HTTP client shared by all network backends, configured by `[network]` in `config.toml`.
+//!
+//! Proxies from `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY` are honored unless
+//! `proxy` is set in the configuration.
+use anyhow::{anyhow, Context, Result};
+use reqwest::{blocking::Client, Certificate, Proxy};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::dict::DictError;
+
+/// Browsers get the same pages as users; some dictionaries block other agents.
+pub static DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:126.0) Gecko/20100101 Firefox/126.0";
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+static RETRY_POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+
+/// The `[network]` section of `config.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` proxy for all requests.
+    pub proxy: Option<String>,
+    /// Seconds to wait for a connection.
+    pub connect_timeout: u64,
+    /// Seconds to wait for a response, or for the next chunk of a streamed one.
+    pub read_timeout: u64,
+    /// How many times a request failing with 429, 5xx or a network error is retried.
+    pub retries: u32,
+    pub user_agent: Option<String>,
+    /// Extra PEM certificates to trust, e.g. of a corporate proxy.
+    pub ca_certs: Vec<PathBuf>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            connect_timeout: 10,
+            read_timeout: 30,
+            retries: 2,
+            user_agent: None,
+            ca_certs: Vec::new(),
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn build_client(&self) -> Result<Client> {
+        // The blocking client applies `timeout` to waiting for the response and
+        // to every read of its body, so streamed answers may take longer in total.
+        let mut builder = Client::builder()
+            .user_agent(self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT))
+            .connect_timeout(Duration::from_secs(self.connect_timeout))
+            .timeout(Duration::from_secs(self.read_timeout));
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy).with_context(|| format!("Invalid proxy {}", proxy))?;
+            builder = builder.proxy(proxy);
+        }
+        for path in &self.ca_certs {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed to read certificate {}", path.display()))?;
+            let cert = Certificate::from_pem(&pem)
+                .with_context(|| format!("Invalid certificate {}", path.display()))?;
+            builder = builder.add_root_certificate(cert);
+        }
+        builder.build().context("Failed to build the HTTP client")
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            retries: self.retries,
+            ..Default::default()
+        }
+    }
+}
+
+/// Set up the shared client. Must be called before the first request.
+pub fn init(config: &NetworkConfig) -> Result<()> {
+    let client = config.build_client()?;
+    CLIENT
+        .set(client)
+        .map_err(|_| anyhow!("The HTTP client is already initialized"))?;
+    let _ = RETRY_POLICY.set(config.retry_policy());
+    Ok(())
+}
+
+/// The shared client, with the default settings if [`init`] was not called.
+pub fn client() -> Result<&'static Client, DictError> {
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let client = NetworkConfig::default()
+        .build_client()
+        .map_err(|e| DictError::Other(format!("{:#}", e)))?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+/// The configured retry policy.
+pub fn retry_policy() -> RetryPolicy {
+    RETRY_POLICY.get().copied().unwrap_or_default()
+}
+
+/// How transient failures are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every further one.
+    pub backoff: Duration,
+    /// Upper bound on how long to wait before a retry.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            backoff: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before retrying after `error`, or `None` to give up.
+    pub fn delay(&self, attempt: u32, error: &DictError) -> Option<Duration> {
+        if !error.is_transient() || attempt >= self.retries {
+            return None;
+        }
+        let delay = match error {
+            DictError::RateLimit {
+                retry_after: Some(delay),
+                ..
+            } => *delay,
+            _ => self.backoff.saturating_mul(1 << attempt.min(16)),
+        };
+        Some(delay.min(self.max_delay))
+    }
+
+    /// Run `f`, retrying failures that may go away on their own.
+    pub fn run<T>(&self, mut f: impl FnMut() -> Result<T, DictError>) -> Result<T, DictError> {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(e) => match self.delay(attempt, &e) {
+                    Some(delay) => {
+                        std::thread::sleep(delay);
+                        attempt += 1;
+                    }
+                    None => return Err(e),
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config: NetworkConfig = toml::from_str(
+            r#"
+            proxy = "socks5h://127.0.0.1:1080"
+            read_timeout = 60
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.connect_timeout, 10);
+        assert_eq!(config.read_timeout, 60);
+        assert!(config.build_client().is_ok());
+
+        let config = NetworkConfig {
+            ca_certs: vec!["/nonexistent/ca.pem".into()],
+            ..Default::default()
+        };
+        assert!(config.build_client().is_err());
+    }
+
+    #[test]
+    fn retry_delays() {
+        let policy = RetryPolicy::default();
+        let server = DictError::Server("503 Service Unavailable".to_owned());
+        assert_eq!(policy.delay(0, &server), Some(Duration::from_millis(500)));
+        assert_eq!(policy.delay(1, &server), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(2, &server), None);
+        assert_eq!(policy.delay(0, &DictError::NotFound), None);
+
+        let rate_limit = DictError::RateLimit {
+            message: "429 Too Many Requests".to_owned(),
+            retry_after: Some(Duration::from_secs(60)),
+        };
+        assert_eq!(policy.delay(0, &rate_limit), Some(policy.max_delay));
+
+        let mut calls = 0;
+        let policy = RetryPolicy {
+            backoff: Duration::ZERO,
+            ..policy
+        };
+        let result = policy.run(|| {
+            calls += 1;
+            if calls < 3 {
+                Err(server.clone())
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(3));
+    }
+}
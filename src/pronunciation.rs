@@ -1,16 +1,30 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use rodio::{Decoder, OutputStream, Sink};
 use std::io::Cursor;
 
-fn build_pronuciation_url(word: &str) -> String {
-    format!("https://dict.youdao.com/dictvoice?audio={}&type=1", word)
+use crate::dict::{online::fetch_pronunciation, LookUpResultItem};
+use crate::dicts::{DictOptions, LookUpHook};
+
+/// Reads the headword aloud when [`DictOptions::read_aloud`] is set.
+pub struct PronunciationHook;
+
+impl LookUpHook for PronunciationHook {
+    fn on_look_up(
+        &self,
+        item: &LookUpResultItem,
+        options: &DictOptions,
+        _: Option<&str>,
+    ) -> Result<()> {
+        if options.read_aloud {
+            pronounce(&item.word).with_context(|| "Failed to read aloud")?;
+        }
+        Ok(())
+    }
 }
 
 pub fn pronounce(word: &str) -> Result<()> {
     let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let url = build_pronuciation_url(word);
-    let response = reqwest::blocking::get(url)?;
-    let inner = response.bytes()?;
+    let inner = fetch_pronunciation(word)?;
     if let Ok(source) = Decoder::new(Cursor::new(inner)) {
         if let Ok(sink) = Sink::try_new(&stream_handle) {
             sink.append(source);
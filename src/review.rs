@@ -0,0 +1,321 @@
+//! Spaced-repetition review of looked-up words with the
+//! [SM-2](https://super-memory.com/english/ol/sm2.htm) algorithm.
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local, NaiveTime, Utc};
+use prettytable::{Attr, Cell, Row, Table};
+
+use crate::db::Database;
+
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+/// How well a word was remembered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grade {
+    /// Forgotten; the word is learned again from the start.
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl Grade {
+    pub const ALL: [Grade; 4] = [Grade::Again, Grade::Hard, Grade::Good, Grade::Easy];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Grade::Again => "Again",
+            Grade::Hard => "Hard",
+            Grade::Good => "Good",
+            Grade::Easy => "Easy",
+        }
+    }
+
+    /// The SM-2 response quality, from 0 to 5.
+    fn quality(&self) -> u8 {
+        match self {
+            Grade::Again => 1,
+            Grade::Hard => 3,
+            Grade::Good => 4,
+            Grade::Easy => 5,
+        }
+    }
+}
+
+/// The review state of a word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Card {
+    pub word: String,
+    /// The definition shown when the word was last looked up.
+    pub definition: Option<String>,
+    pub ease: f64,
+    /// Days between the last review and the next one.
+    pub interval: u32,
+    /// Successful reviews in a row.
+    pub repetitions: u32,
+    /// How many times the word was forgotten.
+    pub lapses: u32,
+    pub due: DateTime<Utc>,
+}
+
+impl Card {
+    pub fn new(word: impl Into<String>, due: DateTime<Utc>) -> Self {
+        Self {
+            word: word.into(),
+            definition: None,
+            ease: INITIAL_EASE,
+            interval: 0,
+            repetitions: 0,
+            lapses: 0,
+            due,
+        }
+    }
+
+    /// Schedule the next review after grading the word at `now`.
+    pub fn review(&mut self, grade: Grade, now: DateTime<Utc>) {
+        let q = grade.quality() as f64;
+        if grade == Grade::Again {
+            self.repetitions = 0;
+            self.interval = 1;
+            self.lapses += 1;
+        } else {
+            self.interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => ((self.interval as f64 * self.ease).round() as u32).max(self.interval + 1),
+            };
+            self.repetitions += 1;
+        }
+        self.ease = (self.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(MIN_EASE);
+        self.due = now + Duration::days(self.interval as i64);
+    }
+}
+
+/// Counts shown before and after a review session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReviewSummary {
+    /// Words due by the end of today, including overdue ones.
+    pub due_today: usize,
+    /// Due words never reviewed before.
+    pub new: usize,
+    pub reviewed_today: usize,
+    pub total: usize,
+}
+
+/// Review states, kept in the history database.
+pub struct ReviewStore {
+    db: Database,
+}
+
+impl ReviewStore {
+    pub fn new(db: Database) -> Result<Self> {
+        // Words looked up since the last review session, or by older versions,
+        // are due from when they were looked up.
+        db.lock().execute(
+            "INSERT OR IGNORE INTO REVIEW (WORD, EASE, DUE) SELECT WORD, ?1, DATE FROM HISTORY",
+            [INITIAL_EASE],
+        )?;
+        Ok(Self { db })
+    }
+
+    /// Remember the definition to reveal when reviewing `word`, for words
+    /// looked up before definitions were recorded.
+    pub fn set_definition(&self, word: &str, definition: &str) -> Result<()> {
+        self.db.lock().execute(
+            "UPDATE HISTORY SET DEFINITION = ?2 WHERE WORD = ?1",
+            (word, definition),
+        )?;
+        Ok(())
+    }
+
+    /// Up to `limit` words due at `now`, the longest overdue first.
+    pub fn due(&self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Card>> {
+        let conn = self.db.lock();
+        let mut stmt = conn.prepare(
+            "SELECT R.WORD, H.DEFINITION, EASE, INTERVAL, REPETITIONS, LAPSES, DUE
+            FROM REVIEW R LEFT JOIN HISTORY H ON H.WORD = R.WORD
+            WHERE DUE <= ?1 ORDER BY DUE, R.WORD LIMIT ?2",
+        )?;
+        let cards = stmt
+            .query_map((now.timestamp(), limit as i64), |row| {
+                Ok(Card {
+                    word: row.get(0)?,
+                    definition: row.get(1)?,
+                    ease: row.get(2)?,
+                    interval: row.get(3)?,
+                    repetitions: row.get(4)?,
+                    lapses: row.get(5)?,
+                    due: DateTime::from_timestamp(row.get(6)?, 0).unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(cards)
+    }
+
+    /// Record a review of `card` at `now`.
+    pub fn save(&self, card: &Card, now: DateTime<Utc>) -> Result<()> {
+        self.db.lock().execute(
+            "UPDATE REVIEW SET EASE = ?2, INTERVAL = ?3, REPETITIONS = ?4, LAPSES = ?5, DUE = ?6,
+            LAST_REVIEW = ?7 WHERE WORD = ?1",
+            (
+                &card.word,
+                card.ease,
+                card.interval,
+                card.repetitions,
+                card.lapses,
+                card.due.timestamp(),
+                now.timestamp(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    pub fn summary(&self, now: DateTime<Utc>) -> Result<ReviewSummary> {
+        let (start, end) = local_day(now);
+        let summary = self.db.lock().query_row(
+            "SELECT COUNT(*),
+            COALESCE(SUM(DUE < ?2), 0),
+            COALESCE(SUM(DUE < ?2 AND LAST_REVIEW IS NULL), 0),
+            COALESCE(SUM(LAST_REVIEW >= ?1), 0)
+            FROM REVIEW",
+            (start.timestamp(), end.timestamp()),
+            |row| {
+                Ok(ReviewSummary {
+                    total: row.get(0)?,
+                    due_today: row.get(1)?,
+                    new: row.get(2)?,
+                    reviewed_today: row.get(3)?,
+                })
+            },
+        )?;
+        Ok(summary)
+    }
+}
+
+/// The start and end of the local day `now` falls in.
+fn local_day(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = now
+        .with_timezone(&Local)
+        .with_time(NaiveTime::MIN)
+        .earliest()
+        .map_or(now, |x| x.with_timezone(&Utc));
+    (start, start + Duration::days(1))
+}
+
+/// Print the review summary as a table.
+pub fn print_summary(summary: &ReviewSummary) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Due today", "New", "Reviewed today", "Total"]
+            .into_iter()
+            .map(|x| Cell::new(x).with_style(Attr::Bold))
+            .collect(),
+    ));
+    table.add_row(Row::new(
+        [
+            summary.due_today,
+            summary.new,
+            summary.reviewed_today,
+            summary.total,
+        ]
+        .iter()
+        .map(|x| Cell::new(&x.to_string()))
+        .collect(),
+    ));
+    table.printstd();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dict::LookUpResultItem;
+    use crate::history::insert_history_record;
+
+    #[test]
+    fn schedule_reviews() {
+        let now = Utc::now();
+        let mut card = Card::new("rust", now);
+        let intervals: Vec<_> = [Grade::Good, Grade::Good, Grade::Good]
+            .into_iter()
+            .map(|grade| {
+                card.review(grade, now);
+                card.interval
+            })
+            .collect();
+        assert_eq!(intervals, [1, 6, 15]);
+        assert_eq!(card.ease, INITIAL_EASE);
+        assert_eq!(card.due, now + Duration::days(15));
+
+        card.review(Grade::Again, now);
+        assert_eq!((card.interval, card.repetitions, card.lapses), (1, 0, 1));
+        assert!((card.ease - 1.96).abs() < 1e-9);
+
+        for _ in 0..5 {
+            card.review(Grade::Again, now);
+        }
+        assert_eq!(card.ease, MIN_EASE);
+
+        card.review(Grade::Easy, now);
+        card.review(Grade::Easy, now);
+        card.review(Grade::Easy, now);
+        assert_eq!(card.interval, 9);
+    }
+
+    #[test]
+    fn store_reviews() {
+        let db = Database::open_in_memory().unwrap();
+        // Words first looked up now are due by then.
+        let now = Utc::now() + Duration::minutes(1);
+        let looked_up = (now - Duration::days(2)).timestamp();
+        for (word, date) in [
+            ("cargo", looked_up),
+            ("rust", looked_up),
+            ("crate", Utc::now().timestamp()),
+        ] {
+            db.lock()
+                .execute(
+                    "INSERT INTO HISTORY (WORD, DATE) VALUES (?1, ?2)",
+                    (word, date),
+                )
+                .unwrap();
+        }
+        let store = ReviewStore::new(db).unwrap();
+        store.set_definition("rust", "n. 铁锈").unwrap();
+
+        let summary = store.summary(now).unwrap();
+        assert_eq!((summary.due_today, summary.new, summary.total), (3, 3, 3));
+
+        let mut cards = store.due(now, 2).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[1].word, "rust");
+        assert_eq!(cards[1].definition.as_deref(), Some("n. 铁锈"));
+
+        cards[1].review(Grade::Good, now);
+        store.save(&cards[1], now).unwrap();
+        let due: Vec<_> = store.due(now, 10).unwrap();
+        assert_eq!(
+            due.iter().map(|x| x.word.as_str()).collect::<Vec<_>>(),
+            ["cargo", "crate"]
+        );
+        let summary = store.summary(now).unwrap();
+        assert_eq!((summary.due_today, summary.reviewed_today), (2, 1));
+
+        // Looking a word up again keeps its schedule.
+        let schedule = || -> (i64, i64, i64) {
+            store
+                .db
+                .lock()
+                .query_row(
+                    "SELECT INTERVAL, REPETITIONS, DUE FROM REVIEW WHERE WORD = 'rust'",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap()
+        };
+        let scheduled = schedule();
+        let rust = LookUpResultItem::new("rust", "n. 铁锈；v. 生锈".to_owned());
+        insert_history_record(&store.db.lock(), &rust).unwrap();
+        assert_eq!(schedule(), scheduled);
+        assert_eq!(store.due(now, 10).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,470 @@
+//! Statistics of the history: look-ups over time, streaks, the words looked
+//! up most, difficulty levels and how well reviewed words are remembered.
+use anyhow::Result;
+use chrono::{Datelike, Days, NaiveDate};
+use clap::ValueEnum;
+use prettytable::{Attr, Cell, Row, Table};
+use rusqlite::Connection;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+use crate::history::{count_tags, day_range, ALLOWED_DIFFICULTY_LEVELS};
+
+/// Reviewed words due at least this many days later are mature, as in Anki.
+const MATURE_INTERVAL: u32 = 21;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const BAR_WIDTH: usize = 40;
+
+/// How statistics are printed.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// Charts and tables for the terminal.
+    Text,
+    /// One JSON object, for other programs.
+    Json,
+}
+
+fn iso_date<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&date.format("%Y-%m-%d"))
+}
+
+/// Look-ups of one day, or of the week starting on `date`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Period {
+    #[serde(serialize_with = "iso_date")]
+    pub date: NaiveDate,
+    pub lookups: usize,
+    /// Words looked up for the first time.
+    pub new_words: usize,
+}
+
+impl Period {
+    /// Look-ups of words met before.
+    pub fn repeats(&self) -> usize {
+        self.lookups.saturating_sub(self.new_words)
+    }
+}
+
+/// Days in a row with at least one look-up.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Streak {
+    /// The streak ending today, or yesterday if nothing was looked up today yet.
+    pub current: usize,
+    pub longest: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TopWord {
+    pub word: String,
+    pub lookups: usize,
+}
+
+/// The looked-up words of a difficulty level.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Level {
+    pub level: String,
+    pub words: usize,
+    /// The share of all looked-up words.
+    pub percentage: f64,
+}
+
+/// Reviewed words, and how many were remembered at their last review.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rate {
+    pub reviewed: usize,
+    pub remembered: usize,
+    pub percentage: Option<f64>,
+}
+
+impl Rate {
+    fn new(reviewed: usize, remembered: usize) -> Self {
+        Self {
+            reviewed,
+            remembered,
+            percentage: percentage(remembered, reviewed),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Retention {
+    pub all: Rate,
+    /// Words due again within three weeks.
+    pub young: Rate,
+    pub mature: Rate,
+    /// How many times reviewed words were forgotten.
+    pub lapses: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub words: usize,
+    pub lookups: usize,
+    /// The last days, oldest first and today last.
+    pub days: Vec<Period>,
+    /// The weeks, starting on Monday, the last days fall in.
+    pub weeks: Vec<Period>,
+    pub streak: Streak,
+    pub top: Vec<TopWord>,
+    pub levels: Vec<Level>,
+    pub retention: Retention,
+}
+
+impl Stats {
+    /// Look-ups of the last days.
+    pub fn total(&self) -> Period {
+        Period {
+            date: self.days.first().map(|x| x.date).unwrap_or_default(),
+            lookups: self.days.iter().map(|x| x.lookups).sum(),
+            new_words: self.days.iter().map(|x| x.new_words).sum(),
+        }
+    }
+}
+
+fn percentage(part: usize, total: usize) -> Option<f64> {
+    (total > 0).then(|| part as f64 * 100.0 / total as f64)
+}
+
+fn parse_day(day: String) -> rusqlite::Result<NaiveDate> {
+    NaiveDate::parse_from_str(&day, "%Y-%m-%d").map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+/// Count the rows of `table` by the local day of `DATE` from `since` on.
+fn count_by_day(conn: &Connection, table: &str, since: i64) -> Result<BTreeMap<NaiveDate, usize>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DATE(DATE, 'unixepoch', 'localtime') AS DAY, COUNT(*) FROM {}
+        WHERE DATE >= ?1 GROUP BY DAY",
+        table
+    ))?;
+    let counts = stmt
+        .query_map([since], |row| Ok((parse_day(row.get(0)?)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(counts)
+}
+
+/// Every day with a look-up, in order.
+fn active_days(conn: &Connection) -> Result<Vec<NaiveDate>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT DATE(DATE, 'unixepoch', 'localtime') AS DAY FROM EVENTS ORDER BY DAY",
+    )?;
+    let days = stmt
+        .query_map([], |row| parse_day(row.get(0)?))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(days)
+}
+
+fn streak(days: &[NaiveDate], today: NaiveDate) -> Streak {
+    let mut streak = Streak::default();
+    let mut run = 0;
+    let mut last: Option<NaiveDate> = None;
+    for &day in days {
+        run = match last {
+            Some(last) if last.succ_opt() == Some(day) => run + 1,
+            _ => 1,
+        };
+        streak.longest = streak.longest.max(run);
+        last = Some(day);
+    }
+    let yesterday = today.pred_opt();
+    if last == Some(today) || last.is_some() && last == yesterday {
+        streak.current = run;
+    }
+    streak
+}
+
+fn top_words(conn: &Connection, limit: usize) -> Result<Vec<TopWord>> {
+    let mut stmt = conn.prepare(
+        "SELECT WORD, LOOKUPS FROM HISTORY ORDER BY LOOKUPS DESC, LAST_DATE DESC, WORD LIMIT ?1",
+    )?;
+    let words = stmt
+        .query_map([limit as i64], |row| {
+            Ok(TopWord {
+                word: row.get(0)?,
+                lookups: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(words)
+}
+
+fn retention(conn: &Connection) -> Result<Retention> {
+    // Forgotten words start over, so a word was remembered at its last
+    // review if it has a successful repetition since.
+    let retention = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(REPETITIONS > 0), 0),
+        COALESCE(SUM(INTERVAL < ?1), 0), COALESCE(SUM(INTERVAL < ?1 AND REPETITIONS > 0), 0),
+        COALESCE(SUM(LAPSES), 0)
+        FROM REVIEW WHERE LAST_REVIEW IS NOT NULL",
+        [MATURE_INTERVAL],
+        |row| {
+            let (reviewed, remembered): (usize, usize) = (row.get(0)?, row.get(1)?);
+            let (young, young_remembered): (usize, usize) = (row.get(2)?, row.get(3)?);
+            Ok(Retention {
+                all: Rate::new(reviewed, remembered),
+                young: Rate::new(young, young_remembered),
+                mature: Rate::new(reviewed - young, remembered - young_remembered),
+                lapses: row.get(4)?,
+            })
+        },
+    )?;
+    Ok(retention)
+}
+
+/// The statistics of the history, charting the `days` days up to `today`
+/// and listing the `top` words looked up most.
+pub fn stats(conn: &Connection, today: NaiveDate, days: usize, top: usize) -> Result<Stats> {
+    let days = days.max(1);
+    let first = today
+        .checked_sub_days(Days::new(days as u64 - 1))
+        .unwrap_or(NaiveDate::MIN);
+    // Weeks are counted from their Monday, before the first charted day.
+    let monday = first
+        .checked_sub_days(Days::new(first.weekday().num_days_from_monday() as u64))
+        .unwrap_or(first);
+    let since = day_range(Some(monday), None).0.unwrap_or(i64::MIN);
+    let lookups = count_by_day(conn, "EVENTS", since)?;
+    let new_words = count_by_day(conn, "HISTORY", since)?;
+    let period = |date: NaiveDate, last: NaiveDate| Period {
+        date,
+        lookups: lookups.range(date..=last).map(|(_, n)| n).sum(),
+        new_words: new_words.range(date..=last).map(|(_, n)| n).sum(),
+    };
+
+    let (words, total) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(LOOKUPS), 0) FROM HISTORY",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let levels: Vec<String> = ALLOWED_DIFFICULTY_LEVELS.map(str::to_owned).to_vec();
+    let levels = count_tags(conn, &levels)?
+        .into_iter()
+        .map(|(level, count)| Level {
+            level,
+            words: count,
+            percentage: percentage(count, words).unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(Stats {
+        words,
+        lookups: total,
+        days: first
+            .iter_days()
+            .take_while(|x| *x <= today)
+            .map(|x| period(x, x))
+            .collect(),
+        weeks: monday
+            .iter_weeks()
+            .take_while(|x| *x <= today)
+            .map(|x| period(x, x + Days::new(6)))
+            .collect(),
+        streak: streak(&active_days(conn)?, today),
+        top: top_words(conn, top)?,
+        levels,
+        retention: retention(conn)?,
+    })
+}
+
+/// One block per count, scaled to the largest.
+pub fn sparkline(counts: &[usize]) -> String {
+    let max = counts.iter().copied().max().unwrap_or_default();
+    counts
+        .iter()
+        .map(|&n| match n {
+            0 => ' ',
+            n => SPARKS[(n * (SPARKS.len() - 1)).div_ceil(max)],
+        })
+        .collect()
+}
+
+fn bold_row(titles: &[&str]) -> Row {
+    Row::new(
+        titles
+            .iter()
+            .map(|x| Cell::new(x).with_style(Attr::Bold))
+            .collect(),
+    )
+}
+
+fn format_rate(rate: &Rate) -> String {
+    match rate.percentage {
+        Some(percentage) => format!("{:.1}% of {}", percentage, rate.reviewed),
+        None => "-".to_owned(),
+    }
+}
+
+/// Print the statistics as charts and tables, or as JSON.
+pub fn print_stats(stats: &Stats, format: StatsFormat) -> Result<()> {
+    if format == StatsFormat::Json {
+        println!("{}", serde_json::to_string_pretty(stats)?);
+        return Ok(());
+    }
+
+    let total = stats.total();
+    println!(
+        "{} words looked up {} times in all",
+        stats.words, stats.lookups
+    );
+    println!(
+        "Last {} days: {} look-ups, {} new words and {} repeats",
+        stats.days.len(),
+        total.lookups,
+        total.new_words,
+        total.repeats()
+    );
+    let counts: Vec<_> = stats.days.iter().map(|x| x.lookups).collect();
+    if let (Some(first), Some(last)) = (stats.days.first(), stats.days.last()) {
+        println!(
+            "{} {} {}",
+            first.date.format("%m-%d"),
+            sparkline(&counts),
+            last.date.format("%m-%d")
+        );
+    }
+    println!(
+        "Streak: {} days, the longest {} days",
+        stats.streak.current, stats.streak.longest
+    );
+
+    println!();
+    let max = stats
+        .weeks
+        .iter()
+        .map(|x| x.lookups)
+        .max()
+        .unwrap_or_default();
+    for week in &stats.weeks {
+        let width = (week.lookups * BAR_WIDTH).div_ceil(max.max(1));
+        println!(
+            "{} {:<width$} {} ({} new)",
+            week.date.format("%Y-%m-%d"),
+            "█".repeat(width),
+            week.lookups,
+            week.new_words,
+            width = BAR_WIDTH
+        );
+    }
+
+    println!();
+    if !stats.top.is_empty() {
+        let mut table = Table::new();
+        table.add_row(bold_row(&["Word", "Lookups"]));
+        for word in &stats.top {
+            table.add_row(Row::new(vec![
+                Cell::new(&word.word),
+                Cell::new(&word.lookups.to_string()),
+            ]));
+        }
+        table.printstd();
+    }
+
+    let mut table = Table::new();
+    table.add_row(bold_row(
+        &stats
+            .levels
+            .iter()
+            .map(|x| x.level.as_str())
+            .collect::<Vec<_>>(),
+    ));
+    table.add_row(Row::new(
+        stats
+            .levels
+            .iter()
+            .map(|x| Cell::new(&format!("{} ({:.1}%)", x.words, x.percentage)))
+            .collect(),
+    ));
+    table.printstd();
+
+    let retention = &stats.retention;
+    let mut table = Table::new();
+    table.add_row(bold_row(&["Retention", "Young", "Mature", "Lapses"]));
+    table.add_row(Row::new(vec![
+        Cell::new(&format_rate(&retention.all)),
+        Cell::new(&format_rate(&retention.young)),
+        Cell::new(&format_rate(&retention.mature)),
+        Cell::new(&retention.lapses.to_string()),
+    ]));
+    table.printstd();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::Database;
+    use crate::dict::LookUpResultItem;
+    use crate::history::{day_start, record_look_up};
+
+    #[test]
+    fn count_streaks() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let days = [day(1), day(2), day(3), day(5), day(6)];
+        assert_eq!(
+            streak(&days, day(7)),
+            Streak {
+                current: 2,
+                longest: 3
+            }
+        );
+        assert_eq!(streak(&days, day(8)).current, 0);
+        assert_eq!(streak(&[], day(8)), Streak::default());
+        assert_eq!(sparkline(&[0, 1, 4, 8]), " ▂▅█");
+    }
+
+    #[test]
+    fn collect_stats() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        // Monday, 4 March 2024.
+        let monday = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let at = |days: u64| day_start(monday + Days::new(days)).unwrap() + 3600;
+        let look_up = |word: &str, date| {
+            let mut item = LookUpResultItem::new(word, String::new());
+            item.difficulty_levels = vec!["GRE".to_owned()];
+            record_look_up(&conn, &item, date).unwrap();
+        };
+        look_up("abate", at(0));
+        look_up("abate", at(1));
+        look_up("rust", at(1));
+        look_up("abate", at(7));
+        conn.execute(
+            "INSERT INTO REVIEW (WORD, EASE, INTERVAL, REPETITIONS, LAPSES, DUE, LAST_REVIEW)
+            VALUES ('abate', 2.5, 30, 3, 1, 0, 0), ('rust', 2.5, 1, 0, 2, 0, 0)",
+            (),
+        )
+        .unwrap();
+
+        let stats = stats(&conn, monday + Days::new(8), 7, 1).unwrap();
+        assert_eq!((stats.words, stats.lookups), (2, 4));
+        let counts: Vec<_> = stats.days.iter().map(|x| x.lookups).collect();
+        assert_eq!(counts, [0, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(stats.weeks.len(), 2);
+        assert_eq!(
+            stats.weeks[0],
+            Period {
+                date: monday,
+                lookups: 3,
+                new_words: 2
+            }
+        );
+        assert_eq!(stats.weeks[1].repeats(), 1);
+        assert_eq!(
+            stats.streak,
+            Streak {
+                current: 1,
+                longest: 2
+            }
+        );
+        assert_eq!(stats.top[0].word, "abate");
+        let gre = stats.levels.iter().find(|x| x.level == "GRE").unwrap();
+        assert_eq!(gre.percentage, 100.0);
+        assert_eq!(stats.retention.all, Rate::new(2, 1));
+        assert_eq!(stats.retention.mature.percentage, Some(100.0));
+        assert_eq!(stats.retention.lapses, 3);
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["weeks"][1]["date"], "2024-03-11");
+    }
+}
@@ -0,0 +1,575 @@
+//! Share the history between machines through a directory, e.g. a git
+//! repository, a Syncthing folder or a network mount, with no server.
+//!
+//! Every machine appends its changes to `<machine>.jsonl` there and only
+//! reads the logs of the others. Syncing writes the pending local changes
+//! and merges the lines added to the other logs since the last sync.
+//! Look-ups are merged like imported history records, see
+//! [`crate::transfer`], so that each machine's are counted once even if they
+//! also arrived by import; of changes to the same tag of a word the latest wins,
+//! ties going to the greater machine name. Every machine thus ends up with
+//! the same history, whatever order the logs are read in.
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::history::{add_tag, remove_word, tag_from_wordlists};
+use crate::transfer::{self, rfc3339, HistoryRecord};
+
+/// Where the history is shared, `[sync]` in the file.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// The shared directory; nothing is synced without one.
+    pub dir: Option<PathBuf>,
+    /// Sync every time dioxionary starts.
+    pub on_start: bool,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            on_start: true,
+        }
+    }
+}
+
+const LOG_EXTENSION: &str = "jsonl";
+
+/// A change to the history, as written to the logs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Change {
+    /// Look-ups of a word: the count of the machine after a single one, or
+    /// all known when the machine first synced or imported them.
+    LookUp(HistoryRecord),
+    /// A tag added to a word, or removed from it.
+    Tag {
+        word: String,
+        tag: String,
+        #[serde(with = "rfc3339")]
+        date: i64,
+        #[serde(default)]
+        removed: bool,
+    },
+    /// A word removed from the history, with the look-ups up to `date`.
+    Remove {
+        word: String,
+        #[serde(with = "rfc3339")]
+        date: i64,
+    },
+    /// A note on a word; an empty one removes it.
+    Note {
+        word: String,
+        note: String,
+        #[serde(with = "rfc3339")]
+        date: i64,
+    },
+}
+
+impl Change {
+    /// Tag a word now, or remove the tag.
+    pub fn tag(word: &str, tag: &str, removed: bool) -> Self {
+        Self::Tag {
+            word: word.to_owned(),
+            tag: tag.to_owned(),
+            date: Utc::now().timestamp(),
+            removed,
+        }
+    }
+}
+
+/// A line of a log. Numbers only grow within a log, so the lines already
+/// merged are known even if a log is rewritten.
+#[derive(Serialize, Deserialize)]
+struct LogLine {
+    seq: i64,
+    #[serde(flatten)]
+    change: Change,
+}
+
+/// Keep a change to write to the log at the next sync, once this machine has synced.
+pub fn log_change(conn: &Connection, change: &Change) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO SYNC_OUTBOX (CHANGE) SELECT ?1 WHERE EXISTS (SELECT 1 FROM SYNC_MACHINE)",
+    )?
+    .execute([serde_json::to_string(change)?])?;
+    Ok(())
+}
+
+/// The name of this machine's log, if it ever synced.
+pub fn machine(conn: &Connection) -> Result<Option<String>> {
+    let name = conn
+        .query_row("SELECT NAME FROM SYNC_MACHINE", [], |row| row.get(0))
+        .optional()?;
+    Ok(name)
+}
+
+/// The name look-ups made on this machine are counted under, also that of
+/// its log once it syncs.
+pub(crate) fn this_machine(conn: &Connection) -> Result<String> {
+    let name = conn
+        .prepare_cached("SELECT NAME FROM MACHINE")?
+        .query_row([], |row| row.get(0))?;
+    Ok(name)
+}
+
+/// The host name with a random suffix, as machines may share a host name.
+pub(crate) fn new_machine_name() -> String {
+    let host = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .unwrap_or_default();
+    let host: String = host
+        .trim()
+        .chars()
+        .filter(|x| x.is_ascii_alphanumeric() || *x == '-' || *x == '_')
+        .collect();
+    let host = if host.is_empty() { "machine" } else { &host };
+    format!("{}-{:08x}", host, rand::random::<u32>())
+}
+
+/// Start the log of this machine and queue the whole history, its tags and
+/// notes as its first changes.
+fn set_up(conn: &Connection) -> Result<String> {
+    let name = this_machine(conn)?;
+    conn.execute(
+        "INSERT INTO SYNC_MACHINE (ID, NAME) VALUES (1, ?1)",
+        [&name],
+    )?;
+    for record in transfer::records(conn)? {
+        log_change(conn, &Change::LookUp(record))?;
+    }
+    // Words may be tagged without being looked up.
+    let tags: Vec<(String, String)> = conn
+        .prepare("SELECT WORD, TAG FROM TAGS WHERE WORD NOT IN (SELECT WORD FROM HISTORY)")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (word, tag) in tags {
+        log_change(conn, &Change::tag(&word, &tag, false))?;
+    }
+    let notes: Vec<(String, String, i64)> = conn
+        .prepare("SELECT WORD, NOTE, DATE FROM NOTES")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (word, note, date) in notes {
+        log_change(conn, &Change::Note { word, note, date })?;
+    }
+    Ok(name)
+}
+
+/// When and where a change was made: changes are ordered by date, then by
+/// machine name, then by their number in the log.
+struct Clock<'a> {
+    date: i64,
+    machine: &'a str,
+    seq: i64,
+}
+
+/// Record a change to a tag unless a later one is known, then tag the word
+/// or not as the latest change says.
+fn apply_tag(conn: &Connection, clock: Clock, word: &str, tag: &str, removed: bool) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO SYNC_TAGS (WORD, TAG, DATE, MACHINE, SEQ, REMOVED)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT (WORD, TAG) DO UPDATE SET DATE = excluded.DATE,
+        MACHINE = excluded.MACHINE, SEQ = excluded.SEQ, REMOVED = excluded.REMOVED
+        WHERE (excluded.DATE, excluded.MACHINE, excluded.SEQ) > (DATE, MACHINE, SEQ)",
+    )?
+    .execute((word, tag, clock.date, clock.machine, clock.seq, removed))?;
+    let removed: bool = conn
+        .prepare_cached("SELECT REMOVED FROM SYNC_TAGS WHERE WORD = ?1 AND TAG = ?2")?
+        .query_row((word, tag), |row| row.get(0))?;
+    if removed {
+        conn.prepare_cached("DELETE FROM TAGS WHERE WORD = ?1 AND TAG = ?2")?
+            .execute((word, tag))?;
+    } else {
+        add_tag(conn, word, tag)?;
+    }
+    Ok(())
+}
+
+/// Apply the tag changes of a change, the `seq`th of `machine`.
+fn apply_tags(conn: &Connection, machine: &str, seq: i64, change: &Change) -> Result<()> {
+    match change {
+        Change::LookUp(record) => {
+            for tag in &record.tags {
+                let clock = Clock {
+                    date: record.last_seen,
+                    machine,
+                    seq,
+                };
+                apply_tag(conn, clock, &record.word, tag, false)?;
+            }
+        }
+        Change::Tag {
+            word,
+            tag,
+            date,
+            removed,
+        } => {
+            let clock = Clock {
+                date: *date,
+                machine,
+                seq,
+            };
+            apply_tag(conn, clock, word, tag, *removed)?;
+        }
+        Change::Remove { .. } | Change::Note { .. } => (),
+    }
+    Ok(())
+}
+
+/// Apply a change made on another machine.
+///
+/// Look-ups of words removed later are dropped, and of notes of the same
+/// word the latest is kept, or the greater of those written in the same second.
+/// A look-up adding one to the count of a word is recorded as an event.
+fn apply(conn: &Connection, machine: &str, seq: i64, change: &Change) -> Result<()> {
+    match change {
+        Change::LookUp(record) => {
+            if transfer::removed_since(conn, record)? {
+                return Ok(());
+            }
+            let mut record = transfer::check(record.clone())?;
+            // Logs written before look-ups were counted by machine hold
+            // look-ups to add up.
+            if record.machines.is_empty() {
+                record
+                    .machines
+                    .insert(format!("{}-{}", machine, seq), record.lookups);
+            }
+            let lookups = transfer::lookups(conn, &record.word)?;
+            transfer::merge_record(conn, &record)?;
+            tag_from_wordlists(conn, &record.word)?;
+            if transfer::lookups(conn, &record.word)? == lookups + 1 {
+                conn.prepare_cached("INSERT INTO EVENTS (WORD, DATE, SOURCE) VALUES (?1, ?2, ?3)")?
+                    .execute((&record.word, record.last_seen, &record.source))?;
+            }
+        }
+        Change::Remove { word, date } => {
+            remove_word(conn, word, *date)?;
+        }
+        Change::Note { word, note, date } => {
+            conn.prepare_cached(
+                "INSERT INTO NOTES (WORD, NOTE, DATE) VALUES (?1, ?2, ?3)
+                ON CONFLICT (WORD) DO UPDATE SET NOTE = excluded.NOTE, DATE = excluded.DATE
+                WHERE (excluded.DATE, excluded.NOTE) > (DATE, NOTE)",
+            )?
+            .execute((word, note, date))?;
+        }
+        Change::Tag { .. } => (),
+    }
+    apply_tags(conn, machine, seq, change)
+}
+
+fn log_path(dir: &Path, machine: &str) -> PathBuf {
+    dir.join(format!("{}.{}", machine, LOG_EXTENSION))
+}
+
+/// Append the pending changes of this machine to its log. Returns how many
+/// were written.
+fn send(conn: &Connection, dir: &Path, machine: &str) -> Result<usize> {
+    let pending: Vec<(i64, String)> = conn
+        .prepare("SELECT SEQ, CHANGE FROM SYNC_OUTBOX ORDER BY SEQ")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let Some(&(last, _)) = pending.last() else {
+        return Ok(0);
+    };
+
+    let mut lines = String::new();
+    for (seq, change) in &pending {
+        let change: Change = serde_json::from_str(change)?;
+        apply_tags(conn, machine, *seq, &change)?;
+        lines.push_str(&serde_json::to_string(&LogLine { seq: *seq, change })?);
+        lines.push('\n');
+    }
+    let path = log_path(dir, machine);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    file.write_all(lines.as_bytes())?;
+    file.sync_all()?;
+    conn.execute("DELETE FROM SYNC_OUTBOX WHERE SEQ <= ?1", [last])?;
+    Ok(pending.len())
+}
+
+/// Merge the lines added to the log of another machine since the last sync.
+/// Returns how many changes were merged.
+fn receive(conn: &Connection, path: &Path, machine: &str) -> Result<usize> {
+    let (mut last, offset): (i64, u64) = conn
+        .query_row(
+            "SELECT SEQ, OFFSET FROM SYNC_PEERS WHERE MACHINE = ?1",
+            [machine],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .unwrap_or_default();
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    // A log shorter than what was read was rewritten, so read it again.
+    let offset = if offset > file.metadata()?.len() {
+        0
+    } else {
+        offset
+    };
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+
+    let mut read = 0;
+    let mut merged = 0;
+    // A last line without a line break may still be being written.
+    for line in data.split_inclusive(|x| *x == b'\n') {
+        if !line.ends_with(b"\n") {
+            break;
+        }
+        read += line.len();
+        let line = std::str::from_utf8(line)?.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let line: LogLine = serde_json::from_str(line)
+            .with_context(|| format!("Invalid change in {:?}: {}", path, line))?;
+        if line.seq > last {
+            apply(conn, machine, line.seq, &line.change)?;
+            last = line.seq;
+            merged += 1;
+        }
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO SYNC_PEERS (MACHINE, SEQ, OFFSET) VALUES (?1, ?2, ?3)",
+        (machine, last, offset + read as u64),
+    )?;
+    Ok(merged)
+}
+
+/// What a sync did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncReport {
+    /// The name of this machine's log.
+    pub machine: String,
+    pub sent: usize,
+    pub received: usize,
+    /// How many other machines' logs were read.
+    pub peers: usize,
+}
+
+/// Write the changes of this machine to `dir` and merge those of the others.
+/// The first sync of a machine writes its whole history.
+pub fn sync(conn: &Connection, dir: &Path) -> Result<SyncReport> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let tx = conn.unchecked_transaction()?;
+    let machine = match machine(&tx)? {
+        Some(machine) => machine,
+        None => set_up(&tx)?,
+    };
+    let sent = send(&tx, dir, &machine)?;
+
+    let mut logs: Vec<(String, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|x| x.path()))
+        .filter(|path| path.extension().is_some_and(|x| x == LOG_EXTENSION))
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_owned(), path.clone())))
+        .filter(|(name, _)| *name != machine)
+        .collect();
+    logs.sort();
+    let mut received = 0;
+    for (name, path) in &logs {
+        received += receive(&tx, path, name)?;
+    }
+    tx.commit()?;
+    Ok(SyncReport {
+        machine,
+        sent,
+        received,
+        peers: logs.len(),
+    })
+}
+
+pub fn print_report(report: &SyncReport) {
+    println!(
+        "Sent {} changes and received {} from {} other machines",
+        report.sent, report.received, report.peers
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::Database;
+    use crate::dict::LookUpResultItem;
+    use crate::history::{insert_history_record, tag_words, untag_words};
+
+    fn look_up(db: &Database, word: &str, definition: &str) {
+        insert_history_record(
+            &db.lock(),
+            &LookUpResultItem::new(word, definition.to_owned()),
+        )
+        .unwrap();
+    }
+
+    fn tags(db: &Database) -> Vec<(String, String)> {
+        db.lock()
+            .prepare("SELECT WORD, TAG FROM TAGS ORDER BY WORD, TAG")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn sync_machines() {
+        let dir = std::env::temp_dir().join(format!("dioxionary-sync-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let machines: Vec<_> = (0..3)
+            .map(|_| Database::open_in_memory().unwrap())
+            .collect();
+        let [a, b, c] = &machines[..] else {
+            unreachable!()
+        };
+        let sync = |db: &Database| super::sync(&db.lock(), &dir).unwrap();
+
+        // Look-ups and tags from before the first sync are written too.
+        look_up(a, "rust", "n. 铁锈");
+        look_up(a, "rust", "n. 铁锈");
+        tag_words(&a.lock(), "Book: Dune", &["spice".to_owned()]).unwrap();
+        assert_eq!(sync(a).sent, 2);
+        look_up(a, "crate", "n. 板条箱");
+        tag_words(&a.lock(), "mine", &["rust".to_owned()]).unwrap();
+        look_up(b, "rust", "v. 生锈");
+        let report = sync(b);
+        assert_eq!((report.sent, report.received, report.peers), (1, 2, 1));
+        assert_eq!(sync(a).sent, 2);
+        untag_words(&b.lock(), "mine", &["rust".to_owned()]).unwrap();
+        sync(b);
+        assert_eq!(sync(c).received, 6);
+        sync(a);
+        assert_eq!(sync(b).sent, 0);
+
+        let records: Vec<_> = machines
+            .iter()
+            .map(|db| transfer::records(&db.lock()).unwrap())
+            .collect();
+        assert_eq!(records[0], records[1]);
+        assert_eq!(records[0], records[2]);
+        let rust = records[0].iter().find(|x| x.word == "rust").unwrap();
+        assert_eq!(rust.lookups, 3);
+        // Tagging and untagging in the same second leaves the tie to the names.
+        assert_eq!(tags(a), tags(b));
+        assert_eq!(tags(a), tags(c));
+        assert!(tags(c).contains(&("spice".to_owned(), "Book: Dune".to_owned())));
+        let events: i64 = c
+            .lock()
+            .query_row("SELECT COUNT(*) FROM EVENTS", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(events, 2);
+
+        // Look-ups moved by import as well are counted once, also when the
+        // histories were merged before the first sync.
+        let d = Database::open_in_memory().unwrap();
+        look_up(&d, "ferris", "n. 螃蟹");
+        look_up(&d, "ferris", "n. 螃蟹");
+        let export = |db: &Database| transfer::records(&db.lock()).unwrap();
+        transfer::merge(&d.lock(), &export(a), false).unwrap();
+        transfer::merge(&a.lock(), &export(&d), false).unwrap();
+        let lookups = |db: &Database| -> Vec<(String, u32)> {
+            export(db)
+                .into_iter()
+                .map(|x| (x.word, x.lookups))
+                .collect()
+        };
+        let before = lookups(a);
+        assert!(before.contains(&("ferris".to_owned(), 2)));
+        assert!(before.contains(&("rust".to_owned(), 3)));
+        assert_eq!(lookups(&d), before);
+        for db in [&d, a, b, c, &d] {
+            sync(db);
+        }
+        for db in [a, b, c, &d] {
+            assert_eq!(lookups(db), before);
+            assert_eq!(export(db), export(a));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn latest_tag_change_wins() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        let tagged = || -> bool {
+            conn.query_row("SELECT COUNT(*) FROM TAGS", [], |row| row.get(0))
+                .unwrap()
+        };
+        let change = |date, removed| Change::Tag {
+            word: "rust".to_owned(),
+            tag: "mine".to_owned(),
+            date,
+            removed,
+        };
+        apply(&conn, "b", 1, &change(200, true)).unwrap();
+        apply(&conn, "a", 2, &change(100, false)).unwrap();
+        assert!(!tagged());
+        apply(&conn, "a", 3, &change(200, false)).unwrap();
+        assert!(!tagged());
+        apply(&conn, "c", 1, &change(200, false)).unwrap();
+        assert!(tagged());
+        apply(&conn, "c", 2, &change(200, true)).unwrap();
+        assert!(!tagged());
+    }
+
+    #[test]
+    fn removals_and_notes() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        let look_up = |date| {
+            Change::LookUp(HistoryRecord {
+                word: "teh".to_owned(),
+                lookups: 1,
+                first_seen: date,
+                last_seen: date,
+                source: None,
+                definition: None,
+                tags: vec![],
+                entry: None,
+                machines: Default::default(),
+            })
+        };
+        let lookups = || -> i64 {
+            conn.query_row("SELECT COALESCE(SUM(LOOKUPS), 0) FROM HISTORY", [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        apply(&conn, "a", 1, &look_up(100)).unwrap();
+        let remove = Change::Remove {
+            word: "teh".to_owned(),
+            date: 200,
+        };
+        apply(&conn, "b", 1, &remove).unwrap();
+        assert_eq!(lookups(), 0);
+        // Look-ups before the removal arriving late are dropped, later ones kept.
+        apply(&conn, "c", 1, &look_up(150)).unwrap();
+        assert_eq!(lookups(), 0);
+        apply(&conn, "c", 2, &look_up(300)).unwrap();
+        assert_eq!(lookups(), 1);
+
+        let note = |note: &str, date| Change::Note {
+            word: "rust".to_owned(),
+            note: note.to_owned(),
+            date,
+        };
+        apply(&conn, "a", 2, &note("new", 200)).unwrap();
+        apply(&conn, "b", 2, &note("old", 100)).unwrap();
+        assert_eq!(crate::history::note(&conn, "rust").unwrap().unwrap(), "new");
+        apply(&conn, "b", 3, &note("", 300)).unwrap();
+        assert_eq!(crate::history::note(&conn, "rust").unwrap(), None);
+    }
+}
@@ -0,0 +1,709 @@
+//! Move the look-up history between machines as CSV or JSON files.
+//!
+//! Importing merges by word: look-ups add up, the first and last look-ups
+//! span both histories, tags are united and review progress is left alone.
+//! Look-ups are counted by the machine they were made on and of each machine
+//! the greater count is kept, so importing the same look-ups again, or
+//! syncing with their machine as well, counts them once. Records without
+//! machines, like those of older files, are counted as made on one named
+//! after the word and its first look-up, so they are counted once as well.
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use csv::StringRecord;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+use crate::history::{add_tag, tag_from_wordlists};
+use crate::sync::{self, Change};
+
+/// The layout of a history file.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// One word per row; tags are separated by `;`, a `\` escaping the next
+    /// character, and entries are left out.
+    Csv,
+    /// Every record with the whole dictionary entry.
+    Json,
+}
+
+impl HistoryFormat {
+    /// Guess the format from the file extension, falling back to JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Self::Csv,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// Bumped when the JSON layout changes incompatibly.
+const JSON_VERSION: u32 = 1;
+
+const CSV_HEADER: [&str; 8] = [
+    "word",
+    "lookups",
+    "first_seen",
+    "last_seen",
+    "source",
+    "definition",
+    "tags",
+    "machines",
+];
+
+/// Timestamps are written as RFC 3339 dates in UTC.
+pub(crate) mod rfc3339 {
+    use chrono::{DateTime, SecondsFormat};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn format(timestamp: i64) -> String {
+        DateTime::from_timestamp(timestamp, 0)
+            .unwrap_or_default()
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    pub fn parse(text: &str) -> Result<i64, chrono::ParseError> {
+        DateTime::parse_from_rfc3339(text.trim()).map(|x| x.timestamp())
+    }
+
+    pub fn serialize<S: Serializer>(timestamp: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(*timestamp))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        parse(&text).map_err(D::Error::custom)
+    }
+}
+
+/// A looked-up word as written to history files.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryRecord {
+    pub word: String,
+    pub lookups: u32,
+    #[serde(with = "rfc3339")]
+    pub first_seen: i64,
+    #[serde(with = "rfc3339")]
+    pub last_seen: i64,
+    /// The dictionary of the last look-up.
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub definition: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The dictionary entry of the last look-up, only kept in JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<Value>,
+    /// How many of the look-ups each machine made, adding up to `lookups`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub machines: BTreeMap<String, u32>,
+}
+
+/// Join the items of a CSV field with `;`, putting a `\` before `;` and `\`
+/// in the items.
+fn join_list<S: AsRef<str>>(items: impl IntoIterator<Item = S>) -> String {
+    let items: Vec<_> = items
+        .into_iter()
+        .map(|x| x.as_ref().replace('\\', "\\\\").replace(';', "\\;"))
+        .collect();
+    items.join(";")
+}
+
+fn split_list(text: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => items.last_mut().unwrap().extend(chars.next()),
+            ';' => items.push(String::new()),
+            c => items.last_mut().unwrap().push(c),
+        }
+    }
+    items.into_iter().map(|x| x.trim().to_owned()).collect()
+}
+
+/// Machines and their look-ups as `name=count` separated by `;`.
+fn format_machines(machines: &BTreeMap<String, u32>) -> String {
+    join_list(
+        machines
+            .iter()
+            .map(|(machine, lookups)| format!("{}={}", machine, lookups)),
+    )
+}
+
+fn parse_machines(text: &str) -> Result<BTreeMap<String, u32>> {
+    split_list(text)
+        .into_iter()
+        .map(|x| {
+            let (machine, lookups) = x
+                .rsplit_once('=')
+                .with_context(|| format!("Invalid machine: {}", x))?;
+            Ok((machine.trim().to_owned(), lookups.trim().parse()?))
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryFile {
+    version: u32,
+    records: Vec<HistoryRecord>,
+}
+
+/// Every history record with its tags, in the order the words were first looked up.
+pub fn records(conn: &Connection) -> Result<Vec<HistoryRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT H.WORD, H.LOOKUPS, H.DATE, H.LAST_DATE, H.SOURCE, H.DEFINITION, H.ENTRY,
+        (SELECT GROUP_CONCAT(TAG, CHAR(31)) FROM TAGS T WHERE T.WORD = H.WORD AND NOT LISTED),
+        (SELECT GROUP_CONCAT(MACHINE || CHAR(30) || LOOKUPS, CHAR(31)) FROM MACHINE_LOOKUPS M
+        WHERE M.WORD = H.WORD)
+        FROM HISTORY H ORDER BY H.DATE, H.WORD",
+    )?;
+    let records = stmt
+        .query_map([], |row| {
+            let entry: Option<String> = row.get(6)?;
+            let tags: Option<String> = row.get(7)?;
+            let mut tags: Vec<String> = tags
+                .map(|x| x.split('\x1f').map(str::to_owned).collect())
+                .unwrap_or_default();
+            tags.sort();
+            let machines: Option<String> = row.get(8)?;
+            Ok(HistoryRecord {
+                word: row.get(0)?,
+                lookups: row.get(1)?,
+                first_seen: row.get(2)?,
+                last_seen: row.get(3)?,
+                source: row.get(4)?,
+                definition: row.get(5)?,
+                tags,
+                entry: entry.and_then(|x| serde_json::from_str(&x).ok()),
+                machines: machines
+                    .iter()
+                    .flat_map(|x| x.split('\x1f'))
+                    .filter_map(|x| x.split_once('\x1e'))
+                    .filter_map(|(machine, lookups)| {
+                        Some((machine.to_owned(), lookups.parse().ok()?))
+                    })
+                    .collect(),
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(records)
+}
+
+/// Write the records in `format`.
+pub fn write(records: &[HistoryRecord], format: HistoryFormat, out: &mut impl Write) -> Result<()> {
+    match format {
+        HistoryFormat::Json => {
+            let file = HistoryFile {
+                version: JSON_VERSION,
+                records: records.to_vec(),
+            };
+            serde_json::to_writer_pretty(&mut *out, &file)?;
+            writeln!(out)?;
+        }
+        HistoryFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record(CSV_HEADER)?;
+            for record in records {
+                writer.write_record([
+                    record.word.clone(),
+                    record.lookups.to_string(),
+                    rfc3339::format(record.first_seen),
+                    rfc3339::format(record.last_seen),
+                    record.source.clone().unwrap_or_default(),
+                    record.definition.clone().unwrap_or_default(),
+                    join_list(&record.tags),
+                    format_machines(&record.machines),
+                ])?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the records of a history file.
+pub fn parse(text: &str, format: HistoryFormat) -> Result<Vec<HistoryRecord>> {
+    let records = match format {
+        HistoryFormat::Json => {
+            let file: HistoryFile = serde_json::from_str(text)?;
+            if file.version > JSON_VERSION {
+                bail!(
+                    "The file is of version {}, newer than this program supports ({})",
+                    file.version,
+                    JSON_VERSION
+                );
+            }
+            file.records
+        }
+        HistoryFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .flexible(true)
+                .from_reader(text.as_bytes());
+            let header = reader.headers()?.clone();
+            if header.is_empty() {
+                return Ok(Vec::new());
+            }
+            let column = |name: &str| {
+                header
+                    .iter()
+                    .position(|x| x.trim().eq_ignore_ascii_case(name))
+                    .with_context(|| format!("The `{}` column is missing", name))
+            };
+            let [word, lookups, first_seen, last_seen, source, definition, tags, machines] =
+                CSV_HEADER.map(column);
+            let (word, lookups, first_seen, last_seen) = (word?, lookups?, first_seen?, last_seen?);
+            let (source, definition, tags) = (source.ok(), definition.ok(), tags.ok());
+            let machines = machines.ok();
+            let optional = |row: &StringRecord, column: Option<usize>| {
+                column
+                    .and_then(|i| row.get(i))
+                    .map(|x| x.trim())
+                    .filter(|x| !x.is_empty())
+                    .map(str::to_owned)
+            };
+            reader
+                .records()
+                .map(|row| {
+                    let row = row?;
+                    let field = |column: usize| row.get(column).unwrap_or("");
+                    let record = HistoryRecord {
+                        word: field(word).trim().to_owned(),
+                        lookups: field(lookups).trim().parse()?,
+                        first_seen: rfc3339::parse(field(first_seen))?,
+                        last_seen: rfc3339::parse(field(last_seen))?,
+                        source: optional(&row, source),
+                        definition: optional(&row, definition),
+                        tags: optional(&row, tags)
+                            .map(|x| split_list(&x))
+                            .unwrap_or_default(),
+                        entry: None,
+                        machines: optional(&row, machines)
+                            .map(|x| parse_machines(&x))
+                            .transpose()?
+                            .unwrap_or_default(),
+                    };
+                    anyhow::Ok(record)
+                })
+                .enumerate()
+                .map(|(i, record)| record.with_context(|| format!("Invalid row {}", i + 2)))
+                .collect::<Result<_>>()?
+        }
+    };
+    records.into_iter().map(check).collect()
+}
+
+pub(crate) fn check(mut record: HistoryRecord) -> Result<HistoryRecord> {
+    record.word = record.word.trim().to_owned();
+    if record.word.is_empty() {
+        bail!("A record has no word");
+    }
+    record
+        .machines
+        .retain(|machine, lookups| !machine.is_empty() && *lookups > 0);
+    let counted = record.machines.values().sum::<u32>();
+    if record.lookups == 0
+        || record.first_seen > record.last_seen
+        || (!record.machines.is_empty() && counted != record.lookups)
+    {
+        bail!("The record of {} is inconsistent", record.word);
+    }
+    record.tags.retain(|x| !x.trim().is_empty());
+    Ok(record)
+}
+
+/// A word whose definition differs between the history and the imported file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub word: String,
+    pub local: String,
+    pub imported: String,
+    /// The imported definition replaces the local one, being looked up later.
+    pub imported_kept: bool,
+}
+
+/// What an import changed, or would change in a dry run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub added: usize,
+    pub merged: usize,
+    pub conflicts: Vec<Conflict>,
+    /// Words left out, having been removed from the history after their last look-up.
+    pub removed: Vec<String>,
+}
+
+/// How a record went into the history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Merged {
+    Added,
+    Merged(Option<Conflict>),
+}
+
+/// Whether the word of a record was removed from the history since its last look-up.
+pub(crate) fn removed_since(conn: &Connection, record: &HistoryRecord) -> Result<bool> {
+    let removed: Option<i64> = conn
+        .prepare_cached("SELECT DATE FROM REMOVED_WORDS WHERE WORD = ?1")?
+        .query_row([&record.word], |row| row.get(0))
+        .optional()?;
+    Ok(removed.is_some_and(|x| x >= record.last_seen))
+}
+
+/// How many times a word was looked up, on any machine.
+pub(crate) fn lookups(conn: &Connection, word: &str) -> Result<u32> {
+    let lookups = conn
+        .prepare_cached("SELECT COALESCE(SUM(LOOKUPS), 0) FROM MACHINE_LOOKUPS WHERE WORD = ?1")?
+        .query_row([word], |row| row.get(0))?;
+    Ok(lookups)
+}
+
+/// Merge a record into the history, leaving its tags to the caller. Of a
+/// word known to both, the dictionary, definition and entry of the later
+/// look-up are kept, or of the greater definition if both were in the same
+/// second, so that the order records are merged in does not matter.
+///
+/// Only the look-ups of the record's machines are merged, the greater count
+/// of each machine winning.
+pub(crate) fn merge_record(conn: &Connection, record: &HistoryRecord) -> Result<Merged> {
+    for (machine, lookups) in &record.machines {
+        conn.prepare_cached(
+            "INSERT INTO MACHINE_LOOKUPS (WORD, MACHINE, LOOKUPS) VALUES (?1, ?2, ?3)
+            ON CONFLICT (WORD, MACHINE) DO UPDATE SET LOOKUPS = MAX(LOOKUPS, excluded.LOOKUPS)",
+        )?
+        .execute((&record.word, machine, lookups))?;
+    }
+    let lookups = lookups(conn, &record.word)?;
+    let entry = record.entry.as_ref().map(Value::to_string);
+    let local: Option<(i64, Option<String>)> = conn
+        .prepare_cached("SELECT LAST_DATE, DEFINITION FROM HISTORY WHERE WORD = ?1")?
+        .query_row([&record.word], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()?;
+    let Some((last_seen, definition)) = local else {
+        conn.prepare_cached(
+            "INSERT INTO HISTORY (WORD, DATE, LAST_DATE, LOOKUPS, SOURCE, DEFINITION, ENTRY)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?
+        .execute((
+            &record.word,
+            record.first_seen,
+            record.last_seen,
+            lookups,
+            &record.source,
+            &record.definition,
+            &entry,
+        ))?;
+        return Ok(Merged::Added);
+    };
+
+    let newer = (record.last_seen, &record.definition) > (last_seen, &definition);
+    conn.prepare_cached(
+        "UPDATE HISTORY SET LOOKUPS = ?2,
+        DATE = MIN(DATE, ?3), LAST_DATE = MAX(LAST_DATE, ?4),
+        SOURCE = IIF(?5, ?6, SOURCE),
+        DEFINITION = IIF(?5, COALESCE(?7, DEFINITION), DEFINITION),
+        ENTRY = IIF(?5, COALESCE(?8, ENTRY), ENTRY)
+        WHERE WORD = ?1",
+    )?
+    .execute((
+        &record.word,
+        lookups,
+        record.first_seen,
+        record.last_seen,
+        newer,
+        &record.source,
+        &record.definition,
+        &entry,
+    ))?;
+    let conflict = match (definition, &record.definition) {
+        (Some(local), Some(imported))
+            if !local.trim().is_empty() && local.trim() != imported.trim() =>
+        {
+            Some(Conflict {
+                word: record.word.clone(),
+                local,
+                imported: imported.clone(),
+                imported_kept: newer,
+            })
+        }
+        _ => None,
+    };
+    Ok(Merged::Merged(conflict))
+}
+
+/// The machine the look-ups of a record without machines are counted as
+/// made on. A later export of the same history has the same first look-up,
+/// so its greater count replaces that imported before.
+fn import_origin(record: &HistoryRecord) -> String {
+    let digest = Sha1::digest(format!("{}\n{}", record.word, record.first_seen));
+    let hash: String = digest[..4].iter().map(|x| format!("{:02x}", x)).collect();
+    format!("import-{}", hash)
+}
+
+/// Merge the records into the history and tag their words, leaving out
+/// those of words removed after their last look-up.
+/// A dry run reports the same without changing anything.
+pub fn merge(conn: &Connection, records: &[HistoryRecord], dry_run: bool) -> Result<MergeReport> {
+    let tx = conn.unchecked_transaction()?;
+    let mut report = MergeReport::default();
+    for record in records {
+        if removed_since(&tx, record)? {
+            report.removed.push(record.word.clone());
+            continue;
+        }
+        let mut record = record.clone();
+        if record.machines.is_empty() {
+            record
+                .machines
+                .insert(import_origin(&record), record.lookups);
+        }
+        match merge_record(&tx, &record)? {
+            Merged::Added => report.added += 1,
+            Merged::Merged(conflict) => {
+                report.merged += 1;
+                report.conflicts.extend(conflict);
+            }
+        }
+        let tags: BTreeSet<_> = record.tags.iter().map(|x| x.trim()).collect();
+        for tag in tags {
+            add_tag(&tx, &record.word, tag)?;
+        }
+        tag_from_wordlists(&tx, &record.word)?;
+        sync::log_change(&tx, &Change::LookUp(record))?;
+    }
+    if !dry_run {
+        tx.commit()?;
+    }
+    Ok(report)
+}
+
+/// Read a history file and merge it into the history.
+pub fn import(
+    conn: &Connection,
+    path: &Path,
+    format: Option<HistoryFormat>,
+    dry_run: bool,
+) -> Result<MergeReport> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let format = format.unwrap_or_else(|| HistoryFormat::from_path(path));
+    let records = parse(&text, format).with_context(|| format!("Failed to parse {:?}", path))?;
+    merge(conn, &records, dry_run)
+}
+
+/// The first line of a definition, to keep conflicts short.
+fn first_line(text: &str) -> &str {
+    text.trim().lines().next().unwrap_or_default()
+}
+
+/// Print what an import did, or would do in a dry run.
+pub fn print_report(report: &MergeReport, dry_run: bool) {
+    let (add, merge) = if dry_run {
+        ("Would add", "merge")
+    } else {
+        ("Added", "merged")
+    };
+    println!(
+        "{} {} new words and {} {} known ones",
+        add, report.added, merge, report.merged
+    );
+    if !report.removed.is_empty() {
+        let skip = if dry_run { "Would skip" } else { "Skipped" };
+        println!(
+            "{} {} words removed from the history: {}",
+            skip,
+            report.removed.len(),
+            report.removed.join(", ")
+        );
+    }
+    if report.conflicts.is_empty() {
+        return;
+    }
+    println!("Definitions differ for {} words:", report.conflicts.len());
+    for conflict in &report.conflicts {
+        let kept = if conflict.imported_kept {
+            "imported"
+        } else {
+            "local"
+        };
+        println!("  {} (keeping the {} one)", conflict.word, kept);
+        println!("    local:    {}", first_line(&conflict.local));
+        println!("    imported: {}", first_line(&conflict.imported));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::Database;
+    use crate::dict::LookUpResultItem;
+    use crate::history::{insert_history_record, tag_words};
+
+    fn history() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        {
+            let conn = db.lock();
+            let mut rust = LookUpResultItem::new("rust", "n. 铁锈".to_owned());
+            rust.difficulty_levels = vec!["CET4".to_owned()];
+            insert_history_record(&conn, &rust).unwrap();
+            insert_history_record(&conn, &rust).unwrap();
+            let crate_ = LookUpResultItem::new("crate", "n. 板条箱, \"箱\"".to_owned());
+            insert_history_record(&conn, &crate_).unwrap();
+            tag_words(&conn, "Book: Dune", &["crate".to_owned()]).unwrap();
+            conn.execute("UPDATE HISTORY SET DATE = 1700000000", [])
+                .unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn round_trip() {
+        let db = history();
+        tag_words(&db.lock(), "Dune; Messiah \\ 2", &["crate".to_owned()]).unwrap();
+        let records = records(&db.lock()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].lookups, 2);
+        assert_eq!(records[1].tags, ["CET4"]);
+        assert!(records[1].entry.is_some());
+        assert_eq!(records[0].tags, ["Book: Dune", "Dune; Messiah \\ 2"]);
+
+        for format in [HistoryFormat::Csv, HistoryFormat::Json] {
+            let mut out = Vec::new();
+            write(&records, format, &mut out).unwrap();
+            let text = String::from_utf8(out).unwrap();
+            let mut parsed = parse(&text, format).unwrap();
+            if format == HistoryFormat::Csv {
+                assert!(text.starts_with("word,lookups,first_seen,last_seen,"));
+                assert!(text.contains(",2023-11-14T22:13:20Z,"));
+                assert!(text.contains(",Book: Dune;Dune\\; Messiah \\\\ 2,"));
+                parsed.iter_mut().zip(&records).for_each(|(x, y)| {
+                    assert!(x.entry.is_none());
+                    x.entry = y.entry.clone();
+                });
+            }
+            assert_eq!(parsed, records);
+        }
+        assert!(parse("word,lookups\nrust,1\n", HistoryFormat::Csv).is_err());
+        assert!(parse(r#"{"version": 2, "records": []}"#, HistoryFormat::Json).is_err());
+    }
+
+    #[test]
+    fn merge_histories() {
+        let db = history();
+        let conn = db.lock();
+        let last_seen: i64 = conn
+            .query_row(
+                "SELECT LAST_DATE FROM HISTORY WHERE WORD = 'rust'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let record = |word: &str, definition: &str, first_seen, last_seen| HistoryRecord {
+            word: word.to_owned(),
+            lookups: 3,
+            first_seen,
+            last_seen,
+            source: Some("Youdao".to_owned()),
+            definition: Some(definition.to_owned()),
+            tags: vec!["GRE".to_owned(), "cet4".to_owned()],
+            entry: None,
+            machines: BTreeMap::new(),
+        };
+        let records = [
+            record("rust", "v. 生锈", 1600000000, last_seen + 60),
+            record("crate", "n. 板条箱, \"箱\"", 1650000000, 1650000000),
+            record("ferris", "n. 螃蟹", 1600000000, 1600000000),
+        ];
+
+        let count = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        let report = merge(&conn, &records, true).unwrap();
+        assert_eq!((report.added, report.merged), (1, 2));
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].word, "rust");
+        assert!(report.conflicts[0].imported_kept);
+        assert_eq!(count("SELECT COUNT(*) FROM HISTORY"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM TAGS"), 2);
+
+        assert_eq!(merge(&conn, &records, false).unwrap(), report);
+        let rust: (u32, i64, i64, String, String) = conn
+            .query_row(
+                "SELECT LOOKUPS, DATE, LAST_DATE, SOURCE, DEFINITION FROM HISTORY WHERE WORD = 'rust'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            rust,
+            (
+                5,
+                1600000000,
+                last_seen + 60,
+                "Youdao".to_owned(),
+                "v. 生锈".to_owned()
+            )
+        );
+        // The local look-up of crate is more recent, so its entry stays.
+        let crate_: (u32, i64, Option<String>) = conn
+            .query_row(
+                "SELECT LOOKUPS, DATE, SOURCE FROM HISTORY WHERE WORD = 'crate'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(crate_, (4, 1650000000, None));
+        assert_eq!(count("SELECT COUNT(*) FROM TAGS WHERE TAG = 'GRE'"), 3);
+        assert_eq!(count("SELECT COUNT(*) FROM TAGS WHERE WORD = 'rust'"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM REVIEW"), 0);
+
+        // Importing the same file again counts its look-ups once.
+        merge(&conn, &records, false).unwrap();
+        let lookups = |word: &str| -> u32 {
+            conn.query_row(
+                "SELECT LOOKUPS FROM HISTORY WHERE WORD = ?1",
+                [word],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            (lookups("rust"), lookups("crate"), lookups("ferris")),
+            (5, 4, 3)
+        );
+
+        // Importing exported look-ups again counts them once.
+        let exported = super::records(&conn).unwrap();
+        merge(&conn, &exported, false).unwrap();
+        assert_eq!(super::records(&conn).unwrap(), exported);
+    }
+
+    #[test]
+    fn skip_removed_words() {
+        let db = history();
+        let conn = db.lock();
+        let mut records = super::records(&conn).unwrap();
+        crate::history::remove_words(&conn, &["crate".to_owned()]).unwrap();
+
+        let count = |word: &str| -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM HISTORY WHERE WORD = ?1",
+                [word],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        let report = merge(&conn, &records, true).unwrap();
+        assert_eq!((report.added, report.merged), (0, 1));
+        assert_eq!(report.removed, ["crate"]);
+        assert_eq!(merge(&conn, &records, false).unwrap(), report);
+        assert_eq!(count("crate"), 0);
+
+        // Look-ups after the removal are kept.
+        records[0].last_seen = chrono::Utc::now().timestamp() + 60;
+        let report = merge(&conn, &records[..1], false).unwrap();
+        assert_eq!((report.added, report.removed.len()), (1, 0));
+        assert_eq!(count("crate"), 1);
+    }
+}
@@ -0,0 +1,408 @@
+//! Word lists imported from local files, e.g. the CET6, GRE or Oxford 3000 lists.
+//!
+//! Words of a list are tagged with its name once looked up, whichever
+//! dictionary answered.
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, Utc};
+use clap::ValueEnum;
+use prettytable::{Attr, Cell, Row, Table};
+use rusqlite::Connection;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::db::Database;
+
+/// The layout of a word list file.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordlistFormat {
+    /// One word or phrase per line; `#` starts a comment.
+    Text,
+    /// The word in the first column, with an optional `word` header.
+    Csv,
+    /// An array of words, or of objects with a `word` field, optionally
+    /// under a `words` key.
+    Json,
+}
+
+impl WordlistFormat {
+    /// Guess the format from the file extension, falling back to plain text.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Self::Csv,
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// The distinct words of a word list, in the order they appear.
+pub fn parse(text: &str, format: WordlistFormat) -> Result<Vec<String>> {
+    let words: Vec<String> = match format {
+        WordlistFormat::Text => text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().to_owned())
+            .collect(),
+        WordlistFormat::Csv => csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes())
+            .into_records()
+            .map(|row| Ok(row?.get(0).unwrap_or_default().to_owned()))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .enumerate()
+            .filter(|(i, field)| *i > 0 || !field.trim().eq_ignore_ascii_case("word"))
+            .map(|(_, field)| field)
+            .collect(),
+        WordlistFormat::Json => {
+            let json: Value = serde_json::from_str(text)?;
+            let items = match &json {
+                Value::Object(object) => object.get("words"),
+                _ => Some(&json),
+            };
+            let Some(Value::Array(items)) = items else {
+                bail!("Expected an array of words");
+            };
+            items
+                .iter()
+                .map(|item| match item {
+                    Value::String(word) => Ok(word.clone()),
+                    Value::Object(object) => match object.get("word") {
+                        Some(Value::String(word)) => Ok(word.clone()),
+                        _ => bail!("An entry has no `word`: {}", item),
+                    },
+                    _ => bail!("Not a word: {}", item),
+                })
+                .collect::<Result<_>>()?
+        }
+    };
+
+    let mut seen = HashSet::new();
+    Ok(words
+        .into_iter()
+        .map(|x| x.trim().to_owned())
+        .filter(|x| !x.is_empty() && seen.insert(x.to_lowercase()))
+        .collect())
+}
+
+/// Read and store a word list, replacing any list of the same name, and tag
+/// the words already looked up. Returns how many words the list has.
+pub fn import(
+    conn: &Connection,
+    name: &str,
+    path: &Path,
+    format: Option<WordlistFormat>,
+) -> Result<usize> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let format = format.unwrap_or_else(|| WordlistFormat::from_path(path));
+    let words = parse(&text, format).with_context(|| format!("Failed to parse {:?}", path))?;
+    if words.is_empty() {
+        bail!("{:?} holds no words", path);
+    }
+    save(conn, name, &path.display().to_string(), &words)?;
+    Ok(words.len())
+}
+
+pub(crate) fn save(conn: &Connection, name: &str, source: &str, words: &[String]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    // Words left out of the list lose its tag, unless given it otherwise.
+    tx.execute("DELETE FROM TAGS WHERE TAG = ?1 AND LISTED", [name])?;
+    tx.execute("DELETE FROM WORDLIST_WORDS WHERE LIST = ?1", [name])?;
+    tx.execute(
+        "INSERT OR REPLACE INTO WORDLISTS (NAME, SOURCE, DATE) VALUES (?1, ?2, ?3)",
+        (name, source, Utc::now().timestamp()),
+    )?;
+    {
+        let mut stmt =
+            tx.prepare("INSERT OR IGNORE INTO WORDLIST_WORDS (LIST, WORD) VALUES (?1, ?2)")?;
+        for word in words {
+            stmt.execute((name, word))?;
+        }
+    }
+    tx.execute(
+        "INSERT OR IGNORE INTO TAGS (WORD, TAG, LISTED)
+        SELECT H.WORD, ?1, 1 FROM HISTORY H JOIN WORDLIST_WORDS W
+        ON W.LIST = ?1 AND W.WORD = H.WORD",
+        [name],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Remove a word list and its tag, but not where the tag was also given
+/// otherwise, e.g. by a dictionary or by hand. Returns whether it existed.
+pub fn remove(conn: &Connection, name: &str) -> Result<bool> {
+    let tx = conn.unchecked_transaction()?;
+    let removed = tx.execute("DELETE FROM WORDLISTS WHERE NAME = ?1", [name])?;
+    tx.execute("DELETE FROM WORDLIST_WORDS WHERE LIST = ?1", [name])?;
+    tx.execute("DELETE FROM TAGS WHERE TAG = ?1 AND LISTED", [name])?;
+    tx.commit()?;
+    Ok(removed > 0)
+}
+
+/// An imported word list and how much of it was looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    pub name: String,
+    pub source: String,
+    pub imported_at: DateTime<Utc>,
+    pub words: usize,
+    pub looked_up: usize,
+}
+
+impl Coverage {
+    pub fn percentage(&self) -> f64 {
+        if self.words == 0 {
+            0.0
+        } else {
+            self.looked_up as f64 * 100.0 / self.words as f64
+        }
+    }
+}
+
+/// Every word list by name, or the named one only.
+pub fn coverage(conn: &Connection, name: Option<&str>) -> Result<Vec<Coverage>> {
+    let mut stmt = conn.prepare(
+        "SELECT L.NAME, L.SOURCE, L.DATE, COUNT(W.WORD), COUNT(H.WORD)
+        FROM WORDLISTS L
+        LEFT JOIN WORDLIST_WORDS W ON W.LIST = L.NAME
+        LEFT JOIN HISTORY H ON W.WORD = H.WORD
+        WHERE ?1 IS NULL OR L.NAME = ?1
+        GROUP BY L.NAME ORDER BY L.NAME",
+    )?;
+    let lists = stmt
+        .query_map([name], |row| {
+            Ok(Coverage {
+                name: row.get(0)?,
+                source: row.get(1)?,
+                imported_at: DateTime::from_timestamp(row.get(2)?, 0).unwrap_or_default(),
+                words: row.get(3)?,
+                looked_up: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(lists)
+}
+
+/// The words of a list, each with whether it was looked up.
+pub fn words(conn: &Connection, name: &str) -> Result<Vec<(String, bool)>> {
+    let mut stmt = conn.prepare(
+        "SELECT W.WORD, H.WORD IS NOT NULL FROM WORDLIST_WORDS W
+        LEFT JOIN HISTORY H ON W.WORD = H.WORD
+        WHERE W.LIST = ?1 ORDER BY W.ROWID",
+    )?;
+    let words = stmt
+        .query_map([name], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(words)
+}
+
+/// The names of the word lists `word` is on.
+pub fn lists_of(conn: &Connection, word: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT L.NAME FROM WORDLIST_WORDS W JOIN WORDLISTS L ON L.NAME = W.LIST
+        WHERE W.WORD = ?1 ORDER BY L.NAME",
+    )?;
+    let lists = stmt
+        .query_map([word], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(lists)
+}
+
+/// The imported word lists, to show which ones a looked-up word is on.
+#[derive(Clone)]
+pub struct Wordlists(pub Database);
+
+impl Wordlists {
+    pub fn lists_of(&self, word: &str) -> Result<Vec<String>> {
+        lists_of(&self.0.lock(), word)
+    }
+}
+
+/// Group the digits of `n` by thousands, e.g. `4,500`.
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Print how much of the list was looked up, e.g.
+/// `You have looked up 212 of 4,500 CET6 words (4.7%)`.
+pub fn print_coverage(coverage: &Coverage) {
+    println!(
+        "You have looked up {} of {} {} words ({:.1}%)",
+        thousands(coverage.looked_up),
+        thousands(coverage.words),
+        coverage.name,
+        coverage.percentage()
+    );
+}
+
+/// Print every word list as a table.
+pub fn print_wordlists(lists: &[Coverage]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        [
+            "Name",
+            "Words",
+            "Looked up",
+            "Coverage",
+            "Imported",
+            "Source",
+        ]
+        .into_iter()
+        .map(|x| Cell::new(x).with_style(Attr::Bold))
+        .collect(),
+    ));
+    for list in lists {
+        table.add_row(Row::new(vec![
+            Cell::new(&list.name),
+            Cell::new(&thousands(list.words)),
+            Cell::new(&thousands(list.looked_up)),
+            Cell::new(&format!("{:.1}%", list.percentage())),
+            Cell::new(
+                &list
+                    .imported_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d")
+                    .to_string(),
+            ),
+            Cell::new(&list.source),
+        ]));
+    }
+    table.printstd();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dict::LookUpResultItem;
+    use crate::history::insert_history_record;
+
+    #[test]
+    fn parse_formats() {
+        let text = "# GRE\nabate\n\nAbate\nlook up # phrasal verb\n";
+        assert_eq!(
+            parse(text, WordlistFormat::Text).unwrap(),
+            ["abate", "look up"]
+        );
+
+        let csv = "word,translation\r\n\"rock \"\"n\"\" roll\",摇滚乐\r\n\r\nabate,减轻\n";
+        assert_eq!(
+            parse(csv, WordlistFormat::Csv).unwrap(),
+            ["rock \"n\" roll", "abate"]
+        );
+
+        for json in [
+            r#"["abate", "abound"]"#,
+            r#"{"words": [{"word": "abate", "level": 1}, "abound"]}"#,
+        ] {
+            assert_eq!(
+                parse(json, WordlistFormat::Json).unwrap(),
+                ["abate", "abound"]
+            );
+        }
+        assert!(parse(r#"{"list": []}"#, WordlistFormat::Json).is_err());
+        assert!(parse("[1]", WordlistFormat::Json).is_err());
+
+        assert_eq!(
+            WordlistFormat::from_path(Path::new("cet6.CSV")),
+            WordlistFormat::Csv
+        );
+        assert_eq!(thousands(4500), "4,500");
+        assert_eq!(thousands(212), "212");
+    }
+
+    #[test]
+    fn tag_words_of_lists() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        let look_up = |word: &str| {
+            insert_history_record(&conn, &LookUpResultItem::new(word, String::new())).unwrap()
+        };
+        look_up("abate");
+        look_up("rust");
+        let list: Vec<_> = ["abate", "abound", "Ubiquitous"]
+            .map(str::to_owned)
+            .to_vec();
+        save(&conn, "GRE", "gre.txt", &list).unwrap();
+        look_up("ubiquitous");
+
+        let tagged: Vec<String> = conn
+            .prepare("SELECT WORD FROM TAGS WHERE TAG = 'gre' ORDER BY WORD")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(tagged, ["abate", "ubiquitous"]);
+
+        let lists = coverage(&conn, None).unwrap();
+        assert_eq!((lists[0].words, lists[0].looked_up), (3, 2));
+        assert_eq!(
+            words(&conn, "gre").unwrap()[1],
+            ("abound".to_owned(), false)
+        );
+        assert_eq!(lists_of(&conn, "UBIQUITOUS").unwrap(), ["GRE"]);
+
+        assert!(remove(&conn, "gre").unwrap());
+        assert!(!remove(&conn, "gre").unwrap());
+        assert!(coverage(&conn, None).unwrap().is_empty());
+        let tags: i64 = conn
+            .query_row("SELECT COUNT(*) FROM TAGS", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tags, 0);
+    }
+
+    #[test]
+    fn keep_tags_given_otherwise() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.lock();
+        let mut abate = LookUpResultItem::new("abate", String::new());
+        abate.difficulty_levels = vec!["GRE".to_owned()];
+        insert_history_record(&conn, &abate).unwrap();
+        for word in ["abound", "rust", "ubiquitous"] {
+            insert_history_record(&conn, &LookUpResultItem::new(word, String::new())).unwrap();
+        }
+        let list = |words: &[&str]| words.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        save(
+            &conn,
+            "GRE",
+            "gre.txt",
+            &list(&["abate", "abound", "ubiquitous"]),
+        )
+        .unwrap();
+        save(&conn, "mine", "mine.txt", &list(&["rust"])).unwrap();
+        crate::history::tag_words(&conn, "GRE", &list(&["abound"])).unwrap();
+        crate::history::tag_words(&conn, "Mine", &list(&["rust"])).unwrap();
+        let tagged = |tag: &str| -> Vec<String> {
+            conn.prepare("SELECT WORD FROM TAGS WHERE TAG = ?1 ORDER BY WORD")
+                .unwrap()
+                .query_map([tag], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+        assert_eq!(tagged("GRE"), ["abate", "abound", "ubiquitous"]);
+
+        // Importing the list again drops the tag of words left out.
+        save(&conn, "GRE", "gre.txt", &list(&["abate", "abound"])).unwrap();
+        assert_eq!(tagged("GRE"), ["abate", "abound"]);
+        save(&conn, "GRE", "gre.txt", &list(&["ubiquitous"])).unwrap();
+        assert_eq!(tagged("GRE"), ["abate", "abound", "ubiquitous"]);
+
+        // Tags from the dictionary or given by hand outlive the list.
+        assert!(remove(&conn, "GRE").unwrap());
+        assert_eq!(tagged("GRE"), ["abate", "abound"]);
+        assert!(remove(&conn, "mine").unwrap());
+        assert_eq!(tagged("mine"), ["rust"]);
+    }
+}
@@ -0,0 +1,14 @@
+-- A history database written by dioxionary before schema versioning.
+CREATE TABLE HISTORY (
+WORD TEXT PRIMARY KEY,
+DATE INTEGER NOT NULL,
+CET4 INTEGER,
+CET6 INTEGER,
+TOEFL INTEGER,
+IELTS INTEGER,
+GMAT INTEGER,
+GRE INTEGER,
+SAT INTEGER
+);
+INSERT INTO HISTORY (WORD, DATE, CET4) VALUES ('rust', 1700000000, 1);
+INSERT INTO HISTORY (WORD, DATE, GRE) VALUES ('ephemeral', 1700086400, 1);